@@ -1,7 +1,8 @@
 use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
 use kube::{CustomResourceExt, api::ObjectMeta};
 use kubedal::resource::crd::{
-    DataNode, DataNodeSpec, DataPod, DataReplicaSet, DataReplicaSetSpecTemplate, MatchLabels, Ref,
+    ClusterDataNode, ConfigSourceRef, DataNode, DataNodeClass, DataNodeGrant, DataNodeSpec,
+    DataPod, DataReplicaSet, DataReplicaSetSpecTemplate, MatchLabels, Ref,
 };
 use std::io::Write;
 
@@ -10,6 +11,9 @@ fn main() {
     let data_node_crd = DataNode::crd();
     let data_pod_crd = DataPod::crd();
     let data_replica_set_crd = DataReplicaSet::crd();
+    let data_node_grant_crd = DataNodeGrant::crd();
+    let cluster_data_node_crd = ClusterDataNode::crd();
+    let data_node_class_crd = DataNodeClass::crd();
 
     let path = std::env::args()
         .nth(1)
@@ -24,6 +28,15 @@ fn main() {
     file.write_all(b"\n---\n")
         .expect("Failed to write CRD separator");
     serde_yaml::to_writer(&mut file, &data_replica_set_crd).expect("Failed to serialize CRD");
+    file.write_all(b"\n---\n")
+        .expect("Failed to write CRD separator");
+    serde_yaml::to_writer(&mut file, &data_node_grant_crd).expect("Failed to serialize CRD");
+    file.write_all(b"\n---\n")
+        .expect("Failed to write CRD separator");
+    serde_yaml::to_writer(&mut file, &cluster_data_node_crd).expect("Failed to serialize CRD");
+    file.write_all(b"\n---\n")
+        .expect("Failed to write CRD separator");
+    serde_yaml::to_writer(&mut file, &data_node_class_crd).expect("Failed to serialize CRD");
 
     let demo_data_node = DataNode {
         metadata: ObjectMeta {
@@ -31,11 +44,15 @@ fn main() {
             ..Default::default()
         },
         spec: DataNodeSpec {
-            backend: kubedal::resource::crd::Backend::S3,
-            secret_ref: Some(Ref {
+            backend: kubedal::resource::crd::Backend::from("s3"),
+            data_node_class_name: None,
+            secret_ref: Some(ConfigSourceRef {
                 name: "my-secret".to_string(),
                 namespace: Some("my-secret-namespace".into()),
+                prefix: None,
+                items: Default::default(),
             }),
+            config_map_ref: None,
             read_only: false,
             config: {
                 let mut map = std::collections::HashMap::new();
@@ -45,6 +62,10 @@ fn main() {
                 map
             },
             limit: Some(Quantity("1Gi".to_string())),
+            node_selector: None,
+            layers: None,
+            http: None,
+            web_identity: None,
         },
         status: None,
     };
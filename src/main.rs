@@ -99,6 +99,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     } else {
         // Is a csi driver noder
         let node_service = NodeService::new(client, node_id);
+        #[cfg(feature = "services-git")]
+        tokio::spawn(kubedal::util::git::collect_checkouts());
         builder.add_service(NodeServer::new(node_service))
     };
 
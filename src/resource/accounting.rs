@@ -0,0 +1,246 @@
+use super::controller::{Context, Error};
+use crate::{
+    resource::crd::{DataNode, DataPod, DataPodUsage},
+    util::{cluster_data_node::get_cluster_data_node, opendal::get_operator},
+};
+use futures::TryStreamExt;
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{
+    Resource,
+    api::{Api, Patch, PatchParams, ResourceExt},
+};
+use opendal::Lister;
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::*;
+
+/// Minimum age of a measurement before the DataPod is walked again
+const ACCOUNTING_INTERVAL: Duration = Duration::from_secs(30 * 60);
+/// Pause between two rounds over all DataPods
+const ROUND_PAUSE: Duration = Duration::from_secs(60);
+/// Pause between two DataPods, spreads the listings of a round over time
+const DATA_POD_PAUSE: Duration = Duration::from_secs(1);
+/// Upper bound of the entries listed per second, backends bill per listing request
+const ENTRIES_PER_SECOND: u64 = 1000;
+/// Entries listed for one DataPod per round, larger prefixes are walked over several rounds
+const ENTRIES_PER_STEP: u64 = 10 * ENTRIES_PER_SECOND;
+
+/// Walk over the path of a DataPod that did not list all entries yet
+struct Walk {
+    /// Path the walk was started for, a changed path starts over
+    path: String,
+    lister: Lister,
+    bytes: u64,
+    objects: u64,
+}
+
+/// Measures the content of all DataPods in the background and records it in their status.
+/// DataPods that were never measured go first, then the ones measured longest ago. Each round
+/// lists a bounded number of entries per DataPod and continues unfinished walks in the next
+/// one, so large prefixes do not hold up the others. Results are recorded once a walk finished.
+/// DataPods that can not be measured are remembered and only looked at every `ACCOUNTING_INTERVAL`.
+pub async fn run_accounting(ctx: Arc<Context>) {
+    let mut walks = HashMap::new();
+    let mut skipped = HashMap::new();
+    loop {
+        if let Err(e) = account_round(&ctx, &mut walks, &mut skipped).await {
+            warn!("Accounting round failed: {:?}", e);
+        }
+        // Unfinished walks continue right away, still limited per DataPod
+        let pause = if walks.is_empty() {
+            ROUND_PAUSE
+        } else {
+            DATA_POD_PAUSE
+        };
+        tokio::time::sleep(pause).await;
+    }
+}
+
+/// Advances the walks of all due DataPods by one step, `walks` and the time DataPods were
+/// `skipped` at are kept by DataPod uid
+async fn account_round(
+    ctx: &Context,
+    walks: &mut HashMap<String, Walk>,
+    skipped: &mut HashMap<String, Instant>,
+) -> Result<(), Error> {
+    let mut data_pods = ctx.data_pods().await?;
+
+    // Skipped DataPods are due again like measured ones, their DataNode may have changed
+    skipped.retain(|_, skipped_at| skipped_at.elapsed() < ACCOUNTING_INTERVAL);
+    data_pods.retain(|data_pod| data_pod.uid().is_none_or(|uid| !skipped.contains_key(&uid)));
+
+    let now = Utc::now();
+    let measured_at = |data_pod: &Arc<DataPod>| {
+        data_pod
+            .status
+            .as_ref()
+            .and_then(|status| status.usage.as_ref())
+            .map(|usage| usage.measured_at.0)
+    };
+    data_pods.retain(|data_pod| {
+        measured_at(data_pod).is_none_or(|measured_at| {
+            (now - measured_at).to_std().unwrap_or_default() >= ACCOUNTING_INTERVAL
+        })
+    });
+    data_pods.sort_by_key(measured_at);
+
+    // Walks of deleted or meanwhile measured DataPods are dropped
+    let due = data_pods
+        .iter()
+        .filter_map(|data_pod| data_pod.uid())
+        .collect::<HashSet<_>>();
+    walks.retain(|uid, _| due.contains(uid));
+
+    for data_pod in data_pods {
+        let Some(uid) = data_pod.uid() else {
+            continue;
+        };
+        // Failures only skip the DataPod until the next round and start its walk over
+        let accounted = match step(ctx, &data_pod, walks.remove(&uid)).await {
+            Ok(Progress::Unfinished(walk)) => {
+                walks.insert(uid, walk);
+                Ok(())
+            }
+            Ok(Progress::Finished(usage)) => record(ctx, &data_pod, usage).await,
+            Ok(Progress::Skipped) => {
+                skipped.insert(uid, Instant::now());
+                Ok(())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = accounted {
+            warn!(
+                "Failed to measure DataPod {}/{}: {:?}",
+                data_pod.namespace().unwrap_or_default(),
+                data_pod.name_any(),
+                e
+            );
+        }
+        tokio::time::sleep(DATA_POD_PAUSE).await;
+    }
+
+    Ok(())
+}
+
+enum Progress {
+    /// Nothing to measure, e.g. a git DataNode or a DataPod without path
+    Skipped,
+    Unfinished(Walk),
+    Finished(DataPodUsage),
+}
+
+/// Lists the next `ENTRIES_PER_STEP` entries below the path of the DataPod, starting a walk if
+/// there is none for its current path
+async fn step(ctx: &Context, data_pod: &DataPod, walk: Option<Walk>) -> Result<Progress, Error> {
+    // Paths are set by the DataPod controller
+    let Some(path) = data_pod.spec.path.as_deref() else {
+        return Ok(Progress::Skipped);
+    };
+    let mut walk = match walk.filter(|walk| walk.path == path) {
+        Some(walk) => walk,
+        None => match start(ctx, data_pod, path).await? {
+            Some(walk) => walk,
+            None => return Ok(Progress::Skipped),
+        },
+    };
+
+    for listed in 1..=ENTRIES_PER_STEP {
+        let Some(entry) = walk
+            .lister
+            .try_next()
+            .await
+            .map_err(|e| Error::ReconcilerError(format!("Listing failed: {}", e)))?
+        else {
+            return Ok(Progress::Finished(DataPodUsage {
+                bytes: walk.bytes,
+                objects: walk.objects,
+                measured_at: Time(Utc::now()),
+            }));
+        };
+        if entry.metadata().is_file() {
+            walk.bytes += entry.metadata().content_length();
+            walk.objects += 1;
+        }
+        if listed % ENTRIES_PER_SECOND == 0 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    Ok(Progress::Unfinished(walk))
+}
+
+/// Starts a recursive listing of the path, `None` if the DataNode can not be walked
+async fn start(ctx: &Context, data_pod: &DataPod, path: &str) -> Result<Option<Walk>, Error> {
+    let data_node = if let Some(cluster_ref) = &data_pod.spec.cluster_data_node_ref {
+        get_cluster_data_node(&ctx.client, &cluster_ref.name).await?
+    } else if let Some(node_ref) = &data_pod.spec.data_node_ref {
+        let namespace = node_ref
+            .namespace
+            .clone()
+            .or_else(|| data_pod.namespace())
+            .unwrap_or_default();
+        Api::<DataNode>::namespaced(ctx.client.clone(), &namespace)
+            .get(&node_ref.name)
+            .await?
+    } else {
+        return Ok(None);
+    };
+
+    // Git DataNodes serve a fixed checkout, backends without listing can not be walked
+    let listable = data_node
+        .status
+        .as_ref()
+        .and_then(|status| status.capabilities.as_ref())
+        .is_none_or(|capabilities| capabilities.list);
+    if data_node.spec.backend.scheme() == "git" || !listable {
+        return Ok(None);
+    }
+
+    let operator = get_operator(&ctx.client, &data_node).await?;
+    let prefix = match path.trim_matches('/') {
+        "" => String::new(),
+        path => format!("{}/", path),
+    };
+    let lister = operator
+        .lister_with(&prefix)
+        .recursive(true)
+        .await
+        .map_err(|e| Error::ReconcilerError(format!("Listing failed: {}", e)))?;
+
+    Ok(Some(Walk {
+        path: path.to_string(),
+        lister,
+        bytes: 0,
+        objects: 0,
+    }))
+}
+
+/// Records the usage with its own field manager, the DataPod controller owns the rest of the status
+async fn record(ctx: &Context, data_pod: &DataPod, usage: DataPodUsage) -> Result<(), Error> {
+    let namespace = data_pod
+        .namespace()
+        .ok_or_else(|| Error::ReconcilerError("Missing namespace".into()))?;
+    let data_pod_api: Api<DataPod> = Api::namespaced(ctx.client.clone(), &namespace);
+
+    let patch = Patch::Apply(json!({
+        "apiVersion": DataPod::api_version(&()),
+        "kind": DataPod::kind(&()),
+        "status": {
+            "usage": usage,
+        }
+    }));
+    data_pod_api
+        .patch_status(
+            &data_pod.name_any(),
+            &PatchParams::apply("accounting").force(),
+            &patch,
+        )
+        .await
+        .inspect_err(|e| error!("Failed to patch usage: {:?}", e))?;
+
+    Ok(())
+}
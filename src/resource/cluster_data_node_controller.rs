@@ -0,0 +1,200 @@
+use super::controller::{Context, Error};
+use crate::{
+    resource::crd::{ClusterDataNode, ClusterDataNodeStatus},
+    util::{
+        data_node_class::resolve_data_node,
+        health,
+        quantity::{bytes_to_quantity, quantity_to_bytes},
+    },
+};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::{
+    Resource,
+    api::{Api, Patch, PatchParams, ResourceExt},
+    runtime::{
+        controller::Action,
+        events::{Event, EventType},
+        finalizer::{Event as Finalizer, finalizer},
+    },
+};
+use serde_json::json;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tracing::*;
+
+const CLUSTER_DATASOURCE_FINALIZER: &str = "kubedal.arunaengine.org/clusterdatanode";
+
+#[instrument(skip(ctx, res), fields(trace_id))]
+pub async fn reconcile_cdn(res: Arc<ClusterDataNode>, ctx: Arc<Context>) -> Result<Action, Error> {
+    let cdn: Api<ClusterDataNode> = Api::all(ctx.client.clone());
+
+    info!("Reconciling ClusterDataNode \"{}\"", res.name_any());
+    finalizer(&cdn, CLUSTER_DATASOURCE_FINALIZER, res, |event| async {
+        match event {
+            Finalizer::Apply(doc) => doc.reconcile(ctx.clone()).await,
+            Finalizer::Cleanup(doc) => doc.cleanup(ctx.clone()).await,
+        }
+    })
+    .await
+    .map_err(|e| Error::FinalizerError(Box::new(e)))
+}
+
+pub fn error_policy_cdn(doc: Arc<ClusterDataNode>, error: &Error, _ctx: Arc<Context>) -> Action {
+    warn!("reconcile failed: {:?}, {:?}", error, doc);
+    Action::requeue(Duration::from_secs(5 * 60))
+}
+
+impl ClusterDataNode {
+    // Reconcile (for non-finalizer related changes)
+    async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action, Error> {
+        let client = ctx.client.clone();
+        let name = self.name_any();
+        let cluster_data_node_api: Api<ClusterDataNode> = Api::all(client.clone());
+
+        // Usage and requests are tracked whether or not the backend is reachable,
+        // changes of the DataPods trigger a reconcile
+        let (used, requested) = self.usage_of_data_pods(&ctx).await?;
+
+        // Status patches trigger a reconcile too, the backend is only checked periodically
+        let previous = self.status.as_ref();
+        let previous_conditions = previous
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default();
+        let recently_checked = previous.filter(|status| {
+            status.observed_generation == self.metadata.generation
+                && health::next_probe(&status.conditions).is_some()
+        });
+        let health::Probe {
+            conditions,
+            capabilities,
+        } = match recently_checked {
+            Some(status) => health::Probe {
+                conditions: status.conditions.clone(),
+                capabilities: status.capabilities.clone(),
+            },
+            // Secrets and ConfigMaps are read from the namespace of the controller
+            None => {
+                let data_node =
+                    resolve_data_node(&client, self.to_data_node(client.default_namespace())).await;
+                match data_node {
+                    Ok(data_node) => health::probe(&client, &data_node, previous_conditions).await,
+                    Err(status) => health::Probe {
+                        conditions: health::unresolved(status.message(), previous_conditions),
+                        capabilities: None,
+                    },
+                }
+            }
+        };
+        let available = health::is_ready(&conditions);
+
+        let new_status = ClusterDataNodeStatus {
+            available,
+            used: bytes_to_quantity(used),
+            message: health::ready_message(&conditions),
+            requested,
+            observed_generation: self.metadata.generation,
+            conditions: conditions.clone(),
+            capabilities,
+        };
+        if previous != Some(&new_status) {
+            let ps = PatchParams::apply("cntrlr").force();
+            cluster_data_node_api
+                .patch_status(
+                    &name,
+                    &ps,
+                    &Patch::Apply(json!({
+                        "apiVersion": ClusterDataNode::api_version(&()),
+                        "kind": ClusterDataNode::kind(&()),
+                        "status": new_status,
+                    })),
+                )
+                .await
+                .inspect_err(|e| error!("Failed to patch status: {:?}", e))
+                .map_err(Error::KubeError)?;
+        }
+
+        // Events are only published when a condition changes
+        for condition in health::transitions(previous_conditions, &conditions) {
+            ctx.recorder
+                .publish(&health::transition_event(condition), &self.object_ref(&()))
+                .await
+                .map_err(Error::KubeError)?;
+        }
+        if previous.is_none() && available {
+            ctx.recorder
+                .publish(
+                    &Event {
+                        type_: EventType::Normal,
+                        reason: "Initialized".into(),
+                        note: Some(format!("Init `{}`", name)),
+                        action: "Initialized".into(),
+                        secondary: None,
+                    },
+                    &self.object_ref(&()),
+                )
+                .await
+                .map_err(Error::KubeError)?;
+        }
+
+        // Credentials expire and buckets disappear without events on the ClusterDataNode
+        Ok(Action::requeue(
+            health::next_probe(&conditions).unwrap_or(health::PROBE_INTERVAL),
+        ))
+    }
+
+    /// Sums the measured usage of the DataPods bound to the ClusterDataNode
+    /// and their requests by namespace
+    async fn usage_of_data_pods(
+        &self,
+        ctx: &Context,
+    ) -> Result<(u64, BTreeMap<String, Quantity>), Error> {
+        let mut used = 0;
+        let mut requested: BTreeMap<String, u64> = BTreeMap::new();
+        for data_pod in ctx.data_pods().await? {
+            let bound = data_pod
+                .spec
+                .cluster_data_node_ref
+                .as_ref()
+                .is_some_and(|cluster_ref| cluster_ref.name == self.name_any());
+            if !bound {
+                continue;
+            }
+            if let Some(request) = data_pod.spec.request.as_ref() {
+                *requested
+                    .entry(data_pod.namespace().unwrap_or_default())
+                    .or_default() += quantity_to_bytes(request)?;
+            }
+            if let Some(usage) = data_pod
+                .status
+                .as_ref()
+                .and_then(|status| status.usage.as_ref())
+            {
+                used += usage.bytes;
+            }
+        }
+
+        let requested = requested
+            .into_iter()
+            .map(|(namespace, bytes)| (namespace, bytes_to_quantity(bytes)))
+            .collect();
+        Ok((used, requested))
+    }
+
+    // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
+    async fn cleanup(&self, ctx: Arc<Context>) -> Result<Action, Error> {
+        let oref = self.object_ref(&());
+        ctx.recorder
+            .publish(
+                &Event {
+                    type_: EventType::Normal,
+                    reason: "DeleteRequested".into(),
+                    note: Some(format!("Delete `{}`", self.name_any())),
+                    action: "Deleting".into(),
+                    secondary: None,
+                },
+                &oref,
+            )
+            .await
+            .map_err(Error::KubeError)?;
+        Ok(Action::await_change())
+    }
+}
@@ -1,12 +1,20 @@
-use crate::resource::crd::{DataNode, DataPod, DataReplicaSet};
+use crate::resource::accounting::run_accounting;
+use crate::resource::cluster_data_node_controller::{error_policy_cdn, reconcile_cdn};
+use crate::resource::crd::{ClusterDataNode, DataNode, DataNodeClass, DataPod, DataReplicaSet};
 use crate::resource::data_node_controller::{error_policy_dn, reconcile_dn};
 use crate::resource::data_pod_controller::{error_policy_dp, reconcile_dp};
 use crate::resource::data_replica_set_controller::{error_policy_drs, reconcile_drs};
 use futures::StreamExt;
 use kube::{
+    ResourceExt,
     api::{Api, ListParams},
     client::Client,
-    runtime::{controller::Controller, events::Recorder, watcher::Config},
+    runtime::{
+        controller::Controller,
+        events::Recorder,
+        reflector::{ObjectRef, Store},
+        watcher::Config,
+    },
 };
 use std::sync::Arc;
 use tracing::*;
@@ -18,6 +26,19 @@ pub struct Context {
     pub client: Client,
     /// Event recorder
     pub recorder: Recorder,
+    /// DataPods of the reflector of the DataPod controller
+    pub data_pod_store: Store<DataPod>,
+}
+
+impl Context {
+    /// All DataPods, once the reflector of the DataPod controller listed them
+    pub async fn data_pods(&self) -> Result<Vec<Arc<DataPod>>, Error> {
+        self.data_pod_store
+            .wait_until_ready()
+            .await
+            .map_err(|e| Error::ReconcilerError(format!("DataPod store unavailable: {}", e)))?;
+        Ok(self.data_pod_store.state())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -64,17 +85,65 @@ pub async fn run(client: Client) {
         std::process::exit(1);
     }
 
+    let cdn_api = Api::<ClusterDataNode>::all(client.clone());
+    if let Err(e) = cdn_api.list(&ListParams::default().limit(1)).await {
+        error!("CRD ClusterDataNode is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    let class_api = Api::<DataNodeClass>::all(client.clone());
+    if let Err(e) = class_api.list(&ListParams::default().limit(1)).await {
+        error!("CRD DataNodeClass is not queryable; {e:?}. Is the CRD installed?");
+        info!("Installation: cargo run --bin crdgen | kubectl apply -f -");
+        std::process::exit(1);
+    }
+
+    // Its reflector serves the DataPods to the usage sums and the accounting task
+    let data_pod_controller =
+        Controller::new(data_pod_api.clone(), Config::default().any_semantic());
     let state = Arc::new(Context {
         client: client.clone(),
         recorder: Recorder::new(client.clone(), "kubedal.arunaengine.org".into()),
+        data_pod_store: data_pod_controller.store(),
     });
 
-    let data_node_controller = Controller::new(data_node_api, Config::default().any_semantic())
+    // Usage of the DataPods is measured in the background and summed up by their DataNodes
+    tokio::spawn(run_accounting(state.clone()));
+
+    // DataNodes are reconciled again whenever their class or one of their DataPods changes
+    let data_node_controller = Controller::new(data_node_api, Config::default().any_semantic());
+    let data_nodes = data_node_controller.store();
+    let data_node_controller = data_node_controller
+        .watches(
+            data_pod_api.clone(),
+            Config::default().any_semantic(),
+            |data_pod: DataPod| {
+                let namespace = data_pod.namespace().unwrap_or_default();
+                data_pod.spec.data_node_ref.map(|node_ref| {
+                    ObjectRef::new(&node_ref.name)
+                        .within(node_ref.namespace.as_ref().unwrap_or(&namespace))
+                })
+            },
+        )
+        .watches(
+            class_api,
+            Config::default().any_semantic(),
+            move |class: DataNodeClass| {
+                let class_name = Some(class.name_any());
+                data_nodes
+                    .state()
+                    .into_iter()
+                    .filter(|data_node| data_node.spec.data_node_class_name == class_name)
+                    .map(|data_node| ObjectRef::from_obj(&*data_node))
+                    .collect::<Vec<_>>()
+            },
+        )
         .shutdown_on_signal()
         .run(reconcile_dn, error_policy_dn, state.clone())
         .for_each(|_| futures::future::ready(()));
 
-    let data_pod_controller = Controller::new(data_pod_api, Config::default().any_semantic())
+    let data_pod_controller = data_pod_controller
         .shutdown_on_signal()
         .run(reconcile_dp, error_policy_dp, state.clone())
         .for_each(|_| futures::future::ready(()));
@@ -84,8 +153,24 @@ pub async fn run(client: Client) {
         .run(reconcile_drs, error_policy_drs, state.clone())
         .for_each(|_| futures::future::ready(()));
 
+    let cluster_data_node_controller = Controller::new(cdn_api, Config::default().any_semantic())
+        .watches(
+            data_pod_api,
+            Config::default().any_semantic(),
+            |data_pod: DataPod| {
+                data_pod
+                    .spec
+                    .cluster_data_node_ref
+                    .map(|cluster_ref| ObjectRef::new(&cluster_ref.name))
+            },
+        )
+        .shutdown_on_signal()
+        .run(reconcile_cdn, error_policy_cdn, state.clone())
+        .for_each(|_| futures::future::ready(()));
+
     tokio::join!(
         data_node_controller,
+        cluster_data_node_controller,
         data_pod_controller,
         data_replica_set_controller
     );
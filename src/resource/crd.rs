@@ -1,9 +1,12 @@
 use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
-use kube::CustomResource;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use kube::{CustomResource, ResourceExt};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub enum MountAccess {
@@ -24,6 +27,20 @@ impl fmt::Display for MountAccess {
     }
 }
 
+impl FromStr for MountAccess {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cache" | "cache-read-only" => Ok(MountAccess::CacheReadOnly),
+            "cache-read-write" => Ok(MountAccess::CacheReadWrite),
+            "fuse" | "fuse-read-only" => Ok(MountAccess::FuseReadOnly),
+            "fuse-read-write" => Ok(MountAccess::FuseReadWrite),
+            _ => Err(format!("Unsupported mount type: {}", s)),
+        }
+    }
+}
+
 /// Datanode is a custom resource for defining data location, it is similar to a K8s Node
 /// but for data sources. It can be used to define a data source and its access configuration.
 #[derive(CustomResource, Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -36,31 +53,514 @@ impl fmt::Display for MountAccess {
     namespaced
 )]
 pub struct DataNodeSpec {
-    /// Storage backend scheme (s3, azblob, gcs, etc.)
+    /// Storage backend scheme (s3, azblob, gcs, etc.), defaults to the one of the class
+    #[serde(default, skip_serializing_if = "Backend::is_empty")]
     pub backend: Backend,
 
+    /// DataNodeClass providing defaults for all fields that are not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "dataNodeClassName")]
+    pub data_node_class_name: Option<String>,
+
     /// Is the resource read-only
+    #[serde(default)]
     pub read_only: bool,
 
     /// Secrets / credentials for accessing the backend
     /// Tokens, keys, etc. can be stored in a Kubernetes secret
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "secretRef")]
-    pub secret_ref: Option<Ref>,
+    pub secret_ref: Option<ConfigSourceRef>,
+
+    /// ConfigMap with non-sensitive config options for the backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "configMapRef")]
+    pub config_map_ref: Option<ConfigSourceRef>,
 
-    /// Additional config options for the backend
+    /// Additional config options for the backend, they take precedence over
+    /// the ConfigMap and are overridden by the secret
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub config: HashMap<String, String>,
 
     /// Maximum storage capacity
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub limit: Option<Quantity>,
+
+    /// Labels of the nodes the backend is reachable from, e.g. hosts that have
+    /// the path of an `fs` backend mounted. Volumes are only published there.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "nodeSelector")]
+    pub node_selector: Option<BTreeMap<String, String>>,
+
+    /// OpenDAL layers wrapped around the backend, e.g. to tune flaky or rate-limited services
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layers: Option<LayerSpec>,
+
+    /// TLS and proxy settings of the HTTP client talking to the backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpClientSpec>,
+
+    /// Exchange the service account token of each mounting pod for temporary
+    /// credentials limited to its DataPod, instead of using the secret (s3 only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "webIdentity")]
+    pub web_identity: Option<WebIdentitySpec>,
+}
+
+/// AssumeRoleWithWebIdentity settings, the audience has to be listed in the
+/// `tokenRequests` of the CSIDriver
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebIdentitySpec {
+    /// Role to assume, MinIO ignores it and uses the claims of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_arn: Option<String>,
+
+    /// STS endpoint, defaults to the `endpoint` of the DataNode if set, otherwise AWS STS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sts_endpoint: Option<String>,
+
+    /// Audience of the service account token, defaults to `sts.amazonaws.com`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+
+    /// Lifetime of the temporary credentials in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<u32>,
+}
+
+impl WebIdentitySpec {
+    pub fn audience(&self) -> &str {
+        self.audience.as_deref().unwrap_or("sts.amazonaws.com")
+    }
+}
+
+/// Settings for endpoints behind a private CA, mutual TLS or a proxy
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpClientSpec {
+    /// PEM encoded CA certificates trusted in addition to the system roots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<KeySelector>,
+
+    /// Secret with the PEM encoded client certificate (`tls.crt`) and
+    /// private key (`tls.key`) for mutual TLS, e.g. of type `kubernetes.io/tls`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_certificate_ref: Option<Ref>,
+
+    /// Proxy for all requests, e.g. `http://proxy.example.com:3128`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Comma separated hosts, domains and CIDRs that are reached without the proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+}
+
+/// A key of either a ConfigMap or a Secret
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeySelector {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_map_ref: Option<Ref>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_ref: Option<Ref>,
+
+    /// Key holding the value, defaults to `ca.crt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// Layer settings of a DataNode, applied from the backend outwards in the
+/// order immutable index, timeout, retry, concurrency limit, throttle
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerSpec {
+    /// Retries of failed operations, defaults to 3 retries with exponential backoff
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetrySpec>,
+
+    /// Timeouts of single operations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<TimeoutSpec>,
+
+    /// Maximum number of concurrent requests to the backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrent_limit: Option<usize>,
+
+    /// Maximum throughput of all volumes of the DataNode on one node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub throttle: Option<ThrottleSpec>,
+
+    /// List the backend once and serve all further listings from memory,
+    /// for backends whose content does not change
+    #[serde(default)]
+    pub immutable_index: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RetrySpec {
+    /// Maximum number of retries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_times: Option<usize>,
+
+    /// Delay before the first retry in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_delay_ms: Option<u64>,
+
+    /// Upper bound of the delay between retries in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delay_ms: Option<u64>,
+
+    /// Factor the delay grows by with every retry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factor: Option<f32>,
+
+    /// Randomize delays to spread retries of concurrent operations
+    #[serde(default)]
+    pub jitter: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeoutSpec {
+    /// Timeout of operations without IO, like stat or delete, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_seconds: Option<u64>,
+
+    /// Timeout of single IO calls while reading, writing or listing, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ThrottleSpec {
+    /// Bytes per second
+    pub bandwidth: Quantity,
+
+    /// Largest burst in bytes, at least and by default the bandwidth
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<Quantity>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct DataNodeStatus {
     pub available: bool,
+    /// Sum of the measured usage of the DataPods of the DataNode
     pub used: Quantity,
+    /// Sum of the requests of the DataPods of the DataNode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested: Option<Quantity>,
+    /// Reason why the DataNode is not available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Generation of the DataNodeClass the DataNode was last checked with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "classGeneration")]
+    pub class_generation: Option<i64>,
+    /// Generation of the DataNode the DataNode was last checked with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "observedGeneration")]
+    pub observed_generation: Option<i64>,
+    /// Results of the last check of the backend: Ready, CredentialsValid and Reachable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<DataNodeCondition>,
+    /// Operations supported by the backend, unknown until the operator was built once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<DataNodeCapabilities>,
+}
+
+/// Operations the backend of a DataNode supports, including those emulated by OpenDAL.
+/// The full capability set of OpenDAL, fields missing in older statuses are false.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DataNodeCapabilities {
+    pub stat: bool,
+    /// Conditional stat operations using If-Match are supported
+    pub stat_with_if_match: bool,
+    /// Conditional stat operations using If-None-Match are supported
+    pub stat_with_if_none_match: bool,
+    /// Conditional stat operations using If-Modified-Since are supported
+    pub stat_with_if_modified_since: bool,
+    /// Conditional stat operations using If-Unmodified-Since are supported
+    pub stat_with_if_unmodified_since: bool,
+    /// Cache-Control header override is supported during stat operations
+    pub stat_with_override_cache_control: bool,
+    /// Content-Disposition header override is supported during stat operations
+    pub stat_with_override_content_disposition: bool,
+    /// Content-Type header override is supported during stat operations
+    pub stat_with_override_content_type: bool,
+    /// Versions stat operations are supported
+    pub stat_with_version: bool,
+    /// Cache control information is available in stat response
+    pub stat_has_cache_control: bool,
+    /// Content disposition information is available in stat response
+    pub stat_has_content_disposition: bool,
+    /// Content length information is available in stat response
+    pub stat_has_content_length: bool,
+    /// Content MD5 checksum is available in stat response
+    pub stat_has_content_md5: bool,
+    /// Content range information is available in stat response
+    pub stat_has_content_range: bool,
+    /// Content type information is available in stat response
+    pub stat_has_content_type: bool,
+    /// Content encoding information is available in stat response
+    pub stat_has_content_encoding: bool,
+    /// Entity tag is available in stat response
+    pub stat_has_etag: bool,
+    /// Last modified timestamp is available in stat response
+    pub stat_has_last_modified: bool,
+    /// Version information is available in stat response
+    pub stat_has_version: bool,
+    /// User-defined metadata is available in stat response
+    pub stat_has_user_metadata: bool,
+
+    pub read: bool,
+    /// Conditional read operations using If-Match are supported
+    pub read_with_if_match: bool,
+    /// Conditional read operations using If-None-Match are supported
+    pub read_with_if_none_match: bool,
+    /// Conditional read operations using If-Modified-Since are supported
+    pub read_with_if_modified_since: bool,
+    /// Conditional read operations using If-Unmodified-Since are supported
+    pub read_with_if_unmodified_since: bool,
+    /// Cache-Control header override is supported during read operations
+    pub read_with_override_cache_control: bool,
+    /// Content-Disposition header override is supported during read operations
+    pub read_with_override_content_disposition: bool,
+    /// Content-Type header override is supported during read operations
+    pub read_with_override_content_type: bool,
+    /// Versions read operations are supported
+    pub read_with_version: bool,
+
+    pub write: bool,
+    /// Objects can be written in multiple parts
+    pub write_can_multi: bool,
+    /// Writing empty content is supported
+    pub write_can_empty: bool,
+    /// Objects can be appended to
+    pub write_can_append: bool,
+    /// Content-Type can be specified during write operations
+    pub write_with_content_type: bool,
+    /// Content-Disposition can be specified during write operations
+    pub write_with_content_disposition: bool,
+    /// Content-Encoding can be specified during write operations
+    pub write_with_content_encoding: bool,
+    /// Cache-Control can be specified during write operations
+    pub write_with_cache_control: bool,
+    /// Conditional write operations using If-Match are supported
+    pub write_with_if_match: bool,
+    /// Conditional write operations using If-None-Match are supported
+    pub write_with_if_none_match: bool,
+    /// Write operations can be conditional on object non-existence
+    pub write_with_if_not_exists: bool,
+    /// Custom user metadata can be attached during write operations
+    pub write_with_user_metadata: bool,
+    /// Maximum size supported for multipart uploads
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_multi_max_size: Option<u64>,
+    /// Minimum size required for multipart uploads (except for the last part)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_multi_min_size: Option<u64>,
+    /// Maximum total size supported for write operations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_total_max_size: Option<u64>,
+    /// Content length information is available in write response
+    pub write_has_content_length: bool,
+    /// Last modified timestamp is available in write response
+    pub write_has_last_modified: bool,
+    /// Entity tag is available in write response
+    pub write_has_etag: bool,
+    /// Version information is available in write response
+    pub write_has_version: bool,
+
+    pub create_dir: bool,
+
+    pub delete: bool,
+    /// Versions delete operations are supported
+    pub delete_with_version: bool,
+    /// Maximum size supported for single delete operations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete_max_size: Option<u64>,
+
+    /// Objects can be copied on the backend
+    pub copy: bool,
+
+    pub rename: bool,
+
+    pub list: bool,
+    /// List operations support result limiting
+    pub list_with_limit: bool,
+    /// List operations support continuation from a specific point
+    pub list_with_start_after: bool,
+    /// Listing can walk all objects below a prefix at once
+    pub list_with_recursive: bool,
+    /// Listing with versions included is supported
+    pub list_with_versions: bool,
+    /// Listing with deleted files included is supported
+    pub list_with_deleted: bool,
+    /// Cache control information is available in list response
+    pub list_has_cache_control: bool,
+    /// Content disposition information is available in list response
+    pub list_has_content_disposition: bool,
+    /// Content length information is available in list response
+    pub list_has_content_length: bool,
+    /// Content MD5 checksum is available in list response
+    pub list_has_content_md5: bool,
+    /// Content range information is available in list response
+    pub list_has_content_range: bool,
+    /// Content type information is available in list response
+    pub list_has_content_type: bool,
+    /// Entity tag is available in list response
+    pub list_has_etag: bool,
+    /// Last modified timestamp is available in list response
+    pub list_has_last_modified: bool,
+    /// Version information is available in list response
+    pub list_has_version: bool,
+    /// User-defined metadata is available in list response
+    pub list_has_user_metadata: bool,
+
+    /// Presigned URL generation is supported
+    pub presign: bool,
+    /// URLs can be presigned for reading
+    pub presign_read: bool,
+    /// Presigned URLs for stat operations are supported
+    pub presign_stat: bool,
+    /// URLs can be presigned for writing
+    pub presign_write: bool,
+
+    /// Shared access
+    pub shared: bool,
+
+    /// Blocking operations are supported
+    pub blocking: bool,
+}
+
+/// State of a DataNode observed by checking its backend, similar to a PodCondition
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataNodeCondition {
+    /// Ready, CredentialsValid or Reachable
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// True, False or Unknown
+    pub status: String,
+
+    /// Last time the backend was checked
+    pub last_probe_time: Time,
+
+    /// Last time the status changed
+    pub last_transition_time: Time,
+
+    /// Machine readable reason of the last transition
+    pub reason: String,
+
+    /// Error of the last check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// DataNodeClass holds the defaults of DataNodes that only differ in a few options,
+/// similar to a StorageClass. Fields set on a DataNode take precedence, entries of
+/// its `config` override the ones of the class.
+#[derive(CustomResource, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[kube(
+    group = "kubedal.arunaengine.org",
+    version = "v1alpha1",
+    kind = "DataNodeClass",
+    shortname = "dnc"
+)]
+pub struct DataNodeClassSpec {
+    /// Storage backend scheme (s3, azblob, gcs, etc.)
+    pub backend: Backend,
+
+    /// Secret with credentials, always in the namespace of each DataNode, its namespace is ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "secretRef")]
+    pub secret_ref: Option<ConfigSourceRef>,
+
+    /// ConfigMap with non-sensitive config options, always in the namespace of each DataNode,
+    /// its namespace is ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "configMapRef")]
+    pub config_map_ref: Option<ConfigSourceRef>,
+
+    /// Config options shared by all DataNodes of the class, e.g. the endpoint
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub config: HashMap<String, String>,
+
+    /// Default maximum storage capacity of each DataNode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<Quantity>,
+
+    /// Labels of the nodes the backend is reachable from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "nodeSelector")]
+    pub node_selector: Option<BTreeMap<String, String>>,
+
+    /// OpenDAL layers wrapped around the backend
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layers: Option<LayerSpec>,
+
+    /// TLS and proxy settings of the HTTP client talking to the backend, its Secrets and
+    /// ConfigMaps are always in the namespace of each DataNode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<HttpClientSpec>,
+}
+
+impl DataNodeClass {
+    /// DataNode with the defaults of the class applied. The class name is removed,
+    /// so applying classes to the returned DataNode has no effect. Secrets and
+    /// ConfigMaps of the class are resolved in the namespace of the DataNode.
+    pub fn apply_to(&self, mut data_node: DataNode) -> DataNode {
+        let class = &self.spec;
+        let spec = &mut data_node.spec;
+        if spec.backend.is_empty() {
+            spec.backend = class.backend.clone();
+        }
+        spec.secret_ref = spec.secret_ref.take().or_else(|| {
+            class.secret_ref.clone().map(|source| ConfigSourceRef {
+                namespace: None,
+                ..source
+            })
+        });
+        spec.config_map_ref = spec.config_map_ref.take().or_else(|| {
+            class.config_map_ref.clone().map(|source| ConfigSourceRef {
+                namespace: None,
+                ..source
+            })
+        });
+        let overrides = std::mem::replace(&mut spec.config, class.config.clone());
+        spec.config.extend(overrides);
+        spec.limit = spec.limit.take().or_else(|| class.limit.clone());
+        spec.node_selector = spec
+            .node_selector
+            .take()
+            .or_else(|| class.node_selector.clone());
+        spec.layers = spec.layers.take().or_else(|| class.layers.clone());
+        spec.http = spec.http.take().or_else(|| {
+            class.http.clone().map(|mut http| {
+                if let Some(reference) = http.client_certificate_ref.as_mut() {
+                    reference.namespace = None;
+                }
+                if let Some(ca_bundle) = http.ca_bundle.as_mut() {
+                    let references = [
+                        ca_bundle.config_map_ref.as_mut(),
+                        ca_bundle.secret_ref.as_mut(),
+                    ];
+                    for reference in references.into_iter().flatten() {
+                        reference.namespace = None;
+                    }
+                }
+                http
+            })
+        });
+        spec.data_node_class_name = None;
+        data_node
+    }
 }
 
 #[derive(CustomResource, Default, Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
@@ -80,6 +580,11 @@ pub struct DataPodSpec {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data_node_ref: Option<Ref>,
 
+    /// ClusterDataNode of the DataPod, instead of `dataNodeRef` or `dataNodeSelector`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "clusterDataNodeRef")]
+    pub cluster_data_node_ref: Option<ClusterRef>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[serde(rename = "dataNodeSelector")]
     pub data_node_selector: Option<BTreeMap<String, String>>,
@@ -90,8 +595,188 @@ pub struct DataPodSpec {
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub struct DataPodStatus {
+    #[serde(default)]
     pub available: bool,
+    #[serde(default)]
     pub generated_path: bool,
+    /// Commit the ref of a git DataNode resolved to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Ref the commit was resolved from, it is only resolved again once the ref changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_ref: Option<String>,
+    /// Measured content of the DataPod path, written by the accounting task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<DataPodUsage>,
+}
+
+/// Size of the content below the path of a DataPod at the time of the last walk
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataPodUsage {
+    /// Sum of the object sizes
+    pub bytes: u64,
+
+    /// Number of objects, directories are not counted
+    pub objects: u64,
+
+    /// Time the walk finished
+    pub measured_at: Time,
+}
+
+/// ClusterDataNode is a cluster-scoped DataNode for storage shared by many namespaces.
+/// Its Secrets and ConfigMaps are read from the namespace of the driver, namespaces of
+/// references are ignored. Access is granted to pods with RBAC, not with DataNodeGrants.
+#[derive(CustomResource, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[kube(
+    group = "kubedal.arunaengine.org",
+    version = "v1alpha1",
+    kind = "ClusterDataNode",
+    shortname = "cdn",
+    status = "ClusterDataNodeStatus"
+)]
+pub struct ClusterDataNodeSpec {
+    #[serde(flatten)]
+    pub node: DataNodeSpec,
+
+    /// Maximum sum of the DataPod requests of a single namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "namespaceLimit")]
+    pub namespace_limit: Option<Quantity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ClusterDataNodeStatus {
+    pub available: bool,
+    /// Sum of the measured usage of the DataPods of the ClusterDataNode
+    pub used: Quantity,
+    /// Reason why the ClusterDataNode is not available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Sum of the DataPod requests per consuming namespace
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub requested: BTreeMap<String, Quantity>,
+    /// Generation of the ClusterDataNode the ClusterDataNode was last checked with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "observedGeneration")]
+    pub observed_generation: Option<i64>,
+    /// Results of the last check of the backend: Ready, CredentialsValid and Reachable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<DataNodeCondition>,
+    /// Operations supported by the backend, unknown until the operator was built once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<DataNodeCapabilities>,
+}
+
+/// Marks the DataNodes ClusterDataNodes are converted into, see `ClusterDataNode::to_data_node`
+pub const CLUSTER_DATA_NODE_ANNOTATION: &str = "kubedal.arunaengine.org/cluster-data-node";
+
+impl ClusterDataNode {
+    /// DataNode in the namespace of the driver with the same spec, so that Secrets and
+    /// ConfigMaps are resolved there and the operator is built like for any other DataNode
+    pub fn to_data_node(&self, namespace: &str) -> DataNode {
+        let mut spec = self.spec.node.clone();
+        if let Some(secret_ref) = spec.secret_ref.as_mut() {
+            secret_ref.namespace = None;
+        }
+        if let Some(config_map_ref) = spec.config_map_ref.as_mut() {
+            config_map_ref.namespace = None;
+        }
+        if let Some(http) = spec.http.as_mut() {
+            if let Some(reference) = http.client_certificate_ref.as_mut() {
+                reference.namespace = None;
+            }
+            if let Some(ca_bundle) = http.ca_bundle.as_mut() {
+                for reference in [&mut ca_bundle.config_map_ref, &mut ca_bundle.secret_ref]
+                    .into_iter()
+                    .flatten()
+                {
+                    reference.namespace = None;
+                }
+            }
+        }
+
+        let mut data_node = DataNode::new(&self.name_any(), spec);
+        data_node.metadata.namespace = Some(namespace.to_string());
+        data_node.metadata.uid = self.metadata.uid.clone();
+        data_node.metadata.labels = self.metadata.labels.clone();
+        data_node.metadata.annotations = Some(BTreeMap::from([(
+            CLUSTER_DATA_NODE_ANNOTATION.to_string(),
+            "true".to_string(),
+        )]));
+        data_node.status = self.status.as_ref().map(|status| DataNodeStatus {
+            available: status.available,
+            used: status.used.clone(),
+            requested: None,
+            message: status.message.clone(),
+            class_generation: None,
+            observed_generation: status.observed_generation,
+            conditions: status.conditions.clone(),
+            capabilities: status.capabilities.clone(),
+        });
+        data_node
+    }
+}
+
+impl DataNode {
+    /// Whether the DataNode stands in for a ClusterDataNode
+    pub fn is_cluster_data_node(&self) -> bool {
+        self.annotations()
+            .get(CLUSTER_DATA_NODE_ANNOTATION)
+            .is_some_and(|value| value == "true")
+    }
+
+    /// Relative directory of the state nodes keep per DataNode, like credentials and caches,
+    /// as `<kind>/<namespace>/<name>`. ClusterDataNodes become DataNodes of the driver
+    /// namespace, their kind keeps them apart from DataNodes of the same name there.
+    pub fn state_dir(&self) -> PathBuf {
+        let kind = if self.is_cluster_data_node() {
+            "clusterdatanodes"
+        } else {
+            "datanodes"
+        };
+        PathBuf::from(kind)
+            .join(self.namespace().unwrap_or_default())
+            .join(self.name_any())
+    }
+}
+
+/// DataNodeGrant allows DataPods and PVCs of other namespaces to bind to the DataNodes
+/// in its own namespace, similar to a ReferenceGrant of the Gateway API.
+/// References within the same namespace never require a grant.
+#[derive(CustomResource, Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[kube(
+    group = "kubedal.arunaengine.org",
+    version = "v1alpha1",
+    kind = "DataNodeGrant",
+    shortname = "dng",
+    namespaced
+)]
+pub struct DataNodeGrantSpec {
+    /// Namespaces whose DataPods and PVCs may reference the DataNodes
+    pub from: Vec<GrantNamespace>,
+
+    /// Names of the granted DataNodes, all DataNodes of the namespace if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(rename = "dataNodeNames")]
+    pub data_node_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct GrantNamespace {
+    /// Namespace of the referencing resources, `*` for all namespaces
+    pub namespace: String,
+}
+
+impl DataNodeGrantSpec {
+    /// Whether resources of the namespace may reference the DataNode
+    pub fn allows(&self, namespace: &str, data_node: &str) -> bool {
+        self.from
+            .iter()
+            .any(|from| from.namespace == namespace || from.namespace == "*")
+            && (self.data_node_names.is_empty()
+                || self.data_node_names.iter().any(|name| name == data_node))
+    }
 }
 
 #[derive(CustomResource, Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -139,15 +824,61 @@ pub struct Ref {
     pub namespace: Option<String>,
 }
 
-/// Resource access mode
+/// Reference to a cluster-scoped Kubernetes resource
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
-#[serde(rename_all = "PascalCase")]
-#[non_exhaustive]
-pub enum Backend {
-    /// S3 compatible storage
-    S3,
-    /// HTTP storage, read-only
-    HTTP,
+pub struct ClusterRef {
+    /// Name of the resource
+    pub name: String,
+}
+
+/// Reference to a Secret or ConfigMap whose keys become config options of a DataNode.
+/// Keys are filtered by the prefix first, the remaining keys are then mapped by `items`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct ConfigSourceRef {
+    /// Name of the resource
+    pub name: String,
+
+    /// Namespace of the resource, only the namespace of the DataNode is allowed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// Only use keys starting with this prefix, the prefix is removed from the option name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// Config option for each key, e.g. `AWS_ACCESS_KEY_ID: access_key_id`.
+    /// If set, only the listed keys are used, otherwise all keys under their own name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub items: BTreeMap<String, String>,
+}
+
+/// OpenDAL scheme of a storage backend, matched case-insensitively
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(transparent)]
+pub struct Backend(pub String);
+
+impl Backend {
+    /// Not set, e.g. on DataNodes that take the backend from their class
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Normalized OpenDAL scheme name
+    pub fn scheme(&self) -> String {
+        self.0.to_ascii_lowercase()
+    }
+}
+
+impl From<&str> for Backend {
+    fn from(scheme: &str) -> Self {
+        Backend(scheme.to_string())
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
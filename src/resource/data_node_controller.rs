@@ -1,9 +1,10 @@
 use super::controller::{Context, Error};
 use crate::{
     resource::crd::{DataNode, DataNodeStatus},
-    util::opendal::get_operator,
+    util::data_node_class::get_data_node_class,
+    util::health,
+    util::quantity::{bytes_to_quantity, quantity_to_bytes},
 };
-use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
 use kube::{
     Resource,
     api::{Api, Patch, PatchParams, ResourceExt},
@@ -43,12 +44,19 @@ pub fn error_policy_dn(doc: Arc<DataNode>, error: &Error, _ctx: Arc<Context>) ->
 impl DataNode {
     // Reconcile (for non-finalizer related changes)
     async fn reconcile(&self, ctx: Arc<Context>) -> Result<Action, Error> {
-        if self.status.is_some() {
-            return Ok(Action::requeue(Duration::from_secs(30 * 60)));
-        }
-
-        // If the status is already initialized, do nothing
         let client = ctx.client.clone();
+
+        // Changes of the class are rolled out by checking the DataNode again,
+        // a class that can not be fetched makes the DataNode unavailable
+        let (class, class_error) = match self.spec.data_node_class_name.as_deref() {
+            Some(class_name) => match get_data_node_class(&client, class_name).await {
+                Ok(class) => (Some(class), None),
+                Err(status) => (None, Some(status)),
+            },
+            None => (None, None),
+        };
+        let class_generation = class.as_ref().and_then(|class| class.metadata.generation);
+
         let ns = self
             .namespace()
             .ok_or_else(|| Error::ReconcilerError("Missing namespace".into()))?;
@@ -56,47 +64,163 @@ impl DataNode {
         let name = self.name_any();
         let data_node_api: Api<DataNode> = Api::namespaced(client.clone(), &ns);
 
-        // Check OpenDAL
-        let operator = get_operator(&client, &self).await?;
-        operator
-            .check()
-            .await
-            .map_err(|e| Error::ReconcilerError(e.to_string()))?;
+        // Usage is summed on every reconcile, changes of the DataPods trigger one
+        let (used, requested) = self.usage_of_data_pods(&ctx).await?;
 
-        // always overwrite status object with what we saw
-        let new_status = Patch::Apply(json!({
-            "apiVersion": DataNode::api_version(&()),
-            "kind": DataNode::kind(&()),
-            "status": DataNodeStatus {
-                available: true,
-                used: Quantity("0".into()),
+        // Status patches trigger a reconcile too, unchanged DataNodes are only checked periodically
+        let previous = self.status.as_ref();
+        let previous_conditions = previous
+            .map(|status| status.conditions.as_slice())
+            .unwrap_or_default();
+        let recently_checked = previous.filter(|status| {
+            status.class_generation == class_generation
+                && status.observed_generation == self.metadata.generation
+                && health::next_probe(&status.conditions).is_some()
+        });
+        let health::Probe {
+            conditions,
+            capabilities,
+        } = match (recently_checked, &class_error) {
+            (Some(status), _) => health::Probe {
+                conditions: status.conditions.clone(),
+                capabilities: status.capabilities.clone(),
+            },
+            (None, Some(status)) => health::Probe {
+                conditions: health::unresolved(status.message(), previous_conditions),
+                capabilities: None,
+            },
+            // Check OpenDAL, failures are recorded in the conditions
+            (None, None) => {
+                let data_node = match class.as_ref() {
+                    Some(class) => class.apply_to(self.clone()),
+                    None => self.clone(),
+                };
+                health::probe(&client, &data_node, previous_conditions).await
             }
-        }));
+        };
+        let available = health::is_ready(&conditions);
+        let message = health::ready_message(&conditions);
 
-        trace!("Patching status for {}", name);
-        let ps = PatchParams::apply("cntrlr").force();
-        let _o = data_node_api
-            .patch_status(&name, &ps, &new_status)
-            .await
-            .inspect_err(|e| error!("Failed to patch status: {:?}", e))
-            .map_err(Error::KubeError)?;
+        // always overwrite status object with what we saw
+        let new_status = DataNodeStatus {
+            available,
+            used: bytes_to_quantity(used),
+            requested: Some(bytes_to_quantity(requested)),
+            message: message.clone(),
+            class_generation,
+            observed_generation: self.metadata.generation,
+            conditions: conditions.clone(),
+            capabilities,
+        };
+        if previous != Some(&new_status) {
+            trace!("Patching status for {}", name);
+            let ps = PatchParams::apply("cntrlr").force();
+            let _o = data_node_api
+                .patch_status(
+                    &name,
+                    &ps,
+                    &Patch::Apply(json!({
+                        "apiVersion": DataNode::api_version(&()),
+                        "kind": DataNode::kind(&()),
+                        "status": new_status,
+                    })),
+                )
+                .await
+                .inspect_err(|e| error!("Failed to patch status: {:?}", e))
+                .map_err(Error::KubeError)?;
+        }
 
-        ctx.recorder
-            .publish(
-                &Event {
-                    type_: EventType::Normal,
-                    reason: "Initialized".into(),
-                    note: Some(format!("Init `{}`", self.name_any())),
-                    action: "Initialized".into(),
-                    secondary: None,
+        // Events are only published when a condition changes
+        let mut events = health::transitions(previous_conditions, &conditions)
+            .into_iter()
+            .map(health::transition_event)
+            .collect::<Vec<_>>();
+
+        // DataNodes that were checked before were re-checked for a changed class
+        let rolled_out = previous.is_some_and(|status| status.class_generation != class_generation);
+        if let Some(class) = class.as_ref().filter(|_| rolled_out) {
+            let generation = class_generation.unwrap_or_default();
+            events.push(Event {
+                type_: match message {
+                    Some(_) => EventType::Warning,
+                    None => EventType::Normal,
                 },
-                &self.object_ref(&()),
-            )
-            .await
-            .map_err(Error::KubeError)?;
+                reason: match message {
+                    Some(_) => "ClassRolloutFailed",
+                    None => "ClassRolledOut",
+                }
+                .into(),
+                note: Some(match message.as_ref() {
+                    Some(message) => format!(
+                        "Generation {} of DataNodeClass `{}` failed: {}",
+                        generation,
+                        class.name_any(),
+                        message
+                    ),
+                    None => format!(
+                        "Applied generation {} of DataNodeClass `{}`",
+                        generation,
+                        class.name_any()
+                    ),
+                }),
+                action: "Initialized".into(),
+                secondary: Some(class.object_ref(&())),
+            });
+        } else if previous.is_none() && available {
+            events.push(Event {
+                type_: EventType::Normal,
+                reason: "Initialized".into(),
+                note: Some(format!("Init `{}`", self.name_any())),
+                action: "Initialized".into(),
+                secondary: None,
+            });
+        }
+        for event in events {
+            ctx.recorder
+                .publish(&event, &self.object_ref(&()))
+                .await
+                .map_err(Error::KubeError)?;
+        }
+
+        // Credentials expire and buckets disappear without events on the DataNode
+        Ok(Action::requeue(
+            health::next_probe(&conditions).unwrap_or(health::PROBE_INTERVAL),
+        ))
+    }
+
+    /// Sums the measured usage and the requests of the DataPods bound to the DataNode
+    async fn usage_of_data_pods(&self, ctx: &Context) -> Result<(u64, u64), Error> {
+        let namespace = self.namespace();
+        let (mut used, mut requested) = (0, 0);
+        for data_pod in ctx.data_pods().await? {
+            let bound = data_pod
+                .spec
+                .data_node_ref
+                .as_ref()
+                .is_some_and(|node_ref| {
+                    node_ref.name == self.name_any()
+                        && node_ref
+                            .namespace
+                            .as_ref()
+                            .or(data_pod.metadata.namespace.as_ref())
+                            == namespace.as_ref()
+                });
+            if !bound {
+                continue;
+            }
+            if let Some(request) = data_pod.spec.request.as_ref() {
+                requested += quantity_to_bytes(request)?;
+            }
+            if let Some(usage) = data_pod
+                .status
+                .as_ref()
+                .and_then(|status| status.usage.as_ref())
+            {
+                used += usage.bytes;
+            }
+        }
 
-        // If no events were received, check back every 30 minutes
-        Ok(Action::requeue(Duration::from_secs(30 * 60)))
+        Ok((used, requested))
     }
 
     // Finalizer cleanup (the object was deleted, ensure nothing is orphaned)
@@ -1,4 +1,7 @@
-use crate::resource::crd::{DataNode, DataPod, DataPodSpec, DataPodStatus, Ref};
+use crate::resource::crd::{ClusterDataNode, DataNode, DataPod, DataPodSpec, DataPodStatus, Ref};
+use crate::util::data_node_class::resolve_data_node;
+use crate::util::grant::check_data_node_grant;
+use crate::util::paths::{cluster_data_pod_path, is_below_root};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use kube::api::ListParams;
 use kube::{
@@ -12,6 +15,7 @@ use kube::{
 };
 use serde_json::json;
 use std::{sync::Arc, time::Duration};
+use tonic::Code;
 use tracing::*;
 
 const SYNC_FINALIZER: &str = "kubedal.arunaengine.org/datapod";
@@ -70,11 +74,36 @@ impl DataPod {
         } else {
             (self.spec.path.clone().unwrap(), false) // Cannot fail as the other branch collects all the other cases.
         };
+        if !is_below_root(new_path.trim_start_matches('/')) {
+            return Err(Error::ReconcilerError(format!(
+                "DataPod {} has an invalid path: {}",
+                &name, new_path
+            )));
+        }
+
+        // DataPods of ClusterDataNodes are kept below a directory of their namespace
+        let new_path = match &self.spec.cluster_data_node_ref {
+            Some(_) => cluster_data_pod_path(&ns, &new_path).unwrap_or(new_path),
+            None => new_path,
+        };
+
+        // ClusterDataNodes are resolved in the namespace of the controller, where their Secrets are
+        let cluster_data_node = match &self.spec.cluster_data_node_ref {
+            Some(cluster_ref) => Some(
+                Api::<ClusterDataNode>::all(client.clone())
+                    .get(&cluster_ref.name)
+                    .await
+                    .map_err(Error::KubeError)?,
+            ),
+            None => None,
+        };
 
         // Set owner reference and datNodeRef depending on ref/selector
-        let data_node_api: Api<DataNode> = Api::namespaced(client.clone(), &ns);
-        let data_node = if let Some(node_ref) = &self.spec.data_node_ref {
-            data_node_api
+        let data_node = if let Some(cluster_data_node) = &cluster_data_node {
+            cluster_data_node.to_data_node(client.default_namespace())
+        } else if let Some(node_ref) = &self.spec.data_node_ref {
+            let node_namespace = node_ref.namespace.as_ref().unwrap_or(&ns);
+            Api::<DataNode>::namespaced(client.clone(), node_namespace)
                 .get(&node_ref.name)
                 .await
                 .map_err(Error::KubeError)?
@@ -86,7 +115,7 @@ impl DataPod {
                 .map(|(k, v)| format!("{}={}", k, v))
                 .collect::<Vec<_>>()
                 .join(",");
-            data_node_api
+            Api::<DataNode>::namespaced(client.clone(), &ns)
                 .list(&ListParams::default().labels(&selector_string))
                 .await?
                 .items
@@ -100,15 +129,78 @@ impl DataPod {
             )));
         };
 
-        // Patch owner ref
-        let owner = data_node.owner_ref(&()).ok_or_else(|| {
-            Error::ReconcilerError(format!(
-                "Failed to create owner ref from DataNode: {}",
-                data_node.name_any()
-            ))
-        })?;
+        let data_node = resolve_data_node(&client, data_node).await?;
 
-        // Patch everything at once
+        // DataNodes of other namespaces have to be granted to the namespace of the DataPod,
+        // ClusterDataNodes are available to all namespaces and checked with RBAC on publish
+        let node_namespace = data_node.namespace().unwrap_or_default();
+        let granted = match &cluster_data_node {
+            Some(_) => Ok(()),
+            None => {
+                check_data_node_grant(&client, &ns, &node_namespace, &data_node.name_any()).await
+            }
+        };
+        if let Err(status) = granted {
+            if status.code() == Code::PermissionDenied {
+                ctx.recorder
+                    .publish(
+                        &Event {
+                            type_: EventType::Warning,
+                            reason: "ReferenceNotGranted".into(),
+                            note: Some(status.message().to_string()),
+                            action: "ReconciledSync".into(),
+                            secondary: Some(data_node.object_ref(&())),
+                        },
+                        &self.object_ref(&()),
+                    )
+                    .await
+                    .map_err(Error::KubeError)?;
+            }
+            return Err(status.into());
+        }
+
+        // Patch owner ref, owners in other namespaces would be treated as absent by the garbage collector
+        let owners = if let Some(cluster_data_node) = &cluster_data_node {
+            vec![cluster_data_node.owner_ref(&()).ok_or_else(|| {
+                Error::ReconcilerError(format!(
+                    "Failed to create owner ref from ClusterDataNode: {}",
+                    cluster_data_node.name_any()
+                ))
+            })?]
+        } else if node_namespace == ns {
+            vec![data_node.owner_ref(&()).ok_or_else(|| {
+                Error::ReconcilerError(format!(
+                    "Failed to create owner ref from DataNode: {}",
+                    data_node.name_any()
+                ))
+            })?]
+        } else {
+            Vec::new()
+        };
+
+        // Record the commit git DataNodes resolve to, so that all nodes publish the same content.
+        // The recorded commit is kept until the ref changes, branches do not move under volumes
+        #[cfg(feature = "services-git")]
+        let (commit, resolved_ref) = if data_node.spec.backend.scheme() == "git" {
+            let cfg = crate::util::opendal::data_node_config(&client, &data_node).await?;
+            let reference = crate::util::git::configured_ref(&cfg).to_string();
+            let recorded = data_pod
+                .status
+                .as_ref()
+                .filter(|status| status.resolved_ref.as_ref() == Some(&reference))
+                .and_then(|status| status.commit.clone());
+            let commit = match recorded {
+                Some(commit) => commit,
+                None => crate::util::git::resolve_commit(&cfg).await?,
+            };
+            (Some(commit), Some(reference))
+        } else {
+            (None, None)
+        };
+        #[cfg(not(feature = "services-git"))]
+        let (commit, resolved_ref) = (None, None);
+
+        // Patch metadata and spec at once
         let data_pod_clone = data_pod.clone();
         let overall_patch = Patch::Apply(json!({
             "apiVersion": DataPod::api_version(&()),
@@ -116,25 +208,39 @@ impl DataPod {
             "metadata": ObjectMeta {
                 name: Some(data_pod_clone.metadata.name.expect("DataPod is missing name.")),
                 namespace: data_pod_clone.metadata.namespace,
-                owner_references: Some(vec![owner]),
+                owner_references: Some(owners),
                 ..Default::default()
             },
             "spec": DataPodSpec {
                 path: Some(new_path),
-                data_node_ref: Some(Ref {
+                data_node_ref: cluster_data_node.is_none().then(|| Ref {
                     name: data_node.name_any(),
-                    namespace: Some(data_node.metadata.namespace.unwrap_or_default()),
+                    namespace: Some(node_namespace),
                 }),
+                cluster_data_node_ref: data_pod_clone.spec.cluster_data_node_ref,
                 data_node_selector: data_pod_clone.spec.data_node_selector,
                 request: data_pod_clone.spec.request,
             },
+        }));
+        data_pod_api
+            .patch(&name, &PatchParams::apply("cntrlr").force(), &overall_patch)
+            .await?;
+
+        // The status is a subresource, it is dropped from patches of the DataPod itself
+        let status_patch = Patch::Apply(json!({
+            "apiVersion": DataPod::api_version(&()),
+            "kind": DataPod::kind(&()),
             "status": DataPodStatus {
                 available: true,
                 generated_path: generated,
+                commit,
+                resolved_ref,
+                // Written by the accounting task
+                usage: None,
             }
         }));
         let new_data_pod = data_pod_api
-            .patch(&name, &PatchParams::apply("cntrlr").force(), &overall_patch)
+            .patch_status(&name, &PatchParams::apply("cntrlr").force(), &status_patch)
             .await?;
 
         if data_pod != new_data_pod.to_owned() {
@@ -1,6 +1,7 @@
 use super::controller::{Context, Error};
 use crate::resource::crd::{DataNode, DataPod, MountAccess};
 use crate::resource::crd::{DataPodSpec, DataReplicaSet, Ref};
+use crate::util::volume_context::pvc_annotations;
 use k8s_openapi::api::core::v1::{
     Container, EnvVar, PersistentVolumeClaim, PersistentVolumeClaimSpec,
     PersistentVolumeClaimVolumeSource, Pod, PodSpec, Volume, VolumeMount,
@@ -241,6 +242,7 @@ async fn create_replica_data_pod(
                 name: data_node.name_any(),
                 namespace: data_node.namespace(),
             }),
+            cluster_data_node_ref: None,
             data_node_selector: None,
             request: None, //TODO: ...
         },
@@ -287,32 +289,13 @@ async fn create_replica_pvc(
             )),
             namespace: data_replica_set.namespace(),
             owner_references: Some(vec![replica_owner.clone()]),
-            annotations: Some(
-                vec![
-                    (
-                        "kubedal.arunaengine.org/data-node-name".to_string(),
-                        data_node.name_any(),
-                    ),
-                    (
-                        "kubedal.arunaengine.org/data-node-namespace".to_string(),
-                        data_node.metadata.namespace.clone().unwrap_or_default(),
-                    ),
-                    (
-                        "kubedal.arunaengine.org/data-pod-name".to_string(),
-                        data_pod.name_any(),
-                    ),
-                    (
-                        "kubedal.arunaengine.org/data-pod-namespace".to_string(),
-                        data_pod.metadata.namespace.clone().unwrap_or_default(),
-                    ),
-                    (
-                        "kubedal.arunaengine.org/mount".to_string(),
-                        mount_access.to_string(),
-                    ),
-                ]
-                .into_iter()
-                .collect(),
-            ),
+            annotations: Some(pvc_annotations(
+                data_node.name_any(),
+                data_node.metadata.namespace.clone().unwrap_or_default(),
+                data_pod.name_any(),
+                data_pod.metadata.namespace.clone().unwrap_or_default(),
+                &mount_access,
+            )),
             ..Default::default()
         },
         spec: Some(PersistentVolumeClaimSpec {
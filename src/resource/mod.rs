@@ -1,3 +1,5 @@
+pub mod accounting;
+pub mod cluster_data_node_controller;
 pub mod controller;
 pub mod crd;
 pub mod data_node_controller;
@@ -1,11 +1,15 @@
-use k8s_openapi::api::core::v1::PersistentVolumeClaim;
-use kube::api::{ListParams, ObjectMeta};
-use kube::{Api, Client};
+use k8s_openapi::api::core::v1::{PersistentVolume, PersistentVolumeClaim};
+use kube::api::{ListParams, ObjectMeta, Patch, PatchParams};
+use kube::{Api, Client, ResourceExt};
+use serde_json::json;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use tonic::{Request, Response, Status};
 
 use crate::csi::controller_server::Controller;
+use crate::csi::validate_volume_capabilities_response::Confirmed;
+use crate::csi::volume_capability::AccessType;
+use crate::csi::volume_capability::access_mode::Mode;
 use crate::csi::{
     ControllerExpandVolumeRequest, ControllerExpandVolumeResponse,
     ControllerGetCapabilitiesRequest, ControllerGetCapabilitiesResponse,
@@ -14,13 +18,34 @@ use crate::csi::{
     ControllerUnpublishVolumeResponse, CreateVolumeRequest, CreateVolumeResponse,
     DeleteVolumeRequest, DeleteVolumeResponse, GetCapacityRequest, GetCapacityResponse,
     ListVolumesRequest, ListVolumesResponse, ValidateVolumeCapabilitiesRequest,
-    ValidateVolumeCapabilitiesResponse, Volume,
+    ValidateVolumeCapabilitiesResponse, Volume, VolumeCapability,
+};
+use crate::resource::crd::{ClusterDataNode, DataNode, DataPod, MountAccess};
+use crate::util::capabilities::{check_mount, check_snapshot};
+use crate::util::cluster_data_node::get_cluster_data_node;
+use crate::util::data_node_class::resolve_data_node;
+use crate::util::grant::check_data_node_grant;
+use crate::util::operation_lock::OperationLocks;
+use crate::util::quantity::{bytes_to_quantity, quantity_to_bytes};
+use crate::util::topology::accessible_topology;
+use crate::util::volume_attributes::{
+    MutableAttributes, find_persistent_volume, persistent_volume_context,
+};
+use crate::util::volume_context::{
+    DATA_NODE_KIND_KEY, DATA_NODE_NAME_KEY, DATA_NODE_NAMESPACE_KEY, VolumeContext,
+    parse_data_node_kind,
 };
 
+/// Capacity reported for StorageClasses without a DataNode or DataNodes without limit
+const UNLIMITED_CAPACITY: i64 = 1024 * 1024 * 1024 * 1024; // 1 TB
+
+#[derive(Clone)]
 pub struct ControllerService {
     // In-memory storage for volumes (for a dummy driver)
     volumes: Arc<Mutex<HashMap<String, Volume>>>,
     client: Client,
+    // Serializes operations per volume
+    operations: OperationLocks,
 }
 
 impl ControllerService {
@@ -31,8 +56,182 @@ impl ControllerService {
         Self {
             client,
             volumes: Arc::new(Mutex::new(HashMap::new())),
+            operations: OperationLocks::default(),
         }
     }
+
+    async fn provision_volume(&self, request: CreateVolumeRequest) -> Result<Volume, Status> {
+        {
+            let volumes = self.volumes.lock().unwrap();
+            // Check if volume already exists
+            if volumes.contains_key(&request.name) {
+                let existing_volume = volumes.get(&request.name).unwrap().clone();
+                return Ok(existing_volume);
+            }
+        }
+
+        let namespace = request.parameters.get("resourceNamespace").ok_or_else(|| {
+            tracing::error!("Resource namespace not provided in parameters");
+            Status::invalid_argument("Resource namespace not provided in parameters")
+        })?;
+
+        let client = self.client.clone();
+        let pvc_meta = get_pvc(client.clone(), namespace, &request.name).await?;
+        let mut context = VolumeContext::from_pvc_meta(&pvc_meta)?;
+
+        // Initial attributes from a VolumeAttributesClass
+        context.apply(MutableAttributes::from_parameters(
+            &request.mutable_parameters,
+        )?);
+
+        tracing::trace!("PVC meta: {:?}", pvc_meta);
+        tracing::trace!("Volume context: {:?}", context);
+
+        tracing::info!(
+            "Creating volume '{}' with annotations '{:?}'",
+            request.name,
+            pvc_meta.annotations.unwrap_or_default()
+        );
+
+        // The external provisioner names the PersistentVolume after the volume, so the
+        // name doubles as volume id and the node can look the PersistentVolume up by it
+        let volume_id = request.name.clone();
+        let capacity_bytes = request
+            .capacity_range
+            .map_or(5 * 1024 * 1024 * 1024, |range| range.required_bytes);
+
+        let data_node = get_data_node(&client, &context).await?;
+
+        // PVCs may only reference DataNodes of other namespaces that were granted to them
+        if !context.cluster_data_node {
+            check_data_node_grant(
+                &client,
+                namespace,
+                &context.data_node_namespace,
+                &context.data_node_name,
+            )
+            .await?;
+        }
+
+        // Mount modes the backend can not serve would only fail on the node
+        check_mount(&data_node, &context.mount)?;
+
+        // Restrict the volume to the nodes the backend is reachable from
+        let accessible_topology = accessible_topology(&client, &data_node).await?;
+
+        let volume = Volume {
+            volume_id: volume_id.clone(),
+            capacity_bytes,
+            content_source: None,
+            accessible_topology,
+            volume_context: context.to_map(),
+        };
+
+        let mut volumes = self.volumes.lock().unwrap();
+        volumes.insert(request.name, volume.clone());
+
+        Ok(volume)
+    }
+
+    async fn expand_volume(&self, volume_id: String, capacity_bytes: i64) -> Result<(), Status> {
+        // The PersistentVolume carries the context, volumes in memory are lost on restarts
+        let pv = find_persistent_volume(self.client.clone(), &volume_id)
+            .await?
+            .ok_or_else(|| Status::not_found(format!("Volume '{}' not found", volume_id)))?;
+        let volume_context = persistent_volume_context(&pv)?;
+
+        let data_pod_api: Api<DataPod> =
+            Api::namespaced(self.client.clone(), &volume_context.data_pod_namespace);
+        let data_pod = data_pod_api
+            .get(&volume_context.data_pod_name)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error getting DataPod: {:?}", e);
+                Status::internal("Error getting DataPod")
+            })?;
+
+        // Capacity is a logical quota: check the headroom on the DataNode and update the request
+        check_headroom(self.client.clone(), &data_pod, capacity_bytes as u64).await?;
+
+        let patch = Patch::Merge(json!({
+            "spec": {
+                "request": bytes_to_quantity(capacity_bytes as u64),
+            }
+        }));
+        data_pod_api
+            .patch(
+                &volume_context.data_pod_name,
+                &PatchParams::default(),
+                &patch,
+            )
+            .await
+            .map_err(|e| {
+                tracing::error!("Error patching DataPod request: {:?}", e);
+                Status::internal("Error patching DataPod request")
+            })?;
+
+        {
+            let mut volumes = self.volumes.lock().unwrap();
+            if let Some(volume) = volumes.values_mut().find(|v| v.volume_id == volume_id) {
+                volume.capacity_bytes = capacity_bytes;
+            }
+        }
+
+        tracing::info!(
+            "Expanded volume '{}' to {} bytes",
+            volume_id,
+            capacity_bytes
+        );
+
+        Ok(())
+    }
+
+    async fn modify_volume(
+        &self,
+        volume_id: String,
+        attributes: MutableAttributes,
+    ) -> Result<(), Status> {
+        let modified = attributes.clone().into_context();
+
+        // Node plugins read modifications from the PersistentVolume annotations,
+        // the mount mode is applied on the next publish, the throughput also live
+        let pv = find_persistent_volume(self.client.clone(), &volume_id)
+            .await?
+            .ok_or_else(|| Status::not_found(format!("Volume '{}' not found", volume_id)))?;
+
+        // Mount modes the DataNode can not serve are refused like on creation
+        let mut context = persistent_volume_context(&pv)?;
+        context.apply(attributes.clone());
+        let data_node = get_data_node(&self.client, &context).await?;
+        check_mount(&data_node, &context.mount)?;
+
+        let pv_api: Api<PersistentVolume> = Api::all(self.client.clone());
+        let patch = Patch::Merge(json!({
+            "metadata": {
+                "annotations": modified,
+            }
+        }));
+        pv_api
+            .patch(&pv.name_any(), &PatchParams::default(), &patch)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error patching PersistentVolume: {:?}", e);
+                Status::internal("Error patching PersistentVolume")
+            })?;
+
+        {
+            let mut volumes = self.volumes.lock().unwrap();
+            if let Some(volume) = volumes.values_mut().find(|v| v.volume_id == volume_id) {
+                let mut context = VolumeContext::parse(&volume.volume_context)?;
+                context.apply(attributes);
+                volume.volume_context = context.to_map();
+            }
+        }
+
+        tracing::info!("Modified volume '{}' with {:?}", volume_id, modified);
+
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
@@ -63,8 +262,36 @@ impl Controller for ControllerService {
             )),
         };
 
+        let expand = ControllerServiceCapability {
+            r#type: Some(crate::csi::controller_service_capability::Type::Rpc(
+                crate::csi::controller_service_capability::Rpc {
+                    r#type: crate::csi::controller_service_capability::rpc::Type::ExpandVolume
+                        .into(),
+                },
+            )),
+        };
+
+        let modify = ControllerServiceCapability {
+            r#type: Some(crate::csi::controller_service_capability::Type::Rpc(
+                crate::csi::controller_service_capability::Rpc {
+                    r#type: crate::csi::controller_service_capability::rpc::Type::ModifyVolume
+                        .into(),
+                },
+            )),
+        };
+
+        // The external-provisioner publishes it as CSIStorageCapacity for the scheduler
+        let capacity = ControllerServiceCapability {
+            r#type: Some(crate::csi::controller_service_capability::Type::Rpc(
+                crate::csi::controller_service_capability::Rpc {
+                    r#type: crate::csi::controller_service_capability::rpc::Type::GetCapacity
+                        .into(),
+                },
+            )),
+        };
+
         let response = ControllerGetCapabilitiesResponse {
-            capabilities: vec![create_delete, snapshot],
+            capabilities: vec![create_delete, snapshot, expand, modify, capacity],
         };
 
         Ok(Response::new(response))
@@ -82,51 +309,12 @@ impl Controller for ControllerService {
             return Err(Status::invalid_argument("Volume name cannot be empty"));
         }
 
-        {
-            let volumes = self.volumes.lock().unwrap();
-            // Check if volume already exists
-            if volumes.contains_key(&request.name) {
-                let existing_volume = volumes.get(&request.name).unwrap().clone();
-                return Ok(Response::new(CreateVolumeResponse {
-                    volume: Some(existing_volume),
-                }));
-            }
-        }
-
-        let namespace = request.parameters.get("resourceNamespace").ok_or_else(|| {
-            tracing::error!("Resource namespace not provided in parameters");
-            Status::invalid_argument("Resource namespace not provided in parameters")
-        })?;
-
-        let client = self.client.clone();
-        let pvc_meta = get_pvc(client.clone(), namespace, &request.name).await?;
-        let context = get_config_from_pvc_meta(pvc_meta.clone()).await?;
-
-        tracing::trace!("PVC meta: {:?}", pvc_meta);
-        tracing::trace!("Volume context: {:?}", context);
-
-        tracing::info!(
-            "Creating volume '{}' with annotations '{:?}'",
-            request.name,
-            pvc_meta.annotations.unwrap_or_default()
-        );
-
-        // Create a new volume entry
-        let volume_id = format!("kubedal-{}", uuid::Uuid::new_v4());
-        let capacity_bytes = request
-            .capacity_range
-            .map_or(5 * 1024 * 1024 * 1024, |range| range.required_bytes);
-
-        let volume = Volume {
-            volume_id: volume_id.clone(),
-            capacity_bytes,
-            content_source: None,
-            accessible_topology: vec![],
-            volume_context: context,
-        };
-
-        let mut volumes = self.volumes.lock().unwrap();
-        volumes.insert(request.name, volume.clone());
+        // Concurrent calls for the same name are aborted, the volume id is not known yet
+        let guard = self.operations.try_lock(&request.name)?;
+        let service = self.clone();
+        let volume = guard
+            .run_detached(async move { service.provision_volume(request).await })
+            .await?;
 
         Ok(Response::new(CreateVolumeResponse {
             volume: Some(volume),
@@ -147,6 +335,8 @@ impl Controller for ControllerService {
 
         tracing::info!("Deleting volume with ID '{}'", volume_id);
 
+        let _guard = self.operations.try_lock(&volume_id)?;
+
         // In a real driver, we would clean up any resources associated with this volume
         // For our dummy driver, we'll just remove it from our in-memory map
 
@@ -190,12 +380,53 @@ impl Controller for ControllerService {
     #[tracing::instrument(skip(self))]
     async fn validate_volume_capabilities(
         &self,
-        _request: Request<ValidateVolumeCapabilitiesRequest>,
+        request: Request<ValidateVolumeCapabilitiesRequest>,
     ) -> Result<Response<ValidateVolumeCapabilitiesResponse>, Status> {
-        // For a dummy driver, we'll say all capabilities are supported
+        let request = request.into_inner();
+
+        if request.volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID cannot be empty"));
+        }
+        if request.volume_capabilities.is_empty() {
+            return Err(Status::invalid_argument(
+                "Volume capabilities cannot be empty",
+            ));
+        }
+
+        // The context passed by the CO is preferred, it survives restarts of the controller
+        let volume_context = if request.volume_context.is_empty() {
+            let volumes = self.volumes.lock().unwrap();
+            volumes
+                .values()
+                .find(|v| v.volume_id == request.volume_id)
+                .map(|v| v.volume_context.clone())
+                .ok_or_else(|| {
+                    Status::not_found(format!("Volume '{}' not found", request.volume_id))
+                })?
+        } else {
+            request.volume_context.clone()
+        };
+        let context = VolumeContext::parse(&volume_context)?;
+        let data_node = get_data_node(&self.client, &context).await?;
+
+        // Unsupported capabilities are reported in the message, not as error
+        if let Err(status) =
+            check_volume_capabilities(&data_node, &context, &request.volume_capabilities)
+        {
+            return Ok(Response::new(ValidateVolumeCapabilitiesResponse {
+                confirmed: None,
+                message: status.message().to_string(),
+            }));
+        }
+
         Ok(Response::new(ValidateVolumeCapabilitiesResponse {
-            confirmed: None,
-            message: "All capabilities are supported".into(),
+            confirmed: Some(Confirmed {
+                volume_context: request.volume_context,
+                volume_capabilities: request.volume_capabilities,
+                parameters: request.parameters,
+                mutable_parameters: request.mutable_parameters,
+            }),
+            message: String::new(),
         }))
     }
 
@@ -223,11 +454,67 @@ impl Controller for ControllerService {
     #[tracing::instrument(skip(self))]
     async fn get_capacity(
         &self,
-        _request: Request<GetCapacityRequest>,
+        request: Request<GetCapacityRequest>,
     ) -> Result<Response<GetCapacityResponse>, Status> {
-        // For a dummy driver, we'll report a fixed large capacity
+        let request = request.into_inner();
+
+        // StorageClasses naming a DataNode report its headroom, others a fixed large capacity
+        let Some(data_node_name) = request.parameters.get(DATA_NODE_NAME_KEY) else {
+            return Ok(Response::new(GetCapacityResponse {
+                available_capacity: UNLIMITED_CAPACITY,
+                ..Default::default()
+            }));
+        };
+        let cluster_data_node = request
+            .parameters
+            .get(DATA_NODE_KIND_KEY)
+            .map(|value| parse_data_node_kind(value))
+            .transpose()?
+            .unwrap_or(false);
+        let data_node = if cluster_data_node {
+            get_cluster_data_node(&self.client, data_node_name).await?
+        } else {
+            let namespace = request
+                .parameters
+                .get(DATA_NODE_NAMESPACE_KEY)
+                .or(request.parameters.get("resourceNamespace"))
+                .ok_or_else(|| {
+                    Status::invalid_argument(format!("{} not provided", DATA_NODE_NAMESPACE_KEY))
+                })?;
+            let data_node_api: Api<DataNode> = Api::namespaced(self.client.clone(), namespace);
+            data_node_api.get(data_node_name).await.map_err(|e| {
+                tracing::error!("Error getting DataNode: {:?}", e);
+                Status::not_found("DataNode not found")
+            })?
+        };
+        let data_node = resolve_data_node(&self.client, data_node).await?;
+
+        let Some(limit) = data_node.spec.limit.as_ref() else {
+            return Ok(Response::new(GetCapacityResponse {
+                available_capacity: UNLIMITED_CAPACITY,
+                ..Default::default()
+            }));
+        };
+        let limit = quantity_to_bytes(limit)?;
+
+        // Requests and measured usage as recorded by the DataNode controller
+        let (used, requested) = match data_node.status.as_ref() {
+            Some(status) => (
+                quantity_to_bytes(&status.used)?,
+                status
+                    .requested
+                    .as_ref()
+                    .map(quantity_to_bytes)
+                    .transpose()?
+                    .unwrap_or_default(),
+            ),
+            None => (0, 0),
+        };
+        let available = limit.saturating_sub(used.max(requested));
+
         Ok(Response::new(GetCapacityResponse {
-            available_capacity: 1024 * 1024 * 1024 * 1024, // 1 TB
+            available_capacity: available as i64,
+            maximum_volume_size: Some(available as i64),
             ..Default::default()
         }))
     }
@@ -235,19 +522,61 @@ impl Controller for ControllerService {
     #[tracing::instrument(skip(self))]
     async fn controller_expand_volume(
         &self,
-        _request: Request<ControllerExpandVolumeRequest>,
+        request: Request<ControllerExpandVolumeRequest>,
     ) -> Result<Response<ControllerExpandVolumeResponse>, Status> {
-        Err(Status::unimplemented(
-            "ControllerExpandVolume is not implemented",
-        ))
+        let request = request.into_inner();
+        let volume_id = request.volume_id;
+
+        if volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID cannot be empty"));
+        }
+
+        let capacity_bytes = request
+            .capacity_range
+            .map(|range| range.required_bytes)
+            .filter(|bytes| *bytes > 0)
+            .ok_or_else(|| Status::invalid_argument("Capacity range cannot be empty"))?;
+
+        let guard = self.operations.try_lock(&volume_id)?;
+        let service = self.clone();
+        guard
+            .run_detached(async move { service.expand_volume(volume_id, capacity_bytes).await })
+            .await?;
+
+        // Node side quota has to be updated on the published mounts
+        Ok(Response::new(ControllerExpandVolumeResponse {
+            capacity_bytes,
+            node_expansion_required: true,
+        }))
     }
 
     #[tracing::instrument(skip(self))]
     async fn create_snapshot(
         &self,
-        _request: Request<crate::csi::CreateSnapshotRequest>,
+        request: Request<crate::csi::CreateSnapshotRequest>,
     ) -> Result<Response<crate::csi::CreateSnapshotResponse>, Status> {
-        tracing::info!("Creating snapshot for {:?}", _request);
+        let request = request.into_inner();
+        tracing::info!("Creating snapshot for {:?}", request);
+
+        if request.source_volume_id.is_empty() {
+            return Err(Status::invalid_argument("Source volume ID cannot be empty"));
+        }
+
+        // Backends that can not copy objects are refused with a reason
+        let volume_context = {
+            let volumes = self.volumes.lock().unwrap();
+            volumes
+                .values()
+                .find(|v| v.volume_id == request.source_volume_id)
+                .map(|v| v.volume_context.clone())
+                .ok_or_else(|| {
+                    Status::not_found(format!("Volume '{}' not found", request.source_volume_id))
+                })?
+        };
+        let context = VolumeContext::parse(&volume_context)?;
+        let data_node = get_data_node(&self.client, &context).await?;
+        check_snapshot(&data_node)?;
+
         Err(Status::unimplemented("CreateSnapshot is not implemented"))
     }
 
@@ -280,14 +609,77 @@ impl Controller for ControllerService {
     #[tracing::instrument(skip(self))]
     async fn controller_modify_volume(
         &self,
-        _request: tonic::Request<ControllerModifyVolumeRequest>,
+        request: tonic::Request<ControllerModifyVolumeRequest>,
     ) -> std::result::Result<tonic::Response<ControllerModifyVolumeResponse>, tonic::Status> {
-        Err(Status::unimplemented(
-            "ControllerModifyVolume is not implemented",
-        ))
+        let request = request.into_inner();
+        let volume_id = request.volume_id;
+
+        if volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID cannot be empty"));
+        }
+
+        let attributes = MutableAttributes::from_parameters(&request.mutable_parameters)?;
+
+        let guard = self.operations.try_lock(&volume_id)?;
+        let service = self.clone();
+        guard
+            .run_detached(async move { service.modify_volume(volume_id, attributes).await })
+            .await?;
+
+        Ok(Response::new(ControllerModifyVolumeResponse {}))
     }
 }
 
+/// Fetches the DataNode or ClusterDataNode of a volume with its class applied
+async fn get_data_node(client: &Client, context: &VolumeContext) -> Result<DataNode, Status> {
+    let data_node = if context.cluster_data_node {
+        get_cluster_data_node(client, &context.data_node_name).await?
+    } else {
+        let data_node_api: Api<DataNode> =
+            Api::namespaced(client.clone(), &context.data_node_namespace);
+        data_node_api
+            .get(&context.data_node_name)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error getting DataNode: {:?}", e);
+                Status::not_found("DataNode not found")
+            })?
+    };
+    resolve_data_node(client, data_node).await
+}
+
+/// Checks requested capabilities against the mount mode of the volume and its DataNode,
+/// volumes are only served as filesystems and read-only mounts can not be written
+fn check_volume_capabilities(
+    data_node: &DataNode,
+    context: &VolumeContext,
+    volume_capabilities: &[VolumeCapability],
+) -> Result<(), Status> {
+    check_mount(data_node, &context.mount)?;
+
+    let read_only = matches!(
+        context.mount,
+        MountAccess::CacheReadOnly | MountAccess::FuseReadOnly
+    );
+    for capability in volume_capabilities {
+        if matches!(capability.access_type, Some(AccessType::Block(_))) {
+            return Err(Status::invalid_argument("Block volumes are not supported"));
+        }
+        let mode = capability
+            .access_mode
+            .map_or(Mode::Unknown, |access_mode| access_mode.mode());
+        let writer = !matches!(mode, Mode::SingleNodeReaderOnly | Mode::MultiNodeReaderOnly);
+        if writer && read_only {
+            return Err(Status::invalid_argument(format!(
+                "Volume is mounted with {} and can not be written, requested {:?}",
+                context.mount, mode
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 async fn get_pvc(client: Client, namespace: &str, uid: &str) -> Result<ObjectMeta, Status> {
     let uid = uid.strip_prefix("pvc-").ok_or_else(|| {
         tracing::error!("PVC name not provided in request");
@@ -323,87 +715,169 @@ async fn get_pvc(client: Client, namespace: &str, uid: &str) -> Result<ObjectMet
     Ok(pvc_meta)
 }
 
-async fn get_config_from_pvc_meta(pvc_meta: ObjectMeta) -> Result<HashMap<String, String>, Status> {
-    let annotations = pvc_meta.annotations.unwrap_or_default();
+/// Checks if the DataNode of the DataPod has enough headroom left for the requested capacity.
+/// The requests of all other DataPods on the same DataNode count against its limit.
+async fn check_headroom(client: Client, data_pod: &DataPod, capacity: u64) -> Result<(), Status> {
+    if let Some(cluster_ref) = data_pod.spec.cluster_data_node_ref.as_ref() {
+        return check_cluster_headroom(client, data_pod, &cluster_ref.name, capacity).await;
+    }
 
-    let data_node_name = annotations
-        .get("kubedal.arunaengine.org/data-node-name")
-        .ok_or_else(|| {
-            tracing::error!("DataNode name annotation not found");
-            Status::invalid_argument("DataNode name annotation not found")
-        })?;
+    let data_pod_namespace = data_pod.metadata.namespace.clone().unwrap_or_default();
+    let node_ref = data_pod
+        .spec
+        .data_node_ref
+        .as_ref()
+        .ok_or_else(|| Status::failed_precondition("DataPod has no DataNode reference"))?;
+    let data_node_namespace = node_ref
+        .namespace
+        .clone()
+        .unwrap_or(data_pod_namespace.clone());
+
+    let data_node_api: Api<DataNode> = Api::namespaced(client.clone(), &data_node_namespace);
+    let data_node = data_node_api.get(&node_ref.name).await.map_err(|e| {
+        tracing::error!("Error getting DataNode: {:?}", e);
+        Status::internal("Error getting DataNode")
+    })?;
+    let data_node = resolve_data_node(&client, data_node).await?;
 
-    let data_node_namespace = annotations
-        .get("kubedal.arunaengine.org/data-node-namespace")
-        .cloned()
-        .unwrap_or(pvc_meta.namespace.clone().unwrap_or_default());
+    let Some(limit) = data_node.spec.limit.as_ref() else {
+        return Ok(());
+    };
+    let limit = quantity_to_bytes(limit)?;
 
-    let data_pod_name = annotations
-        .get("kubedal.arunaengine.org/data-pod-name")
-        .ok_or_else(|| {
-            tracing::error!("DataPod name annotation not found");
-            Status::invalid_argument("DataPod name annotation not found")
+    let data_pod_api: Api<DataPod> = Api::all(client);
+    let all_data_pods = data_pod_api
+        .list(&ListParams::default())
+        .await
+        .map_err(|e| {
+            tracing::error!("Error listing DataPods: {:?}", e);
+            Status::internal("Error listing DataPods")
         })?;
 
-    let data_pod_namespace = annotations
-        .get("kubedal.arunaengine.org/data-pod-namespace")
-        .cloned()
-        .unwrap_or(pvc_meta.namespace.clone().unwrap_or_default());
+    let mut requested = 0;
+    for other in all_data_pods.items {
+        if other.metadata.uid == data_pod.metadata.uid {
+            continue;
+        }
+        let other_namespace = other.metadata.namespace.clone().unwrap_or_default();
+        let same_node = other.spec.data_node_ref.as_ref().is_some_and(|other_ref| {
+            other_ref.name == node_ref.name
+                && other_ref.namespace.as_ref().unwrap_or(&other_namespace) == &data_node_namespace
+        });
+        if let (true, Some(request)) = (same_node, other.spec.request.as_ref()) {
+            requested += quantity_to_bytes(request)?;
+        }
+    }
 
-    let data_mount = annotations
-        .get("kubedal.arunaengine.org/mount")
-        .ok_or_else(|| {
-            tracing::error!("Mount mode annotation not found");
-            Status::invalid_argument("Mount mode annotation not found")
-        })?;
+    // Measured usage counts as well, DataPods may hold more than they requested
+    let used = data_node
+        .status
+        .as_ref()
+        .map(|status| quantity_to_bytes(&status.used))
+        .transpose()?
+        .unwrap_or_default();
+    let committed = requested.max(used.saturating_sub(measured_bytes(data_pod)));
+    if committed + capacity > limit {
+        return Err(Status::out_of_range(format!(
+            "DataNode '{}' has insufficient headroom: {} of {} bytes already requested or used, {} bytes required",
+            node_ref.name, committed, limit, capacity
+        )));
+    }
 
-    let mut config = HashMap::new();
-    config.extend([
-        (
-            "kubedal.arunaengine.org/data-node-name".to_string(),
-            data_node_name.to_string(),
-        ),
-        (
-            "kubedal.arunaengine.org/data-node-namespace".to_string(),
-            data_node_namespace.to_string(),
-        ),
-        (
-            "kubedal.arunaengine.org/data-pod-name".to_string(),
-            data_pod_name.to_string(),
-        ),
-        (
-            "kubedal.arunaengine.org/data-pod-namespace".to_string(),
-            data_pod_namespace.to_string(),
-        ),
-        (
-            "kubedal.arunaengine.org/mount".to_string(),
-            data_mount.to_string(),
-        ),
-    ]);
-
-    // Override the mount mode if specified in the PVC annotations
-    /*
-    let res_api: Api<DataNode> = Api::namespaced(client, &data_node_namespace);
-    let res = res_api.get(data_node_name).await.map_err(|e| {
-        tracing::error!("Error getting resource: {:?}", e);
-        Status::internal("Error getting resource")
+    Ok(())
+}
+
+/// Checks the limit of a ClusterDataNode against the requests of all DataPods
+/// and its namespace limit against the requests of the DataPods in the same namespace
+async fn check_cluster_headroom(
+    client: Client,
+    data_pod: &DataPod,
+    name: &str,
+    capacity: u64,
+) -> Result<(), Status> {
+    let cluster_data_node_api: Api<ClusterDataNode> = Api::all(client.clone());
+    let cluster_data_node = cluster_data_node_api.get(name).await.map_err(|e| {
+        tracing::error!("Error getting ClusterDataNode: {:?}", e);
+        Status::internal("Error getting ClusterDataNode")
     })?;
+    let limit = cluster_data_node
+        .spec
+        .node
+        .limit
+        .as_ref()
+        .map(quantity_to_bytes)
+        .transpose()?;
+    let namespace_limit = cluster_data_node
+        .spec
+        .namespace_limit
+        .as_ref()
+        .map(quantity_to_bytes)
+        .transpose()?;
+    if limit.is_none() && namespace_limit.is_none() {
+        return Ok(());
+    }
 
-    config.insert(
-        "kubedal.arunaengine.org/mount".to_string(),
-        annotations
-            .get("kubedal.arunaengine.org/mount")
-            .and_then(|mode| match mode.as_str() {
-                m @ ("cached" | "fuse") => Some(m),
-                _ => None,
-            })
-            .unwrap_or(match res.spec.mount {
-                crate::resource::crd::MountMode::Cached => "cached",
-                crate::resource::crd::MountMode::Fuse => "fuse",
-            })
-            .to_string(),
-    );
-    */
+    let data_pod_api: Api<DataPod> = Api::all(client);
+    let all_data_pods = data_pod_api
+        .list(&ListParams::default())
+        .await
+        .map_err(|e| {
+            tracing::error!("Error listing DataPods: {:?}", e);
+            Status::internal("Error listing DataPods")
+        })?;
+
+    let (mut requested, mut namespace_requested) = (0, 0);
+    for other in all_data_pods.items {
+        if other.metadata.uid == data_pod.metadata.uid {
+            continue;
+        }
+        let same_node = other
+            .spec
+            .cluster_data_node_ref
+            .as_ref()
+            .is_some_and(|other_ref| other_ref.name == name);
+        if let (true, Some(request)) = (same_node, other.spec.request.as_ref()) {
+            let request = quantity_to_bytes(request)?;
+            requested += request;
+            if other.metadata.namespace == data_pod.metadata.namespace {
+                namespace_requested += request;
+            }
+        }
+    }
+
+    // Measured usage counts as well, DataPods may hold more than they requested
+    let used = cluster_data_node
+        .status
+        .as_ref()
+        .map(|status| quantity_to_bytes(&status.used))
+        .transpose()?
+        .unwrap_or_default();
+    let committed = requested.max(used.saturating_sub(measured_bytes(data_pod)));
+    if let Some(limit) = limit.filter(|limit| committed + capacity > *limit) {
+        return Err(Status::out_of_range(format!(
+            "ClusterDataNode '{}' has insufficient headroom: {} of {} bytes already requested or used, {} bytes required",
+            name, committed, limit, capacity
+        )));
+    }
+    if let Some(limit) = namespace_limit.filter(|limit| namespace_requested + capacity > *limit) {
+        return Err(Status::out_of_range(format!(
+            "ClusterDataNode '{}' has insufficient headroom in namespace '{}': {} of {} bytes already requested, {} bytes required",
+            name,
+            data_pod.metadata.namespace.clone().unwrap_or_default(),
+            namespace_requested,
+            limit,
+            capacity
+        )));
+    }
+
+    Ok(())
+}
 
-    Ok(config)
+/// Bytes the accounting task measured for the DataPod, 0 if it was not measured yet
+fn measured_bytes(data_pod: &DataPod) -> u64 {
+    data_pod
+        .status
+        .as_ref()
+        .and_then(|status| status.usage.as_ref())
+        .map_or(0, |usage| usage.bytes)
 }
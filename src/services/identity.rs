@@ -50,8 +50,32 @@ impl Identity for IdentityService {
             )),
         };
 
+        // Volumes can be expanded while they are published
+        let volume_expansion = PluginCapability {
+            r#type: Some(crate::csi::plugin_capability::Type::VolumeExpansion(
+                crate::csi::plugin_capability::VolumeExpansion {
+                    r#type: crate::csi::plugin_capability::volume_expansion::Type::Online.into(),
+                },
+            )),
+        };
+
+        // Volumes of DataNodes with a node selector are only accessible from matching nodes
+        let accessibility_constraints = PluginCapability {
+            r#type: Some(crate::csi::plugin_capability::Type::Service(
+                crate::csi::plugin_capability::Service {
+                    r#type:
+                        crate::csi::plugin_capability::service::Type::VolumeAccessibilityConstraints
+                            .into(),
+                },
+            )),
+        };
+
         let response = GetPluginCapabilitiesResponse {
-            capabilities: vec![controller_service],
+            capabilities: vec![
+                controller_service,
+                volume_expansion,
+                accessibility_constraints,
+            ],
         };
 
         Ok(Response::new(response))
@@ -1,55 +1,318 @@
 use crate::csi::node_server::Node;
 use crate::csi::{
     NodeExpandVolumeRequest, NodeExpandVolumeResponse, NodeGetCapabilitiesRequest,
-    NodeGetCapabilitiesResponse, NodeGetInfoRequest, NodeGetInfoResponse, NodePublishVolumeRequest,
+    NodeGetCapabilitiesResponse, NodeGetInfoRequest, NodeGetInfoResponse,
+    NodeGetVolumeStatsRequest, NodeGetVolumeStatsResponse, NodePublishVolumeRequest,
     NodePublishVolumeResponse, NodeServiceCapability, NodeUnpublishVolumeRequest,
-    NodeUnpublishVolumeResponse,
+    NodeUnpublishVolumeResponse, VolumeCondition, VolumeUsage,
 };
-use crate::resource::crd::{DataNode, DataPod, MountAccess};
-use crate::util::mount_helper::{AccessMode, Mount, MountMode};
-use crate::util::opendal::get_operator;
+use crate::resource::crd::{ClusterDataNode, DataNode, DataPod, MountAccess};
+use crate::util::capabilities::check_mount;
+use crate::util::cluster_data_node::get_cluster_data_node;
+use crate::util::data_node_class::resolve_data_node;
+use crate::util::http_client::HttpSettings;
+use crate::util::mount_helper::{AccessMode, CacheEvent, Mount, MountMode};
+use crate::util::opendal::{PublishCredentials, SharedCredentials, get_data_pod_operator};
+use crate::util::operation_lock::OperationLocks;
+use crate::util::operator_swap::OperatorSwap;
+use crate::util::quantity::quantity_to_bytes;
+use crate::util::throttle::Throttle;
+use crate::util::topology::node_topology;
+use crate::util::volume_attributes::{find_persistent_volume, persistent_volume_attributes};
+use crate::util::volume_context::{PodInfo, VolumeContext};
+use futures::StreamExt;
 use k8s_openapi::api::authorization::v1::{
     ResourceAttributes, SubjectAccessReview, SubjectAccessReviewSpec,
 };
-use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::api::core::v1::{ObjectReference, PersistentVolume, Secret};
 use kube::api::PostParams;
-use kube::{Api, Client};
+use kube::runtime::events::{Event, EventType, Recorder};
+use kube::runtime::{WatchStreamExt, watcher};
+use kube::{Api, Client, Resource, ResourceExt};
 use opendal::Operator;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tonic::{Request, Response, Status};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::JoinHandle;
+use tonic::{Code, Request, Response, Status};
 
+#[derive(Clone)]
 pub struct FullDataSource {
     pub source: DataNode,
     pub pod: DataPod,
     pub secret: Option<Secret>,
+    /// The source is a ClusterDataNode, its Secrets belong to the driver
+    pub cluster_scoped: bool,
 }
 
-pub struct PodInfo {
-    pub name: String,
-    pub namespace: String,
-    pub service_account: String,
-    pub uid: String,
-}
-
+#[derive(Clone)]
 pub struct NodeService {
     client: Client,
     node_id: String,
     // Track mounted volumes for our dummy driver
     mounts: Arc<Mutex<HashMap<String, Mount>>>,
+    // Serializes operations per volume
+    operations: OperationLocks,
+    // Publishes events on pods and PVCs
+    recorder: Recorder,
 }
 
 impl NodeService {
     pub fn new(client: Client, node_id: &str) -> Self {
         Self {
+            recorder: Recorder::new(client.clone(), "kubedal.arunaengine.org".into()),
             client,
             node_id: node_id.to_string(),
             mounts: Arc::new(Mutex::new(HashMap::new())),
+            operations: OperationLocks::default(),
+        }
+    }
+
+    async fn publish_volume(
+        &self,
+        volume_id: String,
+        target_path: String,
+        volume_context: HashMap<String, String>,
+        secrets: HashMap<String, String>,
+    ) -> Result<(), Status> {
+        // Publishing is idempotent for volumes that are already mounted at the target
+        if self
+            .mounts
+            .lock()
+            .await
+            .get(&volume_id)
+            .is_some_and(|mount| mount.target_path == target_path)
+        {
+            tracing::info!(
+                "Volume '{}' already published to '{}'",
+                volume_id,
+                target_path
+            );
+            // The kubelet republishes with fresh tokens if the CSIDriver requires republishing
+            return self
+                .refresh_credentials(&volume_id, &volume_context, secrets)
+                .await;
+        }
+
+        // Attributes modified via ControllerModifyVolume override the ones from creation
+        let mut context = VolumeContext::parse(&volume_context)?;
+        let pv = find_persistent_volume(self.client.clone(), &volume_id).await?;
+        if let Some(pv) = pv.as_ref() {
+            context.apply(persistent_volume_attributes(pv)?);
+        }
+        let pod_info = context.pod_info()?;
+
+        let full_data_source = get_full_data_mount(self.client.clone(), &context).await?;
+
+        // Backends like `fs` are only reachable from some nodes
+        check_node_affinity(self.client.clone(), &self.node_id, &full_data_source.source).await?;
+
+        // Checks if the pods service account has access to the data source / secret and may
+        // mount the DataPod with the requested access, denials are reported on the pod
+        if let Err(e) = check_access(
+            self.client.clone(),
+            &full_data_source,
+            pod_info,
+            &context.mount,
+        )
+        .await
+        {
+            if e.code() == Code::PermissionDenied {
+                self.publish_pod_event(pod_info, "MountForbidden", e.message())
+                    .await;
+            }
+            return Err(e);
+        }
+
+        // Read dataset into target path
+        tracing::info!(
+            "Publishing volume '{}' with data source '{}' to '{}'",
+            volume_id,
+            full_data_source
+                .source
+                .metadata
+                .name
+                .clone()
+                .unwrap_or_default(),
+            target_path
+        );
+
+        // The DataPod request is the logical quota of the volume
+        let quota = full_data_source
+            .pod
+            .spec
+            .request
+            .as_ref()
+            .map(quantity_to_bytes)
+            .transpose()?;
+
+        let credentials = publish_credentials(&context, &full_data_source.source, secrets)?;
+
+        let (operator, mount_mode, access_mode) = full_data_source
+            .clone()
+            .into_parts(self.client.clone(), context.mount.clone(), &credentials)
+            .await?;
+        let credentials = SharedCredentials::new(std::sync::Mutex::new(credentials));
+        // Rotated secrets and renewed tokens replace the operator behind the live mount
+        let (swap, operator) = OperatorSwap::new(operator);
+
+        let throttle = Throttle::new(context.throughput);
+        let mut mount = Mount::new(
+            volume_id.clone(),
+            target_path,
+            operator,
+            mount_mode,
+            access_mode,
+            quota,
+        )
+        .with_throttle(throttle.clone())
+        .with_cache_reuse(context.cache_reuse)
+        .with_cache_async(context.cache_async)
+        .with_operator_swap(swap.clone())
+        .with_credentials(credentials.clone());
+
+        // Cache progress is reported on the pod and, if known, on the PVC
+        if mount.mount_mode == MountMode::Cached {
+            let mut references = vec![pod_reference(pod_info)];
+            if let Some(claim_ref) = pv
+                .as_ref()
+                .and_then(|pv| pv.spec.as_ref())
+                .and_then(|spec| spec.claim_ref.clone())
+            {
+                references.push(claim_ref);
+            }
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(forward_cache_events(
+                self.recorder.clone(),
+                volume_id.clone(),
+                references,
+                receiver,
+            ));
+            mount = mount.with_cache_events(sender);
+        }
+
+        mount.mount().await?;
+
+        if let Some(pv) = pv.as_ref() {
+            mount = mount.with_attribute_watch(watch_volume_attributes(
+                self.client.clone(),
+                pv.name_any(),
+                throttle,
+            ));
+        }
+        if let Some(credential_watch) = watch_credentials(
+            self.client.clone(),
+            self.recorder.clone(),
+            volume_id.clone(),
+            full_data_source,
+            credentials,
+            swap,
+        ) {
+            mount = mount.with_credential_watch(credential_watch);
+        }
+
+        // Track this mount in our in-memory state
+        let mut mounts = self.mounts.lock().await;
+        mounts.insert(volume_id, mount);
+
+        Ok(())
+    }
+
+    /// Publishes a warning about a failed publish on the pod
+    async fn publish_pod_event(&self, pod_info: &PodInfo, reason: &str, note: &str) {
+        let result = self
+            .recorder
+            .publish(
+                &Event {
+                    type_: EventType::Warning,
+                    reason: reason.into(),
+                    note: Some(note.to_string()),
+                    action: "PublishVolume".into(),
+                    secondary: None,
+                },
+                &pod_reference(pod_info),
+            )
+            .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to publish pod event: {:?}", e);
+        }
+    }
+
+    /// Rebuilds the operator of a live mount when the kubelet republishes it with a
+    /// renewed service account token or changed node-publish secrets
+    async fn refresh_credentials(
+        &self,
+        volume_id: &str,
+        volume_context: &HashMap<String, String>,
+        secrets: HashMap<String, String>,
+    ) -> Result<(), Status> {
+        let (swap, credentials) = match self.mounts.lock().await.get(volume_id) {
+            Some(mount) => (mount.operator_swap.clone(), mount.credentials.clone()),
+            None => return Ok(()),
+        };
+        let Some(swap) = swap else {
+            return Ok(());
+        };
+
+        // The kubelet republishes periodically, mostly with what it already passed
+        let context = VolumeContext::parse(volume_context)?;
+        {
+            let current = credentials.lock().unwrap_or_else(|e| e.into_inner());
+            let token_unchanged = current.service_account_token.as_ref().is_none_or(|token| {
+                context
+                    .service_account_tokens
+                    .values()
+                    .any(|value| value == token)
+            });
+            if token_unchanged && current.secrets == secrets {
+                return Ok(());
+            }
         }
+
+        let full_data_source = get_full_data_mount(self.client.clone(), &context).await?;
+        let refreshed = publish_credentials(&context, &full_data_source.source, secrets)?;
+
+        let operator = get_data_pod_operator(
+            &self.client,
+            &full_data_source.source,
+            &full_data_source.pod,
+            &refreshed,
+        )
+        .await?;
+        swap.replace(operator);
+        *credentials.lock().unwrap_or_else(|e| e.into_inner()) = refreshed;
+        tracing::info!("Refreshed publish credentials of '{}'", volume_id);
+
+        Ok(())
     }
 }
 
+/// Secrets of the publish request and, for DataNodes using web identity,
+/// service account token and session name
+fn publish_credentials(
+    context: &VolumeContext,
+    data_node: &DataNode,
+    secrets: HashMap<String, String>,
+) -> Result<PublishCredentials, Status> {
+    let Some(web_identity) = data_node.spec.web_identity.as_ref() else {
+        return Ok(PublishCredentials {
+            secrets,
+            ..Default::default()
+        });
+    };
+
+    Ok(PublishCredentials {
+        service_account_token: Some(
+            context
+                .service_account_token(web_identity.audience())?
+                .to_string(),
+        ),
+        session_name: format!("kubedal-{}", context.pod_info()?.uid),
+        secrets,
+    })
+}
+
 #[tonic::async_trait]
 impl Node for NodeService {
     async fn node_get_capabilities(
@@ -66,8 +329,32 @@ impl Node for NodeService {
             )),
         };
 
+        let expand_volume = NodeServiceCapability {
+            r#type: Some(crate::csi::node_service_capability::Type::Rpc(
+                crate::csi::node_service_capability::Rpc {
+                    r#type: crate::csi::node_service_capability::rpc::Type::ExpandVolume.into(),
+                },
+            )),
+        };
+
+        let volume_stats = NodeServiceCapability {
+            r#type: Some(crate::csi::node_service_capability::Type::Rpc(
+                crate::csi::node_service_capability::Rpc {
+                    r#type: crate::csi::node_service_capability::rpc::Type::GetVolumeStats.into(),
+                },
+            )),
+        };
+
+        let volume_condition = NodeServiceCapability {
+            r#type: Some(crate::csi::node_service_capability::Type::Rpc(
+                crate::csi::node_service_capability::Rpc {
+                    r#type: crate::csi::node_service_capability::rpc::Type::VolumeCondition.into(),
+                },
+            )),
+        };
+
         let response = NodeGetCapabilitiesResponse {
-            capabilities: vec![stage_unstage],
+            capabilities: vec![stage_unstage, expand_volume, volume_stats, volume_condition],
         };
 
         Ok(Response::new(response))
@@ -81,7 +368,7 @@ impl Node for NodeService {
         let response = NodeGetInfoResponse {
             node_id: self.node_id.clone(),
             max_volumes_per_node: 1000, // Arbitrary limit for the dummy driver
-            accessible_topology: None,
+            accessible_topology: Some(node_topology(&self.node_id)),
         };
 
         Ok(Response::new(response))
@@ -104,43 +391,22 @@ impl Node for NodeService {
             return Err(Status::invalid_argument("Target path cannot be empty"));
         }
 
-        let (full_data_source, mount_access) =
-            get_full_data_mount(self.client.clone(), &request.volume_context).await?;
-        let pod_info = get_pod_info(&request.volume_context)?;
-
-        // Checks if the pods service account has access to the data source / secret
-        check_access(self.client.clone(), &full_data_source, &pod_info).await?;
-
-        // Read dataset into target path
-        tracing::info!(
-            "Publishing volume '{}' with data source '{}' to '{}'",
-            volume_id,
-            full_data_source
-                .source
-                .metadata
-                .name
-                .clone()
-                .unwrap_or_default(),
-            target_path
-        );
-
-        let (operator, mount_mode, access_mode) = full_data_source
-            .into_parts(self.client.clone(), mount_access)
+        // Publishing keeps running in the background if the kubelet times out
+        let guard = self.operations.try_lock(&volume_id)?;
+        let service = self.clone();
+        guard
+            .run_detached(async move {
+                service
+                    .publish_volume(
+                        volume_id,
+                        target_path,
+                        request.volume_context,
+                        request.secrets,
+                    )
+                    .await
+            })
             .await?;
 
-        let mut mount = Mount::new(
-            volume_id.clone(),
-            target_path,
-            operator,
-            mount_mode,
-            access_mode,
-        );
-        mount.mount().await?;
-
-        // Track this mount in our in-memory state
-        let mut mounts = self.mounts.lock().await;
-        mounts.insert(volume_id, mount);
-
         Ok(Response::new(NodePublishVolumeResponse {}))
     }
 
@@ -162,11 +428,19 @@ impl Node for NodeService {
 
         tracing::info!("Unpublishing volume '{}' from '{}'", volume_id, target_path);
 
-        // Remove from our tracking
-        let mut mounts = self.mounts.lock().await;
-        if let Some(mut mount) = mounts.remove(&volume_id) {
-            mount.unmount().await?;
-        }
+        let guard = self.operations.try_lock(&volume_id)?;
+        let mounts = self.mounts.clone();
+        guard
+            .run_detached(async move {
+                // Remove from our tracking once unmounted
+                let mut mounts = mounts.lock().await;
+                if let Some(mount) = mounts.get_mut(&volume_id) {
+                    mount.unmount().await?;
+                    mounts.remove(&volume_id);
+                }
+                Ok::<_, Status>(())
+            })
+            .await?;
 
         Ok(Response::new(NodeUnpublishVolumeResponse {}))
     }
@@ -188,100 +462,393 @@ impl Node for NodeService {
         Ok(Response::new(crate::csi::NodeUnstageVolumeResponse {}))
     }
 
+    #[tracing::instrument(skip(self))]
     async fn node_expand_volume(
         &self,
-        _request: Request<NodeExpandVolumeRequest>,
+        request: Request<NodeExpandVolumeRequest>,
     ) -> Result<Response<NodeExpandVolumeResponse>, Status> {
-        // Our simple driver doesn't support this
-        Err(Status::unimplemented("Volume expansion is not supported"))
+        let request = request.into_inner();
+        let volume_id = request.volume_id;
+
+        if volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID cannot be empty"));
+        }
+
+        let capacity_bytes = request
+            .capacity_range
+            .map(|range| range.required_bytes)
+            .filter(|bytes| *bytes > 0)
+            .ok_or_else(|| Status::invalid_argument("Capacity range cannot be empty"))?;
+
+        let _guard = self.operations.try_lock(&volume_id)?;
+
+        // Capacity is a logical quota, so expanding only updates the mount in place. Volumes
+        // that are only staged or were mounted before a restart read the new request of
+        // their DataPod on publish.
+        match self.mounts.lock().await.get_mut(&volume_id) {
+            Some(mount) => {
+                mount.set_quota(Some(capacity_bytes as u64));
+                tracing::info!(
+                    "Expanded volume '{}' to {} bytes",
+                    volume_id,
+                    capacity_bytes
+                );
+            }
+            None => tracing::info!(
+                "Volume '{}' is not published, {} bytes apply on publish",
+                volume_id,
+                capacity_bytes
+            ),
+        }
+
+        Ok(Response::new(NodeExpandVolumeResponse { capacity_bytes }))
     }
 
-    // Additional required methods
+    #[tracing::instrument(skip(self))]
     async fn node_get_volume_stats(
         &self,
-        _request: Request<crate::csi::NodeGetVolumeStatsRequest>,
-    ) -> Result<Response<crate::csi::NodeGetVolumeStatsResponse>, Status> {
-        // Our simple driver doesn't support this
-        Err(Status::unimplemented("NodeGetVolumeStats is not supported"))
+        request: Request<NodeGetVolumeStatsRequest>,
+    ) -> Result<Response<NodeGetVolumeStatsResponse>, Status> {
+        let request = request.into_inner();
+        let volume_id = request.volume_id;
+
+        if volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID cannot be empty"));
+        }
+
+        // Measuring a FUSE volume lists its data source, don't block other
+        // volume operations meanwhile
+        let (meter, quota, condition) = {
+            let mounts = self.mounts.lock().await;
+            let mount = mounts.get(&volume_id).ok_or_else(|| {
+                Status::not_found(format!("Volume '{}' is not published", volume_id))
+            })?;
+            (mount.usage_meter(), mount.quota, mount.cache_condition())
+        };
+
+        let used = meter.usage().await?;
+        let total = quota.unwrap_or(used).max(used);
+
+        Ok(Response::new(NodeGetVolumeStatsResponse {
+            usage: vec![VolumeUsage {
+                available: (total - used) as i64,
+                total: total as i64,
+                used: used as i64,
+                unit: crate::csi::volume_usage::Unit::Bytes.into(),
+            }],
+            volume_condition: condition
+                .map(|(abnormal, message)| VolumeCondition { abnormal, message }),
+        }))
+    }
+}
+
+fn pod_reference(pod_info: &PodInfo) -> ObjectReference {
+    ObjectReference {
+        api_version: Some("v1".to_string()),
+        kind: Some("Pod".to_string()),
+        name: Some(pod_info.name.clone()),
+        namespace: Some(pod_info.namespace.clone()),
+        uid: Some(pod_info.uid.clone()),
+        ..Default::default()
     }
 }
 
-#[tracing::instrument]
-fn get_pod_info(volume_context: &HashMap<String, String>) -> Result<PodInfo, Status> {
-    // Fetch Pod meta from context and check with SubjectAccessReview
-    let pod_name = volume_context
-        .get("csi.storage.k8s.io/pod.name")
-        .expect("Pod name not provided");
-    let pod_namespace = volume_context
-        .get("csi.storage.k8s.io/pod.namespace")
-        .expect("Pod namespace not provided");
-    let pod_service_account = volume_context
-        .get("csi.storage.k8s.io/serviceAccount.name")
-        .expect("Pod service account not provided");
-
-    let pod_uid = volume_context
-        .get("csi.storage.k8s.io/pod.uid")
-        .expect("Pod UID not provided");
-
-    Ok(PodInfo {
-        name: pod_name.clone(),
-        namespace: pod_namespace.clone(),
-        service_account: pod_service_account.clone(),
-        uid: pod_uid.clone(),
+/// Publishes the progress of a cache population as events on the referenced objects
+async fn forward_cache_events(
+    recorder: Recorder,
+    volume_id: String,
+    references: Vec<ObjectReference>,
+    mut events: UnboundedReceiver<CacheEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        let (type_, reason, note) = match event {
+            CacheEvent::Started { entries } => (
+                EventType::Normal,
+                "CachePopulating",
+                format!(
+                    "Populating cache of volume {} with {} entries",
+                    volume_id, entries
+                ),
+            ),
+            CacheEvent::Progress {
+                entries,
+                total,
+                bytes,
+            } => (
+                EventType::Normal,
+                "CacheProgress",
+                format!(
+                    "Cached {} of {} entries ({} bytes) of volume {}",
+                    entries, total, bytes, volume_id
+                ),
+            ),
+            CacheEvent::Ready { entries, bytes } => (
+                EventType::Normal,
+                "CacheReady",
+                format!(
+                    "Cache of volume {} is ready with {} entries ({} bytes)",
+                    volume_id, entries, bytes
+                ),
+            ),
+            CacheEvent::Failed(message) => (
+                EventType::Warning,
+                "CacheFailed",
+                format!(
+                    "Populating cache of volume {} failed: {}",
+                    volume_id, message
+                ),
+            ),
+        };
+
+        for reference in &references {
+            let result = recorder
+                .publish(
+                    &Event {
+                        type_,
+                        reason: reason.into(),
+                        note: Some(note.clone()),
+                        action: "PopulateCache".into(),
+                        secondary: None,
+                    },
+                    reference,
+                )
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to publish cache event: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Applies live-adjustable attribute changes of the PersistentVolume to a published mount
+fn watch_volume_attributes(client: Client, pv_name: String, throttle: Throttle) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let pv_api: Api<PersistentVolume> = Api::all(client);
+        let config = watcher::Config::default().fields(&format!("metadata.name={}", pv_name));
+        let mut stream = watcher(pv_api, config)
+            .default_backoff()
+            .applied_objects()
+            .boxed();
+
+        while let Some(event) = stream.next().await {
+            match event.map(|pv| persistent_volume_attributes(&pv)) {
+                Ok(Ok(attributes)) => {
+                    if attributes.throughput_limit() != throttle.limit() {
+                        tracing::info!(
+                            "Adjusting throughput of '{}' to {:?} bytes/s",
+                            pv_name,
+                            attributes.throughput_limit()
+                        );
+                        throttle.set_limit(attributes.throughput_limit());
+                    }
+                }
+                Ok(Err(e)) => tracing::warn!("Invalid attributes on '{}': {}", pv_name, e),
+                Err(e) => tracing::warn!("Watching '{}' failed: {:?}", pv_name, e),
+            }
+        }
     })
 }
 
-async fn get_full_data_mount(
+/// Rebuilds the operator of a published mount whenever one of the Secrets
+/// referenced by its DataNode changes. Returns `None` without Secrets.
+fn watch_credentials(
     client: Client,
-    volume_context: &HashMap<String, String>,
-) -> Result<(FullDataSource, MountAccess), Status> {
-    // Get DataSource meta from volume context
-    let data_node_name = volume_context
-        .get("kubedal.arunaengine.org/data-node-name")
-        .cloned()
-        .ok_or_else(|| Status::invalid_argument("DataNode name not provided"))?;
-
-    let data_node_namespace = volume_context
-        .get("kubedal.arunaengine.org/data-node-namespace")
-        .cloned()
-        .ok_or_else(|| Status::invalid_argument("DataNode namespace not provided"))?;
-
-    let data_pod_name = volume_context
-        .get("kubedal.arunaengine.org/data-pod-name")
-        .cloned()
-        .ok_or_else(|| Status::invalid_argument("DataNode name not provided"))?;
-
-    let data_pod_namespace = volume_context
-        .get("kubedal.arunaengine.org/data-pod-namespace")
-        .cloned()
-        .ok_or_else(|| Status::invalid_argument("DataNode namespace not provided"))?;
-
-    let mount_mode = match volume_context
-        .get("kubedal.arunaengine.org/mount")
-        .ok_or_else(|| Status::invalid_argument("Resource namespace not provided"))?
-        .as_str()
-    {
-        "cache" => MountAccess::CacheReadOnly,
-        "cache-read-only" => MountAccess::CacheReadOnly,
-        "cache-read-write" => MountAccess::CacheReadWrite,
-        "fuse" => MountAccess::FuseReadOnly,
-        "fuse-read-only" => MountAccess::FuseReadOnly,
-        "fuse-read-write" => MountAccess::FuseReadWrite,
-        _ => return Err(Status::invalid_argument("Unsupported mount type")),
+    recorder: Recorder,
+    volume_id: String,
+    source: FullDataSource,
+    credentials: SharedCredentials,
+    swap: OperatorSwap,
+) -> Option<JoinHandle<()>> {
+    let secrets = referenced_secrets(&source.source);
+    if secrets.is_empty() {
+        return None;
+    }
+    let reference = source.source_reference();
+    let FullDataSource {
+        source: data_node,
+        pod: data_pod,
+        secret,
+        ..
+    } = source;
+    let mut versions: HashMap<String, Option<String>> = secret
+        .map(|secret| (secret_key(&secret), secret.resource_version()))
+        .into_iter()
+        .collect();
+
+    Some(tokio::spawn(async move {
+        let streams = secrets.iter().map(|(namespace, name)| {
+            let secret_api: Api<Secret> = Api::namespaced(client.clone(), namespace);
+            let config = watcher::Config::default().fields(&format!("metadata.name={}", name));
+            watcher(secret_api, config)
+                .default_backoff()
+                .applied_objects()
+                .boxed()
+        });
+        let mut stream = futures::stream::select_all(streams);
+
+        while let Some(event) = stream.next().await {
+            let secret = match event {
+                Ok(secret) => secret,
+                Err(e) => {
+                    tracing::warn!("Watching secrets of '{}' failed: {:?}", volume_id, e);
+                    continue;
+                }
+            };
+
+            // Secrets seen for the first time are the versions the operator was built with
+            let key = secret_key(&secret);
+            match versions.insert(key.clone(), secret.resource_version()) {
+                Some(version) if version != secret.resource_version() => {}
+                _ => continue,
+            }
+
+            // Readers opened before keep their backend, so in-flight reads are not interrupted
+            let current = credentials
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            let (type_, reason, note) =
+                match get_data_pod_operator(&client, &data_node, &data_pod, &current).await {
+                    Ok(operator) => {
+                        swap.replace(operator);
+                        tracing::info!("Rebuilt operator of '{}' after {} changed", volume_id, key);
+                        (
+                            EventType::Normal,
+                            "CredentialsRotated",
+                            format!(
+                                "Secret {} changed, volume {} uses the new credentials",
+                                key, volume_id
+                            ),
+                        )
+                    }
+                    Err(e) => (
+                        EventType::Warning,
+                        "CredentialRotationFailed",
+                        format!(
+                            "Secret {} changed, but volume {} keeps the previous credentials: {}",
+                            key,
+                            volume_id,
+                            e.message()
+                        ),
+                    ),
+                };
+
+            let result = recorder
+                .publish(
+                    &Event {
+                        type_,
+                        reason: reason.into(),
+                        note: Some(note),
+                        action: "RotateCredentials".into(),
+                        secondary: None,
+                    },
+                    &reference,
+                )
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to publish rotation event: {:?}", e);
+            }
+        }
+    }))
+}
+
+/// Namespace and name of all Secrets the operator of a DataNode is built from
+fn referenced_secrets(data_node: &DataNode) -> Vec<(String, String)> {
+    let secret_ref = data_node.spec.secret_ref.as_ref().map(|source| {
+        (
+            source
+                .namespace
+                .clone()
+                .or_else(|| data_node.namespace())
+                .unwrap_or_default(),
+            source.name.clone(),
+        )
+    });
+    let http_secrets = HttpSettings::references(data_node)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(resource, _, _)| *resource == "secrets")
+        .map(|(_, namespace, name)| (namespace, name));
+
+    let mut secrets = Vec::new();
+    for secret in secret_ref.into_iter().chain(http_secrets) {
+        if !secrets.contains(&secret) {
+            secrets.push(secret);
+        }
+    }
+    secrets
+}
+
+fn secret_key(secret: &Secret) -> String {
+    format!(
+        "{}/{}",
+        secret.namespace().unwrap_or_default(),
+        secret.name_any()
+    )
+}
+
+/// Ensures the node matches the `nodeSelector` of the DataNode
+async fn check_node_affinity(
+    client: Client,
+    node_id: &str,
+    data_node: &DataNode,
+) -> Result<(), Status> {
+    let Some(selector) = data_node.spec.node_selector.as_ref() else {
+        return Ok(());
     };
 
-    // Fetch DataNode, DataPod, [optional] Secret
-    let data_node_api: Api<DataNode> = Api::namespaced(client.clone(), &data_node_namespace);
-    let data_node = data_node_api.get(&data_node_name).await.map_err(|e| {
-        tracing::error!("Error getting DataNode: {:?}", e);
-        Status::internal("Error getting DataNode")
+    let node_api: Api<k8s_openapi::api::core::v1::Node> = Api::all(client);
+    let node = node_api.get(node_id).await.map_err(|e| {
+        tracing::error!("Error getting Node: {:?}", e);
+        Status::internal("Error getting Node")
     })?;
 
-    let data_pod_api: Api<DataPod> = Api::namespaced(client.clone(), &data_pod_namespace);
-    let data_pod = data_pod_api.get(&data_pod_name).await.map_err(|e| {
-        tracing::error!("Error getting DataPod: {:?}", e);
-        Status::internal("Error getting DataPod")
-    })?;
+    let labels = node.labels();
+    if let Some((key, value)) = selector
+        .iter()
+        .find(|(key, value)| labels.get(*key) != Some(*value))
+    {
+        return Err(Status::failed_precondition(format!(
+            "DataNode {} is not reachable from node {}, missing label {}={}",
+            data_node.name_any(),
+            node_id,
+            key,
+            value
+        )));
+    }
+
+    Ok(())
+}
+
+async fn get_full_data_mount(
+    client: Client,
+    volume_context: &VolumeContext,
+) -> Result<FullDataSource, Status> {
+    // Fetch DataNode, DataPod, [optional] Secret
+    let data_node = if volume_context.cluster_data_node {
+        get_cluster_data_node(&client, &volume_context.data_node_name).await?
+    } else {
+        let data_node_api: Api<DataNode> =
+            Api::namespaced(client.clone(), &volume_context.data_node_namespace);
+        data_node_api
+            .get(&volume_context.data_node_name)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error getting DataNode: {:?}", e);
+                Status::internal("Error getting DataNode")
+            })?
+    };
+    let data_node = resolve_data_node(&client, data_node).await?;
+
+    let data_pod_api: Api<DataPod> =
+        Api::namespaced(client.clone(), &volume_context.data_pod_namespace);
+    let data_pod = data_pod_api
+        .get(&volume_context.data_pod_name)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error getting DataPod: {:?}", e);
+            Status::internal("Error getting DataPod")
+        })?;
 
     let secret = match data_node.spec.secret_ref.as_ref() {
         None => None,
@@ -301,14 +868,20 @@ async fn get_full_data_mount(
         }
     };
 
-    Ok((
-        FullDataSource {
-            source: data_node,
-            pod: data_pod,
-            secret,
-        },
-        mount_mode,
-    ))
+    Ok(FullDataSource {
+        source: data_node,
+        pod: data_pod,
+        secret,
+        cluster_scoped: volume_context.cluster_data_node,
+    })
+}
+
+/// Custom verb on the DataPod that allows mounting it with the given access
+fn mount_verb(mount: &MountAccess) -> &'static str {
+    match mount {
+        MountAccess::CacheReadOnly | MountAccess::FuseReadOnly => "mount",
+        MountAccess::CacheReadWrite | MountAccess::FuseReadWrite => "mount-write",
+    }
 }
 
 #[tracing::instrument(skip(client, full_data_source, pod_info))]
@@ -316,79 +889,76 @@ async fn check_access(
     client: Client,
     full_data_source: &FullDataSource,
     pod_info: &PodInfo,
+    mount: &MountAccess,
 ) -> Result<(), Status> {
     let auth_api: Api<SubjectAccessReview> = Api::all(client.clone());
-    let response = auth_api
-        .create(
-            &PostParams::default(),
-            &SubjectAccessReview {
-                metadata: Default::default(),
-                spec: SubjectAccessReviewSpec {
-                    resource_attributes: Some(ResourceAttributes {
-                        group: Some("kubedal.arunaengine.org".to_string()),
-                        name: full_data_source.source.metadata.name.clone(),
-                        namespace: full_data_source.source.metadata.namespace.clone(),
-                        resource: Some("datanodes".to_string()),
-                        verb: Some("get".to_string()),
-                        ..Default::default()
-                    }),
-                    user: Some(format!(
-                        "system:serviceaccount:{}:{}",
-                        pod_info.namespace, pod_info.service_account
-                    )),
-                    ..Default::default()
-                },
-                status: None,
-            },
-        )
-        .await
-        .map_err(|_| Status::internal("SubjectAccessReview failed"))?;
-    tracing::info!("SubjectAccessReview response: {:#?}", &response);
+    let user = format!(
+        "system:serviceaccount:{}:{}",
+        pod_info.namespace, pod_info.service_account
+    );
 
-    if let Some(status) = response.status {
-        if !status.allowed {
-            return Err(Status::permission_denied(format!(
-                "system:serviceaccount:{}:{}, has unsufficient permission to GET datanode.kubedal.arunaengine.org {:?} in namespace {:?}",
-                pod_info.namespace,
-                pod_info.service_account,
-                full_data_source
-                    .source
-                    .metadata
-                    .name
-                    .as_ref()
-                    .unwrap_or(&"".to_string()),
-                full_data_source
-                    .source
-                    .metadata
-                    .namespace
-                    .as_ref()
-                    .unwrap_or(&"".to_string())
-            )));
-        }
+    let data_node = &full_data_source.source.metadata;
+    let data_pod = &full_data_source.pod.metadata;
+    let (data_node_namespace, data_node_resource) = if full_data_source.cluster_scoped {
+        (None, "clusterdatanodes")
     } else {
-        return Err(Status::permission_denied(
-            "SubjectAccessReview did not return status",
-        ));
+        (data_node.namespace.clone(), "datanodes")
+    };
+    let mut reviews = vec![
+        ResourceAttributes {
+            group: Some("kubedal.arunaengine.org".to_string()),
+            name: data_node.name.clone(),
+            namespace: data_node_namespace,
+            resource: Some(data_node_resource.to_string()),
+            verb: Some("get".to_string()),
+            ..Default::default()
+        },
+        // Read and write access to the data are granted separately through custom verbs
+        ResourceAttributes {
+            group: Some("kubedal.arunaengine.org".to_string()),
+            name: data_pod.name.clone(),
+            namespace: data_pod.namespace.clone(),
+            resource: Some("datapods".to_string()),
+            verb: Some(mount_verb(mount).to_string()),
+            ..Default::default()
+        },
+    ];
+    // Secrets of ClusterDataNodes are used on behalf of all pods allowed to get it
+    if let Some(secret) = full_data_source
+        .secret
+        .as_ref()
+        .filter(|_| !full_data_source.cluster_scoped)
+    {
+        reviews.push(ResourceAttributes {
+            name: secret.metadata.name.clone(),
+            namespace: secret.metadata.namespace.clone(),
+            resource: Some("secrets".to_string()),
+            verb: Some("get".to_string()),
+            ..Default::default()
+        });
+    }
+    // So are the CA bundle and client certificate of the HTTP settings
+    if !full_data_source.cluster_scoped {
+        for (resource, namespace, name) in HttpSettings::references(&full_data_source.source)? {
+            reviews.push(ResourceAttributes {
+                name: Some(name),
+                namespace: Some(namespace),
+                resource: Some(resource.to_string()),
+                verb: Some("get".to_string()),
+                ..Default::default()
+            });
+        }
     }
 
-    if let Some(secret) = full_data_source.secret.as_ref() {
+    for attributes in reviews {
         let response = auth_api
             .create(
                 &PostParams::default(),
                 &SubjectAccessReview {
                     metadata: Default::default(),
                     spec: SubjectAccessReviewSpec {
-                        resource_attributes: Some(ResourceAttributes {
-                            name: secret.metadata.name.clone(),
-                            namespace: secret.metadata.namespace.clone(),
-                            resource: Some("secrets".to_string()),
-                            verb: Some("get".to_string()),
-                            ..Default::default()
-                        }),
-                        user: Some(format!(
-                            "system:serviceaccount:{}:{}",
-                            pod_info.namespace, pod_info.service_account
-                        )),
+                        resource_attributes: Some(attributes.clone()),
+                        user: Some(user.clone()),
                         ..Default::default()
                     },
                     status: None,
@@ -398,35 +968,47 @@ async fn check_access(
             .map_err(|_| Status::internal("SubjectAccessReview failed"))?;
         tracing::info!("SubjectAccessReview response: {:#?}", &response);
 
-        if let Some(status) = response.status {
-            if !status.allowed {
-                return Err(Status::permission_denied(format!(
-                    "system:serviceaccount:{}:{}, has unsufficient permission to GET secret.kubernetes.io/secret {:?} in namespace {:?}",
-                    pod_info.namespace,
-                    pod_info.service_account,
-                    secret.metadata.name.as_ref().unwrap_or(&"".to_string()),
-                    secret
-                        .metadata
-                        .namespace
-                        .as_ref()
-                        .unwrap_or(&"".to_string())
-                )));
-            }
-        } else {
+        let Some(status) = response.status else {
             return Err(Status::permission_denied(
                 "SubjectAccessReview did not return status",
             ));
+        };
+        if !status.allowed {
+            return Err(Status::permission_denied(format!(
+                "{} has insufficient permission to {} {}.{} {:?} in namespace {:?}",
+                user,
+                attributes.verb.unwrap_or_default(),
+                attributes.resource.unwrap_or_default(),
+                attributes.group.unwrap_or_else(|| "core".to_string()),
+                attributes.name.unwrap_or_default(),
+                attributes.namespace.unwrap_or_default()
+            )));
         }
     }
 
-    return Ok(());
+    Ok(())
 }
 
 impl FullDataSource {
+    /// Reference for events about the DataNode or ClusterDataNode
+    pub fn source_reference(&self) -> ObjectReference {
+        if !self.cluster_scoped {
+            return self.source.object_ref(&());
+        }
+        ObjectReference {
+            api_version: Some(ClusterDataNode::api_version(&()).to_string()),
+            kind: Some(ClusterDataNode::kind(&()).to_string()),
+            name: self.source.metadata.name.clone(),
+            uid: self.source.metadata.uid.clone(),
+            ..Default::default()
+        }
+    }
+
     pub async fn into_parts(
         self,
         client: Client,
         mount_mode: MountAccess,
+        credentials: &PublishCredentials,
     ) -> Result<(Operator, MountMode, AccessMode), Status> {
         let (mode, access) = match mount_mode {
             MountAccess::CacheReadWrite => (MountMode::Cached, AccessMode::ReadWrite),
@@ -435,6 +1017,20 @@ impl FullDataSource {
             MountAccess::FuseReadOnly => (MountMode::Fuse, AccessMode::ReadOnly),
         };
 
-        Ok((get_operator(&client, &self.source).await?, mode, access))
+        // The mount mode can be changed with annotations of the PersistentVolume after provisioning
+        check_mount(&self.source, &mount_mode)?;
+
+        // Writes would end up in the checkout shared by all volumes of a commit
+        if self.source.spec.backend.scheme() == "git" && mount_mode == MountAccess::FuseReadWrite {
+            return Err(Status::failed_precondition(
+                "Git DataNodes can not be mounted with fuse-read-write",
+            ));
+        }
+
+        Ok((
+            get_data_pod_operator(&client, &self.source, &self.pod, credentials).await?,
+            mode,
+            access,
+        ))
     }
 }
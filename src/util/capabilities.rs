@@ -0,0 +1,278 @@
+use crate::resource::crd::{DataNode, DataNodeCapabilities, MountAccess};
+use opendal::Operator;
+use tonic::Status;
+
+/// Full capability set of an operator as recorded in the status of its DataNode,
+/// including emulated operations
+pub fn from_operator(operator: &Operator) -> DataNodeCapabilities {
+    let capability = operator.info().full_capability();
+    DataNodeCapabilities {
+        stat: capability.stat,
+        stat_with_if_match: capability.stat_with_if_match,
+        stat_with_if_none_match: capability.stat_with_if_none_match,
+        stat_with_if_modified_since: capability.stat_with_if_modified_since,
+        stat_with_if_unmodified_since: capability.stat_with_if_unmodified_since,
+        stat_with_override_cache_control: capability.stat_with_override_cache_control,
+        stat_with_override_content_disposition: capability.stat_with_override_content_disposition,
+        stat_with_override_content_type: capability.stat_with_override_content_type,
+        stat_with_version: capability.stat_with_version,
+        stat_has_cache_control: capability.stat_has_cache_control,
+        stat_has_content_disposition: capability.stat_has_content_disposition,
+        stat_has_content_length: capability.stat_has_content_length,
+        stat_has_content_md5: capability.stat_has_content_md5,
+        stat_has_content_range: capability.stat_has_content_range,
+        stat_has_content_type: capability.stat_has_content_type,
+        stat_has_content_encoding: capability.stat_has_content_encoding,
+        stat_has_etag: capability.stat_has_etag,
+        stat_has_last_modified: capability.stat_has_last_modified,
+        stat_has_version: capability.stat_has_version,
+        stat_has_user_metadata: capability.stat_has_user_metadata,
+        read: capability.read,
+        read_with_if_match: capability.read_with_if_match,
+        read_with_if_none_match: capability.read_with_if_none_match,
+        read_with_if_modified_since: capability.read_with_if_modified_since,
+        read_with_if_unmodified_since: capability.read_with_if_unmodified_since,
+        read_with_override_cache_control: capability.read_with_override_cache_control,
+        read_with_override_content_disposition: capability.read_with_override_content_disposition,
+        read_with_override_content_type: capability.read_with_override_content_type,
+        read_with_version: capability.read_with_version,
+        write: capability.write,
+        write_can_multi: capability.write_can_multi,
+        write_can_empty: capability.write_can_empty,
+        write_can_append: capability.write_can_append,
+        write_with_content_type: capability.write_with_content_type,
+        write_with_content_disposition: capability.write_with_content_disposition,
+        write_with_content_encoding: capability.write_with_content_encoding,
+        write_with_cache_control: capability.write_with_cache_control,
+        write_with_if_match: capability.write_with_if_match,
+        write_with_if_none_match: capability.write_with_if_none_match,
+        write_with_if_not_exists: capability.write_with_if_not_exists,
+        write_with_user_metadata: capability.write_with_user_metadata,
+        write_multi_max_size: capability.write_multi_max_size.map(|size| size as u64),
+        write_multi_min_size: capability.write_multi_min_size.map(|size| size as u64),
+        write_total_max_size: capability.write_total_max_size.map(|size| size as u64),
+        write_has_content_length: capability.write_has_content_length,
+        write_has_last_modified: capability.write_has_last_modified,
+        write_has_etag: capability.write_has_etag,
+        write_has_version: capability.write_has_version,
+        create_dir: capability.create_dir,
+        delete: capability.delete,
+        delete_with_version: capability.delete_with_version,
+        delete_max_size: capability.delete_max_size.map(|size| size as u64),
+        copy: capability.copy,
+        rename: capability.rename,
+        list: capability.list,
+        list_with_limit: capability.list_with_limit,
+        list_with_start_after: capability.list_with_start_after,
+        list_with_recursive: capability.list_with_recursive,
+        list_with_versions: capability.list_with_versions,
+        list_with_deleted: capability.list_with_deleted,
+        list_has_cache_control: capability.list_has_cache_control,
+        list_has_content_disposition: capability.list_has_content_disposition,
+        list_has_content_length: capability.list_has_content_length,
+        list_has_content_md5: capability.list_has_content_md5,
+        list_has_content_range: capability.list_has_content_range,
+        list_has_content_type: capability.list_has_content_type,
+        list_has_etag: capability.list_has_etag,
+        list_has_last_modified: capability.list_has_last_modified,
+        list_has_version: capability.list_has_version,
+        list_has_user_metadata: capability.list_has_user_metadata,
+        presign: capability.presign,
+        presign_read: capability.presign_read,
+        presign_stat: capability.presign_stat,
+        presign_write: capability.presign_write,
+        shared: capability.shared,
+        blocking: capability.blocking,
+    }
+}
+
+/// Operations a mount mode relies on that the backend lacks: both modes list the
+/// DataPod prefix, read-write mounts additionally write and delete objects. Read-only
+/// DataNodes lack everything that changes objects, whatever their backend supports.
+fn missing_for_mount(
+    capabilities: &DataNodeCapabilities,
+    read_only: bool,
+    mount: &MountAccess,
+) -> Vec<&'static str> {
+    let writable = is_writable(mount);
+    [
+        ("read", capabilities.read),
+        ("stat", capabilities.stat),
+        ("list", capabilities.list),
+        ("write", (capabilities.write && !read_only) || !writable),
+        ("delete", (capabilities.delete && !read_only) || !writable),
+        (
+            "create_dir",
+            (capabilities.create_dir && !read_only) || *mount != MountAccess::FuseReadWrite,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, supported)| !supported)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+fn is_writable(mount: &MountAccess) -> bool {
+    matches!(
+        mount,
+        MountAccess::CacheReadWrite | MountAccess::FuseReadWrite
+    )
+}
+
+/// Checks that the DataNode and its backend support the mount mode. Read-write mounts of
+/// read-only DataNodes are refused, otherwise DataNodes that were not checked yet are not.
+pub fn check_mount(data_node: &DataNode, mount: &MountAccess) -> Result<(), Status> {
+    if data_node.spec.read_only && is_writable(mount) {
+        return Err(Status::failed_precondition(format!(
+            "DataNode '{}' is read-only and can not be mounted with {}",
+            data_node.metadata.name.clone().unwrap_or_default(),
+            mount
+        )));
+    }
+    let Some(capabilities) = capabilities(data_node) else {
+        return Ok(());
+    };
+
+    let missing = missing_for_mount(capabilities, data_node.spec.read_only, mount);
+    if !missing.is_empty() {
+        return Err(Status::failed_precondition(format!(
+            "DataNode '{}' with backend `{}` can not be mounted with {}, missing: {}",
+            data_node.metadata.name.clone().unwrap_or_default(),
+            data_node.spec.backend,
+            mount,
+            missing.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// Snapshots are copies of the DataPod prefix made on the backend
+pub fn check_snapshot(data_node: &DataNode) -> Result<(), Status> {
+    match capabilities(data_node) {
+        Some(capabilities) if !(capabilities.copy && capabilities.list) => {
+            Err(Status::failed_precondition(format!(
+                "DataNode '{}' with backend `{}` can not copy objects, snapshots are not supported",
+                data_node.metadata.name.clone().unwrap_or_default(),
+                data_node.spec.backend
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn capabilities(data_node: &DataNode) -> Option<&DataNodeCapabilities> {
+    data_node
+        .status
+        .as_ref()
+        .and_then(|status| status.capabilities.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn data_node(read_only: bool, capabilities: Option<DataNodeCapabilities>) -> DataNode {
+        let mut data_node: DataNode = serde_json::from_value(json!({
+            "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+            "kind": "DataNode",
+            "metadata": { "name": "bucket", "namespace": "storage" },
+            "spec": { "backend": "s3", "read_only": read_only },
+        }))
+        .unwrap();
+        data_node.status =
+            Some(serde_json::from_value(json!({ "available": true, "used": "0" })).unwrap());
+        data_node.status.as_mut().unwrap().capabilities = capabilities;
+        data_node
+    }
+
+    fn object_store() -> DataNodeCapabilities {
+        DataNodeCapabilities {
+            stat: true,
+            read: true,
+            write: true,
+            delete: true,
+            copy: true,
+            list: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lists_operations_missing_for_mounts() {
+        let capabilities = object_store();
+        assert!(missing_for_mount(&capabilities, false, &MountAccess::CacheReadWrite).is_empty());
+        assert_eq!(
+            missing_for_mount(&capabilities, false, &MountAccess::FuseReadWrite),
+            ["create_dir"]
+        );
+
+        let http = DataNodeCapabilities {
+            stat: true,
+            read: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            missing_for_mount(&http, false, &MountAccess::CacheReadOnly),
+            ["list"]
+        );
+        assert_eq!(
+            missing_for_mount(&http, false, &MountAccess::CacheReadWrite),
+            ["list", "write", "delete"]
+        );
+    }
+
+    #[test]
+    fn refuses_writable_mounts_of_read_only_data_nodes() {
+        assert_eq!(
+            missing_for_mount(&object_store(), true, &MountAccess::CacheReadWrite),
+            ["write", "delete"]
+        );
+        assert!(missing_for_mount(&object_store(), true, &MountAccess::FuseReadOnly).is_empty());
+
+        // Also before the capabilities of the backend are known
+        for capabilities in [Some(object_store()), None] {
+            let read_only = data_node(true, capabilities.clone());
+            assert!(check_mount(&read_only, &MountAccess::FuseReadWrite).is_err());
+            assert!(check_mount(&read_only, &MountAccess::CacheReadOnly).is_ok());
+            assert!(
+                check_mount(
+                    &data_node(false, capabilities),
+                    &MountAccess::CacheReadWrite
+                )
+                .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn requires_copy_for_snapshots() {
+        assert!(check_snapshot(&data_node(false, Some(object_store()))).is_ok());
+        assert!(check_snapshot(&data_node(false, None)).is_ok());
+        let without_copy = DataNodeCapabilities {
+            copy: false,
+            ..object_store()
+        };
+        assert!(check_snapshot(&data_node(false, Some(without_copy))).is_err());
+    }
+
+    #[cfg(feature = "services-fs")]
+    #[test]
+    fn records_the_full_capability_set() {
+        let operator = Operator::new(opendal::services::Fs::default().root("/tmp"))
+            .unwrap()
+            .finish();
+        let capabilities = from_operator(&operator);
+        assert!(capabilities.write_can_empty && capabilities.write_with_if_not_exists);
+        assert_eq!(
+            serde_json::to_value(&capabilities).unwrap()["writeCanEmpty"],
+            true
+        );
+
+        // Statuses recorded before a capability was added read it as unsupported
+        let recorded: DataNodeCapabilities =
+            serde_json::from_value(json!({ "stat": true, "read": true, "list": true })).unwrap();
+        assert!(recorded.list && !recorded.write && recorded.delete_max_size.is_none());
+    }
+}
@@ -0,0 +1,15 @@
+use crate::resource::crd::{ClusterDataNode, DataNode};
+use kube::{Api, Client};
+use tonic::Status;
+
+/// Fetches a ClusterDataNode as DataNode in the namespace of the driver,
+/// which holds the Secrets and ConfigMaps of all ClusterDataNodes
+pub async fn get_cluster_data_node(client: &Client, name: &str) -> Result<DataNode, Status> {
+    let cluster_data_node_api: Api<ClusterDataNode> = Api::all(client.clone());
+    let cluster_data_node = cluster_data_node_api.get(name).await.map_err(|e| {
+        tracing::error!("Error getting ClusterDataNode: {:?}", e);
+        Status::not_found("ClusterDataNode not found")
+    })?;
+
+    Ok(cluster_data_node.to_data_node(client.default_namespace()))
+}
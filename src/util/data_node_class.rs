@@ -0,0 +1,131 @@
+use crate::resource::crd::{DataNode, DataNodeClass};
+use crate::util::grant::check_reference_namespaces;
+use kube::{Api, Client};
+use tonic::Status;
+
+pub async fn get_data_node_class(client: &Client, name: &str) -> Result<DataNodeClass, Status> {
+    let class_api: Api<DataNodeClass> = Api::all(client.clone());
+    class_api.get(name).await.map_err(|e| {
+        tracing::error!("Error getting DataNodeClass: {:?}", e);
+        Status::not_found(format!("DataNodeClass '{}' not found", name))
+    })
+}
+
+/// Applies the DataNodeClass of a DataNode, DataNodes without class are returned as they are.
+/// The result may only reference Secrets and ConfigMaps of its own namespace.
+pub async fn resolve_data_node(client: &Client, data_node: DataNode) -> Result<DataNode, Status> {
+    let data_node = match data_node.spec.data_node_class_name.as_deref() {
+        Some(name) => get_data_node_class(client, name).await?.apply_to(data_node),
+        None => data_node,
+    };
+    check_reference_namespaces(&data_node)?;
+    Ok(data_node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+    use serde_json::json;
+
+    fn data_node(spec: serde_json::Value) -> DataNode {
+        serde_json::from_value(json!({
+            "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+            "kind": "DataNode",
+            "metadata": { "name": "bucket-a", "namespace": "analysis" },
+            "spec": spec,
+        }))
+        .unwrap()
+    }
+
+    fn class(spec: serde_json::Value) -> DataNodeClass {
+        serde_json::from_value(json!({
+            "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+            "kind": "DataNodeClass",
+            "metadata": { "name": "institute-s3" },
+            "spec": spec,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn applies_defaults_of_the_class() {
+        let class = class(json!({
+            "backend": "s3",
+            "secretRef": { "name": "credentials" },
+            "config": { "endpoint": "https://s3.example.org", "bucket": "default" },
+            "limit": "1Ti",
+            "nodeSelector": { "zone": "a" },
+        }));
+        let node = class.apply_to(data_node(json!({
+            "backend": "",
+            "dataNodeClassName": "institute-s3",
+            "config": { "bucket": "bucket-a" },
+            "limit": "10Gi",
+        })));
+
+        assert_eq!(node.spec.backend.scheme(), "s3");
+        assert_eq!(node.spec.secret_ref.unwrap().name, "credentials");
+        assert_eq!(node.spec.config["endpoint"], "https://s3.example.org");
+        assert_eq!(node.spec.config["bucket"], "bucket-a");
+        assert_eq!(node.spec.limit, Some(Quantity("10Gi".to_string())));
+        assert_eq!(node.spec.node_selector.unwrap()["zone"], "a");
+        assert_eq!(node.spec.data_node_class_name, None);
+    }
+
+    #[test]
+    fn keeps_fields_set_on_the_data_node() {
+        let class = class(json!({
+            "backend": "s3",
+            "secretRef": { "name": "credentials" },
+            "http": { "proxy": "http://proxy:3128" },
+        }));
+        let node = class.apply_to(data_node(json!({
+            "backend": "gcs",
+            "secretRef": { "name": "own-credentials" },
+            "http": { "noProxy": "example.org" },
+        })));
+
+        assert_eq!(node.spec.backend.scheme(), "gcs");
+        assert_eq!(node.spec.secret_ref.unwrap().name, "own-credentials");
+        let http = node.spec.http.unwrap();
+        assert_eq!(http.proxy, None);
+        assert_eq!(http.no_proxy.as_deref(), Some("example.org"));
+    }
+
+    #[test]
+    fn resolves_references_of_the_class_in_the_data_node_namespace() {
+        let class = class(json!({
+            "backend": "s3",
+            "secretRef": { "name": "credentials", "namespace": "kube-system" },
+            "configMapRef": { "name": "settings", "namespace": "kube-system" },
+            "http": {
+                "clientCertificateRef": { "name": "tls", "namespace": "kube-system" },
+                "caBundle": {
+                    "configMapRef": { "name": "ca", "namespace": "kube-system" },
+                    "secretRef": { "name": "ca", "namespace": "kube-system" },
+                },
+            },
+        }));
+        let node = class.apply_to(data_node(json!({ "backend": "" })));
+
+        assert_eq!(node.spec.secret_ref.as_ref().unwrap().namespace, None);
+        assert_eq!(node.spec.config_map_ref.as_ref().unwrap().namespace, None);
+        let http = node.spec.http.as_ref().unwrap();
+        assert_eq!(
+            http.client_certificate_ref.as_ref().unwrap().namespace,
+            None
+        );
+        let ca_bundle = http.ca_bundle.as_ref().unwrap();
+        assert_eq!(ca_bundle.config_map_ref.as_ref().unwrap().namespace, None);
+        assert_eq!(ca_bundle.secret_ref.as_ref().unwrap().namespace, None);
+        check_reference_namespaces(&node).unwrap();
+
+        // References set on the DataNode itself are still checked
+        let node = class.apply_to(data_node(json!({
+            "backend": "",
+            "secretRef": { "name": "credentials", "namespace": "kube-system" },
+        })));
+        assert!(check_reference_namespaces(&node).is_err());
+    }
+}
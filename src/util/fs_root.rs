@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tonic::Status;
+
+/// Directory `fs` DataNodes are confined to, the node plugin mounts it from the host
+pub const FS_BASE: &str = "/srv/kubedal";
+
+/// Options of the fs service that name directories on the node
+const PATH_OPTIONS: [&str; 2] = ["root", "atomic_write_dir"];
+
+/// Resolves the directories of an `fs` DataNode, rejecting the ones outside of `FS_BASE`
+pub fn confine_config(cfg: &mut HashMap<String, String>) -> Result<(), Status> {
+    confine_config_to(Path::new(FS_BASE), cfg)
+}
+
+fn confine_config_to(base: &Path, cfg: &mut HashMap<String, String>) -> Result<(), Status> {
+    if !cfg.contains_key("root") {
+        return Err(Status::invalid_argument(format!(
+            "fs DataNodes need a `root` below {}",
+            base.display()
+        )));
+    }
+
+    for option in PATH_OPTIONS {
+        if let Some(value) = cfg.get_mut(option) {
+            let confined = confine(base, value)?;
+            *value = confined.to_string_lossy().into_owned();
+        }
+    }
+    Ok(())
+}
+
+/// Canonical form of a path below `base`: symlinks are resolved as far as the
+/// path exists, the components that do not exist yet may only be plain names
+fn confine(base: &Path, path: &str) -> Result<PathBuf, Status> {
+    let outside =
+        || Status::invalid_argument(format!("Path {} is outside of {}", path, base.display()));
+
+    let requested = Path::new(path);
+    if !requested.is_absolute()
+        || requested
+            .components()
+            .any(|component| !matches!(component, Component::RootDir | Component::Normal(_)))
+    {
+        return Err(outside());
+    }
+
+    let mut existing = requested;
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        // The root directory always exists, so every path has an existing ancestor
+        let (Some(name), Some(parent)) = (existing.file_name(), existing.parent()) else {
+            return Err(outside());
+        };
+        missing.push(name);
+        existing = parent;
+    }
+
+    let mut resolved = existing
+        .canonicalize()
+        .map_err(|e| Status::invalid_argument(format!("Failed to resolve {}: {}", path, e)))?;
+    resolved.extend(missing.iter().rev());
+
+    // The base does not exist where the DataNode is only checked, e.g. in the controller
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    if !resolved.starts_with(&base) {
+        return Err(outside());
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("kubedal-fs-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(path.join("base/data")).unwrap();
+            std::fs::create_dir_all(path.join("outside")).unwrap();
+            TempDir(path.canonicalize().unwrap())
+        }
+
+        fn base(&self) -> PathBuf {
+            self.0.join("base")
+        }
+
+        fn path(&self, relative: &str) -> String {
+            self.0.join(relative).to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn accepts_paths_below_the_base() {
+        let dir = TempDir::new();
+        assert_eq!(
+            confine(&dir.base(), &dir.path("base/data")).unwrap(),
+            dir.base().join("data")
+        );
+        // Roots that do not exist yet are created by the fs service
+        assert_eq!(
+            confine(&dir.base(), &dir.path("base/data/new/dir")).unwrap(),
+            dir.base().join("data/new/dir")
+        );
+    }
+
+    #[test]
+    fn rejects_paths_outside_of_the_base() {
+        let dir = TempDir::new();
+        assert!(confine(&dir.base(), &dir.path("outside")).is_err());
+        assert!(confine(&dir.base(), &dir.path("base/../outside")).is_err());
+        assert!(confine(&dir.base(), "base/data").is_err());
+        assert!(confine(&dir.base(), "/").is_err());
+    }
+
+    #[test]
+    fn resolves_symlinks() {
+        let dir = TempDir::new();
+        symlink(dir.path("outside"), dir.path("base/escape")).unwrap();
+        symlink(dir.path("base/data"), dir.path("base/alias")).unwrap();
+
+        assert!(confine(&dir.base(), &dir.path("base/escape")).is_err());
+        assert!(confine(&dir.base(), &dir.path("base/escape/new")).is_err());
+        assert_eq!(
+            confine(&dir.base(), &dir.path("base/alias")).unwrap(),
+            dir.base().join("data")
+        );
+    }
+
+    #[test]
+    fn confines_all_path_options() {
+        let dir = TempDir::new();
+        let mut cfg = HashMap::from([
+            ("root".to_string(), dir.path("base/data")),
+            ("atomic_write_dir".to_string(), dir.path("outside")),
+        ]);
+        assert!(confine_config_to(&dir.base(), &mut cfg).is_err());
+
+        cfg.insert("atomic_write_dir".to_string(), dir.path("base/tmp"));
+        confine_config_to(&dir.base(), &mut cfg).unwrap();
+        assert_eq!(cfg["atomic_write_dir"], dir.path("base/tmp"));
+
+        assert!(confine_config_to(&dir.base(), &mut HashMap::new()).is_err());
+    }
+}
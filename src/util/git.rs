@@ -0,0 +1,725 @@
+use crate::resource::crd::DataNodeCapabilities;
+use crate::util::opendal::init_service;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use opendal::raw::{
+    Access, Layer, LayeredAccess, OpList, OpRead, OpWrite, RpDelete, RpList, RpRead, RpWrite,
+};
+use opendal::{Operator, services};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+use tokio::process::Command;
+use tonic::Status;
+
+/// Config key of the repository URL
+pub const URL_KEY: &str = "url";
+/// Config key of the branch, tag or commit, defaults to the default branch
+pub const REF_KEY: &str = "ref";
+/// Config key of the hosts reached without the proxy, git takes them from `no_proxy`
+pub const NO_PROXY_KEY: &str = "http.noProxy";
+/// Options written by `HttpSettings::git_config`, the only ones passed on to git:
+/// options like `http.cookieFile` or `http.saveCookies` read and write files of the node
+const HTTP_OPTIONS: [&str; 4] = [
+    "http.sslCAInfo",
+    "http.sslCert",
+    "http.sslKey",
+    "http.proxy",
+];
+
+// Checkouts are shared by all volumes of the same commit and DataNode, below its state directory
+const CHECKOUT_DIR: &str = "/mnt/kubedal-git";
+// Depth of the checkouts below `CHECKOUT_DIR`, `<kind>/<namespace>/<name>/<commit>`
+const CHECKOUT_DEPTH: usize = 4;
+// Unused checkouts are kept this long for volumes of the same commit published later
+const CHECKOUT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const COLLECT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// Only remote transports, `file://`, `ext::` and friends would give access to the node
+const ALLOWED_PROTOCOLS: [&str; 3] = ["https", "ssh", "git"];
+
+// Checkouts with an operator over them, by path
+static LEASES: LazyLock<Mutex<HashMap<PathBuf, Weak<CheckoutLease>>>> =
+    LazyLock::new(Default::default);
+
+/// Resolves the configured ref of a repository to a commit SHA
+pub async fn resolve_commit(cfg: &HashMap<String, String>) -> Result<String, Status> {
+    let url = repository_url(cfg)?;
+    let reference = configured_ref(cfg);
+    if is_commit(reference) {
+        return Ok(reference.to_ascii_lowercase());
+    }
+
+    check_argument(reference)?;
+
+    // Patterns match whole trailing components, the peeled tags need their own
+    let peeled = format!("{}^{{}}", reference);
+    let output = git(cfg, None, &["ls-remote", "--", url, reference, &peeled]).await?;
+    select_ref(&output, reference)
+        .ok_or_else(|| Status::not_found(format!("Ref {} not found in {}", reference, url)))
+}
+
+/// Ref of the configuration, the default branch if none is set
+pub fn configured_ref(cfg: &HashMap<String, String>) -> &str {
+    cfg.get(REF_KEY).map(String::as_str).unwrap_or("HEAD")
+}
+
+/// Checks that the repository is reachable and has the configured ref with `ls-remote`,
+/// without fetching anything. Servers do not list commits, for those only the repository is checked.
+pub async fn check_repository(cfg: &HashMap<String, String>) -> Result<(), Status> {
+    let reference = configured_ref(cfg);
+    if is_commit(reference) {
+        let url = repository_url(cfg)?;
+        git(cfg, None, &["ls-remote", "--", url, "HEAD"]).await?;
+        return Ok(());
+    }
+    resolve_commit(cfg).await.map(|_| ())
+}
+
+/// Operations on checkouts, which are only read: they are shared by all volumes of a commit
+pub fn capabilities() -> DataNodeCapabilities {
+    DataNodeCapabilities {
+        stat: true,
+        read: true,
+        list: true,
+        list_with_recursive: true,
+        ..Default::default()
+    }
+}
+
+/// Commit of a ref in the output of `ls-remote`. Names are matched exactly as full
+/// ref, tag or branch, tags taking precedence like in git. Annotated tags are listed
+/// twice, their peeled entry names the commit.
+fn select_ref(output: &str, reference: &str) -> Option<String> {
+    let refs: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(commit, name)| (name, commit))
+        .collect();
+
+    // `<ref>^{}` would match the peeled entries themselves
+    if reference.contains('^') {
+        return None;
+    }
+
+    let names = if reference == "HEAD" || reference.starts_with("refs/") {
+        vec![format!("{}^{{}}", reference), reference.to_string()]
+    } else {
+        vec![
+            format!("refs/tags/{}^{{}}", reference),
+            format!("refs/tags/{}", reference),
+            format!("refs/heads/{}", reference),
+        ]
+    };
+    names
+        .iter()
+        .find_map(|name| refs.get(name.as_str()))
+        .map(|commit| commit.to_string())
+}
+
+/// Builds a read-only operator over a checkout of the given or resolved commit,
+/// optionally rooted at a subdirectory. Checkouts are kept below the state
+/// directory of the DataNode.
+pub async fn init_git(
+    cfg: HashMap<String, String>,
+    commit: Option<&str>,
+    subdirectory: Option<&str>,
+    state_dir: &Path,
+) -> Result<Operator, Status> {
+    let commit = match commit {
+        Some(commit) => commit.to_string(),
+        None => resolve_commit(&cfg).await?,
+    };
+    let dir = Path::new(CHECKOUT_DIR).join(state_dir);
+    let (checkout, lease) = checkout(&dir, &cfg, &commit).await?;
+
+    let subdirectory = Path::new(subdirectory.unwrap_or_default().trim_start_matches('/'));
+    if subdirectory
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(Status::invalid_argument(format!(
+            "Invalid repository subdirectory: {}",
+            subdirectory.display()
+        )));
+    }
+    let root = checkout.join(subdirectory);
+    if !root.is_dir() {
+        return Err(Status::not_found(format!(
+            "Directory {} not found in commit {}",
+            subdirectory.display(),
+            commit
+        )));
+    }
+
+    let operator = init_service::<services::Fs>(
+        HashMap::from([("root".to_string(), root.to_string_lossy().to_string())]),
+        None,
+    )?;
+    Ok(operator.layer(LeaseLayer(lease)))
+}
+
+/// Checks out a commit once, without the repository metadata. The checkout is
+/// not collected while the lease exists.
+async fn checkout(
+    dir: &Path,
+    cfg: &HashMap<String, String>,
+    commit: &str,
+) -> Result<(PathBuf, Arc<CheckoutLease>), Status> {
+    if !is_commit(commit) {
+        return Err(Status::invalid_argument(format!(
+            "Invalid commit SHA: {}",
+            commit
+        )));
+    }
+    let commit = commit.to_ascii_lowercase();
+    let target = dir.join(&commit);
+    let lease = CheckoutLease::acquire(&target);
+    if target.is_dir() {
+        touch(&target);
+        return Ok((target, lease));
+    }
+
+    let url = repository_url(cfg)?;
+    let temp = dir.join(format!(".tmp-{}", uuid::Uuid::new_v4()));
+    let result = async {
+        tokio::fs::create_dir_all(&temp)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to create checkout: {}", e)))?;
+        git(cfg, Some(&temp), &["init", "-q"]).await?;
+
+        // Servers may refuse to serve unadvertised commits, the ref is fetched with history then
+        if git(
+            cfg,
+            Some(&temp),
+            &["fetch", "-q", "--depth", "1", "--", url, &commit],
+        )
+        .await
+        .is_err()
+        {
+            let reference = configured_ref(cfg);
+            check_argument(reference)?;
+            git(cfg, Some(&temp), &["fetch", "-q", "--", url, reference]).await?;
+        }
+        git(
+            cfg,
+            Some(&temp),
+            &["checkout", "-q", "--detach", &commit, "--"],
+        )
+        .await?;
+
+        tokio::fs::remove_dir_all(temp.join(".git"))
+            .await
+            .map_err(|e| Status::internal(format!("Failed to clean checkout: {}", e)))?;
+        Ok::<_, Status>(())
+    }
+    .await;
+
+    // Concurrent checkouts of the same commit are equal, the first one wins
+    let result = match result {
+        Ok(()) => match tokio::fs::rename(&temp, &target).await {
+            Ok(()) => Ok((target, lease)),
+            Err(_) if target.is_dir() => Ok((target, lease)),
+            Err(e) => Err(Status::internal(format!("Failed to store checkout: {}", e))),
+        },
+        Err(e) => Err(e),
+    };
+    if temp.exists() {
+        let _ = tokio::fs::remove_dir_all(&temp).await;
+    }
+    result
+}
+
+/// Runs git non-interactively, with HTTP basic credentials if configured.
+/// Config keys starting with `http.` are passed on as git options.
+async fn git(
+    cfg: &HashMap<String, String>,
+    dir: Option<&Path>,
+    args: &[&str],
+) -> Result<String, Status> {
+    let mut command = Command::new("git");
+    // Options are passed in the environment, unlike arguments it is only readable by the driver
+    let mut options: Vec<(&str, String)> = http_options(cfg)
+        .map(|(key, value)| (key, value.to_string()))
+        .collect();
+    if let (Some(username), Some(password)) = (cfg.get("username"), cfg.get("password")) {
+        let credentials = STANDARD.encode(format!("{}:{}", username, password));
+        options.push((
+            "http.extraHeader",
+            format!("Authorization: Basic {}", credentials),
+        ));
+    }
+    command.env("GIT_CONFIG_COUNT", options.len().to_string());
+    for (index, (key, value)) in options.into_iter().enumerate() {
+        command
+            .env(format!("GIT_CONFIG_KEY_{}", index), key)
+            .env(format!("GIT_CONFIG_VALUE_{}", index), value);
+    }
+    if let Some(no_proxy) = cfg.get(NO_PROXY_KEY) {
+        command.env("no_proxy", no_proxy);
+    }
+    command.arg("-c").arg("protocol.allow=never");
+    for protocol in ALLOWED_PROTOCOLS {
+        command
+            .arg("-c")
+            .arg(format!("protocol.{}.allow=always", protocol));
+    }
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+
+    let output = command
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Status::internal(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(Status::unavailable(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// TLS and proxy options of the config
+fn http_options(cfg: &HashMap<String, String>) -> impl Iterator<Item = (&str, &str)> {
+    HTTP_OPTIONS
+        .into_iter()
+        .filter_map(|key| cfg.get(key).map(|value| (key, value.as_str())))
+}
+
+/// URL of the repository, either with an allowed scheme or in the scp-like ssh syntax
+fn repository_url(cfg: &HashMap<String, String>) -> Result<&str, Status> {
+    let url = cfg
+        .get(URL_KEY)
+        .map(String::as_str)
+        .ok_or_else(|| Status::invalid_argument("Git DataNode requires a url"))?;
+    check_argument(url)?;
+
+    let allowed = match url.split_once("://") {
+        Some((scheme, _)) => ALLOWED_PROTOCOLS.contains(&scheme),
+        // `<transport>::<address>` names a remote helper
+        None => {
+            !url.contains("::")
+                && url
+                    .split_once(':')
+                    .is_some_and(|(host, _)| !host.is_empty() && !host.contains('/'))
+        }
+    };
+    if !allowed {
+        return Err(Status::invalid_argument(format!(
+            "Git repositories must be reached through {}: {}",
+            ALLOWED_PROTOCOLS.join(", "),
+            url
+        )));
+    }
+    Ok(url)
+}
+
+/// Values that git could take for options
+fn check_argument(value: &str) -> Result<(), Status> {
+    if value.is_empty() || value.starts_with('-') {
+        return Err(Status::invalid_argument(format!(
+            "Invalid git argument: {}",
+            value
+        )));
+    }
+    Ok(())
+}
+
+fn is_commit(reference: &str) -> bool {
+    reference.len() == 40 && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Keeps a checkout from being collected while an operator over it exists
+#[derive(Debug)]
+struct CheckoutLease;
+
+impl CheckoutLease {
+    fn acquire(checkout: &Path) -> Arc<Self> {
+        let mut leases = LEASES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(lease) = leases.get(checkout).and_then(Weak::upgrade) {
+            return lease;
+        }
+        let lease = Arc::new(CheckoutLease);
+        leases.insert(checkout.to_path_buf(), Arc::downgrade(&lease));
+        lease
+    }
+}
+
+/// Records the use of a checkout, unused ones are collected after `CHECKOUT_TTL`
+fn touch(path: &Path) {
+    let touched = std::fs::File::open(path).and_then(|dir| dir.set_modified(SystemTime::now()));
+    if let Err(e) = touched {
+        tracing::warn!("Failed to touch checkout {}: {}", path.display(), e);
+    }
+}
+
+/// Removes checkouts that are neither leased nor used within `CHECKOUT_TTL`, runs forever
+pub async fn collect_checkouts() {
+    loop {
+        tokio::time::sleep(COLLECT_INTERVAL).await;
+        let collected = tokio::task::spawn_blocking(|| {
+            remove_unused_checkouts(Path::new(CHECKOUT_DIR), CHECKOUT_DEPTH, CHECKOUT_TTL)
+        })
+        .await;
+        match collected {
+            Ok(Ok(0)) => {}
+            Ok(Ok(removed)) => tracing::info!("Removed {} unused git checkouts", removed),
+            Ok(Err(e)) => tracing::warn!("Collecting git checkouts failed: {}", e),
+            Err(e) => tracing::warn!("Collecting git checkouts failed: {}", e),
+        }
+    }
+}
+
+/// Walks the state directories down to the checkouts `depth` levels below `dir`
+fn remove_unused_checkouts(dir: &Path, depth: usize, ttl: Duration) -> std::io::Result<usize> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if depth > 1 {
+            if entry.file_type()?.is_dir() {
+                removed += remove_unused_checkouts(&entry.path(), depth - 1, ttl)?;
+            }
+            continue;
+        }
+        let unused_for = entry.metadata()?.modified()?.elapsed().unwrap_or_default();
+        if unused_for < ttl {
+            continue;
+        }
+
+        // Moved aside under the lock, a checkout leased afterwards is checked out again
+        let doomed = dir.join(format!(".gc-{}", uuid::Uuid::new_v4()));
+        {
+            let mut leases = LEASES.lock().unwrap_or_else(|e| e.into_inner());
+            leases.retain(|_, lease| lease.strong_count() > 0);
+            if leases.contains_key(&entry.path()) {
+                continue;
+            }
+            std::fs::rename(entry.path(), &doomed)?;
+        }
+        std::fs::remove_dir_all(&doomed)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Holds the lease of the checkout for as long as the operator lives
+#[derive(Debug, Clone)]
+struct LeaseLayer(Arc<CheckoutLease>);
+
+impl<A: Access> Layer<A> for LeaseLayer {
+    type LayeredAccess = LeasedAccessor<A>;
+
+    fn layer(&self, inner: A) -> Self::LayeredAccess {
+        LeasedAccessor {
+            inner,
+            _lease: self.0.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LeasedAccessor<A: Access> {
+    inner: A,
+    _lease: Arc<CheckoutLease>,
+}
+
+impl<A: Access> LayeredAccess for LeasedAccessor<A> {
+    type Inner = A;
+    type Reader = A::Reader;
+    type Writer = A::Writer;
+    type Lister = A::Lister;
+    type Deleter = A::Deleter;
+    type BlockingReader = A::BlockingReader;
+    type BlockingWriter = A::BlockingWriter;
+    type BlockingLister = A::BlockingLister;
+    type BlockingDeleter = A::BlockingDeleter;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        self.inner.read(path, args).await
+    }
+
+    async fn write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::Writer)> {
+        self.inner.write(path, args).await
+    }
+
+    async fn delete(&self) -> opendal::Result<(RpDelete, Self::Deleter)> {
+        self.inner.delete().await
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        self.inner.list(path, args).await
+    }
+
+    fn blocking_read(
+        &self,
+        path: &str,
+        args: OpRead,
+    ) -> opendal::Result<(RpRead, Self::BlockingReader)> {
+        self.inner.blocking_read(path, args)
+    }
+
+    fn blocking_write(
+        &self,
+        path: &str,
+        args: OpWrite,
+    ) -> opendal::Result<(RpWrite, Self::BlockingWriter)> {
+        self.inner.blocking_write(path, args)
+    }
+
+    fn blocking_delete(&self) -> opendal::Result<(RpDelete, Self::BlockingDeleter)> {
+        self.inner.blocking_delete()
+    }
+
+    fn blocking_list(
+        &self,
+        path: &str,
+        args: OpList,
+    ) -> opendal::Result<(RpList, Self::BlockingLister)> {
+        self.inner.blocking_list(path, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::process::{Child, Stdio};
+
+    const LS_REMOTE: &str = "\
+1111111111111111111111111111111111111111\tHEAD
+1111111111111111111111111111111111111111\trefs/heads/main
+2222222222222222222222222222222222222222\trefs/heads/release/v1
+3333333333333333333333333333333333333333\trefs/tags/v1
+4444444444444444444444444444444444444444\trefs/tags/v1^{}
+5555555555555555555555555555555555555555\trefs/heads/v1
+";
+
+    /// Bare repository with a branch and an annotated tag, served by `git daemon`
+    struct Repository {
+        dir: PathBuf,
+        daemon: Child,
+        port: u16,
+    }
+
+    impl Repository {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("kubedal-git-{}", uuid::Uuid::new_v4()));
+            let work = dir.join("work");
+            std::fs::create_dir_all(work.join("docs")).unwrap();
+            std::fs::write(work.join("docs/readme.txt"), "first").unwrap();
+            // Commits of concurrently created repositories would share checkouts otherwise
+            std::fs::write(work.join("id"), dir.to_string_lossy().as_bytes()).unwrap();
+            run(&work, &["init", "-q", "-b", "main"]);
+            run(&work, &["add", "."]);
+            run(&work, &["commit", "-q", "-m", "first"]);
+            run(&work, &["tag", "-a", "-m", "release", "v1"]);
+            std::fs::write(work.join("docs/readme.txt"), "second").unwrap();
+            run(&work, &["commit", "-q", "-am", "second"]);
+            run(&dir, &["clone", "-q", "--bare", "work", "repo.git"]);
+
+            let port = TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            // `git daemon` would leave the daemon running when killed
+            let exec_path = run(&dir, &["--exec-path"]);
+            let daemon = std::process::Command::new(Path::new(&exec_path).join("git-daemon"))
+                .arg("--export-all")
+                .arg("--reuseaddr")
+                .arg("--listen=127.0.0.1")
+                .arg(format!("--port={}", port))
+                .arg(format!("--base-path={}", dir.display()))
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+            for _ in 0..50 {
+                if std::net::TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Repository { dir, daemon, port }
+        }
+
+        fn cfg(&self, reference: &str) -> HashMap<String, String> {
+            HashMap::from([
+                (
+                    URL_KEY.to_string(),
+                    format!("git://127.0.0.1:{}/repo.git", self.port),
+                ),
+                (REF_KEY.to_string(), reference.to_string()),
+            ])
+        }
+
+        fn rev_parse(&self, reference: &str) -> String {
+            run(&self.dir.join("repo.git"), &["rev-parse", reference])
+        }
+    }
+
+    impl Drop for Repository {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=kubedal",
+                "-c",
+                "user.email=kubedal@example.org",
+            ])
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn selects_refs_exactly() {
+        let commit = |reference| select_ref(LS_REMOTE, reference);
+        assert_eq!(commit("HEAD").unwrap(), "1".repeat(40));
+        assert_eq!(commit("main").unwrap(), "1".repeat(40));
+        assert_eq!(commit("release/v1").unwrap(), "2".repeat(40));
+        // Peeled annotated tags win over the tag object and branches of the same name
+        assert_eq!(commit("v1").unwrap(), "4".repeat(40));
+        assert_eq!(commit("refs/heads/v1").unwrap(), "5".repeat(40));
+        // Suffixes of other refs do not match
+        assert_eq!(commit("v1^{}"), None);
+        assert_eq!(commit("release"), None);
+        assert_eq!(commit("heads/main"), None);
+    }
+
+    #[test]
+    fn rejects_options_and_local_transports() {
+        let allowed = |url: &str| {
+            repository_url(&HashMap::from([(URL_KEY.to_string(), url.to_string())])).is_ok()
+        };
+        assert!(allowed("https://example.org/repo.git"));
+        assert!(allowed("ssh://git@example.org/repo.git"));
+        assert!(allowed("git@example.org:repo.git"));
+        assert!(allowed("git://example.org/repo.git"));
+        assert!(!allowed("file:///srv/repo.git"));
+        assert!(!allowed("/srv/repo.git"));
+        assert!(!allowed("./repo:git"));
+        assert!(!allowed("ext::sh -c touch% /tmp/pwned"));
+        assert!(!allowed("--upload-pack=touch /tmp/pwned"));
+
+        assert!(check_argument("main").is_ok());
+        assert!(check_argument("--output=/tmp/pwned").is_err());
+        assert!(check_argument("").is_err());
+    }
+
+    #[test]
+    fn passes_only_http_settings() {
+        let cfg = HashMap::from([
+            ("http.cookieFile".to_string(), "/etc/shadow".to_string()),
+            ("http.saveCookies".to_string(), "true".to_string()),
+            ("http.proxy".to_string(), "http://proxy:3128".to_string()),
+        ]);
+        assert_eq!(
+            http_options(&cfg).collect::<Vec<_>>(),
+            vec![("http.proxy", "http://proxy:3128")]
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_credentials_in_the_environment() {
+        let cfg = HashMap::from([
+            ("username".to_string(), "user".to_string()),
+            ("password".to_string(), "secret".to_string()),
+        ]);
+        let header = git(&cfg, None, &["config", "--get", "http.extraHeader"])
+            .await
+            .unwrap();
+        assert_eq!(header.trim(), "Authorization: Basic dXNlcjpzZWNyZXQ=");
+    }
+
+    #[tokio::test]
+    async fn resolves_and_checks_out_refs() {
+        let repo = Repository::new();
+        let head = repo.rev_parse("main");
+        let tagged = repo.rev_parse("v1^{}");
+        assert_eq!(resolve_commit(&repo.cfg("main")).await.unwrap(), head);
+        assert_eq!(resolve_commit(&repo.cfg("v1")).await.unwrap(), tagged);
+        assert!(resolve_commit(&repo.cfg("ain")).await.is_err());
+        assert!(
+            resolve_commit(&repo.cfg("--upload-pack=true"))
+                .await
+                .is_err()
+        );
+
+        let dir = repo.dir.join("checkouts");
+        let (checkout, _lease) = checkout(&dir, &repo.cfg("v1"), &tagged).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(checkout.join("docs/readme.txt")).unwrap(),
+            "first"
+        );
+        assert!(!checkout.join(".git").exists());
+    }
+
+    #[tokio::test]
+    async fn checks_repositories_without_fetching() {
+        let repo = Repository::new();
+        check_repository(&repo.cfg("main")).await.unwrap();
+        check_repository(&repo.cfg(&repo.rev_parse("v1^{}")))
+            .await
+            .unwrap();
+
+        let missing = check_repository(&repo.cfg("gone")).await.unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::NotFound);
+        let mut unreachable = repo.cfg("main");
+        unreachable.insert(
+            URL_KEY.to_string(),
+            "git://127.0.0.1:1/repo.git".to_string(),
+        );
+        let unreachable = check_repository(&unreachable).await.unwrap_err();
+        assert_eq!(unreachable.code(), tonic::Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn collects_unleased_checkouts() {
+        let repo = Repository::new();
+        let root = repo.dir.join("checkouts");
+        let dir = root.join("datanodes/default/repo");
+        let other = root.join("clusterdatanodes/default/repo");
+        let head = repo.rev_parse("main");
+        let tagged = repo.rev_parse("v1^{}");
+        let (used, lease) = checkout(&dir, &repo.cfg("main"), &head).await.unwrap();
+        let (unused, _) = checkout(&dir, &repo.cfg("v1"), &tagged).await.unwrap();
+        let (same_commit, _) = checkout(&other, &repo.cfg("main"), &head).await.unwrap();
+
+        let collect = |ttl| remove_unused_checkouts(&root, CHECKOUT_DEPTH, ttl).unwrap();
+        assert_eq!(collect(CHECKOUT_TTL), 0);
+        assert_eq!(collect(Duration::ZERO), 2);
+        assert!(used.is_dir());
+        assert!(!unused.exists());
+        assert!(!same_commit.exists());
+
+        drop(lease);
+        assert_eq!(collect(Duration::ZERO), 1);
+        assert!(!used.exists());
+    }
+}
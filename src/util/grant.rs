@@ -0,0 +1,157 @@
+use crate::resource::crd::{DataNode, DataNodeGrant};
+use kube::api::ListParams;
+use kube::{Api, Client, ResourceExt};
+use tonic::Status;
+
+/// Checks that resources of `from_namespace` may reference the DataNode, either
+/// because it lives in the same namespace or a DataNodeGrant in its namespace allows it
+pub async fn check_data_node_grant(
+    client: &Client,
+    from_namespace: &str,
+    data_node_namespace: &str,
+    data_node_name: &str,
+) -> Result<(), Status> {
+    if from_namespace == data_node_namespace {
+        return Ok(());
+    }
+
+    let grant_api: Api<DataNodeGrant> = Api::namespaced(client.clone(), data_node_namespace);
+    let grants = grant_api.list(&ListParams::default()).await.map_err(|e| {
+        tracing::error!("Error listing DataNodeGrants: {:?}", e);
+        Status::internal("Error listing DataNodeGrants")
+    })?;
+
+    if grants
+        .items
+        .iter()
+        .any(|grant| grant.spec.allows(from_namespace, data_node_name))
+    {
+        return Ok(());
+    }
+
+    Err(Status::permission_denied(format!(
+        "No DataNodeGrant in namespace '{}' allows namespace '{}' to reference DataNode '{}'",
+        data_node_namespace, from_namespace, data_node_name
+    )))
+}
+
+/// Rejects Secret and ConfigMap references of a DataNode into other namespaces. The
+/// driver reads them with its own permissions, which would bypass the RBAC of that
+/// namespace, and DataNodeGrants only cover references to DataNodes.
+pub fn check_reference_namespaces(data_node: &DataNode) -> Result<(), Status> {
+    let spec = &data_node.spec;
+    let http = spec.http.as_ref();
+    let ca_bundle = http.and_then(|http| http.ca_bundle.as_ref());
+    let references = [
+        (
+            "secretRef",
+            spec.secret_ref
+                .as_ref()
+                .and_then(|source| source.namespace.as_ref()),
+        ),
+        (
+            "configMapRef",
+            spec.config_map_ref
+                .as_ref()
+                .and_then(|source| source.namespace.as_ref()),
+        ),
+        (
+            "http.clientCertificateRef",
+            http.and_then(|http| http.client_certificate_ref.as_ref())
+                .and_then(|reference| reference.namespace.as_ref()),
+        ),
+        (
+            "http.caBundle.configMapRef",
+            ca_bundle
+                .and_then(|selector| selector.config_map_ref.as_ref())
+                .and_then(|reference| reference.namespace.as_ref()),
+        ),
+        (
+            "http.caBundle.secretRef",
+            ca_bundle
+                .and_then(|selector| selector.secret_ref.as_ref())
+                .and_then(|reference| reference.namespace.as_ref()),
+        ),
+    ];
+
+    let own_namespace = data_node.namespace();
+    for (field, namespace) in references {
+        if let Some(namespace) =
+            namespace.filter(|namespace| Some(*namespace) != own_namespace.as_ref())
+        {
+            return Err(Status::invalid_argument(format!(
+                "{} of DataNode '{}' references namespace '{}', only its own namespace is allowed",
+                field,
+                data_node.name_any(),
+                namespace
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::crd::{DataNodeGrantSpec, GrantNamespace};
+
+    fn grant(namespaces: &[&str], data_node_names: &[&str]) -> DataNodeGrantSpec {
+        DataNodeGrantSpec {
+            from: namespaces
+                .iter()
+                .map(|namespace| GrantNamespace {
+                    namespace: namespace.to_string(),
+                })
+                .collect(),
+            data_node_names: data_node_names
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        }
+    }
+
+    fn data_node(spec: serde_json::Value) -> DataNode {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+            "kind": "DataNode",
+            "metadata": { "name": "shared", "namespace": "storage" },
+            "spec": spec,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn grants_namespaces_and_data_nodes() {
+        let spec = grant(&["analysis"], &[]);
+        assert!(spec.allows("analysis", "shared"));
+        assert!(spec.allows("analysis", "other"));
+        assert!(!spec.allows("other", "shared"));
+
+        let spec = grant(&["analysis", "training"], &["shared"]);
+        assert!(spec.allows("training", "shared"));
+        assert!(!spec.allows("training", "other"));
+
+        assert!(grant(&["*"], &[]).allows("any", "shared"));
+        assert!(!grant(&[], &[]).allows("analysis", "shared"));
+    }
+
+    #[test]
+    fn rejects_references_into_other_namespaces() {
+        let own = data_node(serde_json::json!({
+            "backend": "s3",
+            "secretRef": { "name": "credentials", "namespace": "storage" },
+            "configMapRef": { "name": "settings" },
+            "http": { "caBundle": { "configMapRef": { "name": "ca" } } },
+        }));
+        check_reference_namespaces(&own).unwrap();
+
+        for spec in [
+            serde_json::json!({ "backend": "s3", "secretRef": { "name": "credentials", "namespace": "other" } }),
+            serde_json::json!({ "backend": "s3", "configMapRef": { "name": "settings", "namespace": "other" } }),
+            serde_json::json!({ "backend": "s3", "http": { "clientCertificateRef": { "name": "tls", "namespace": "other" } } }),
+            serde_json::json!({ "backend": "s3", "http": { "caBundle": { "secretRef": { "name": "ca", "namespace": "other" } } } }),
+        ] {
+            assert!(check_reference_namespaces(&data_node(spec)).is_err());
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use crate::resource::crd::{DataNode, DataNodeCapabilities, DataNodeCondition};
+use crate::util::capabilities;
+#[cfg(feature = "services-git")]
+use crate::util::data_node_class::resolve_data_node;
+#[cfg(feature = "services-git")]
+use crate::util::git;
+#[cfg(feature = "services-git")]
+use crate::util::opendal::data_node_config;
+use crate::util::opendal::{get_operator, is_supported, supported_schemes};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use k8s_openapi::chrono::Utc;
+use kube::Client;
+use kube::runtime::events::{Event, EventType};
+use opendal::ErrorKind;
+use std::time::Duration;
+#[cfg(feature = "services-git")]
+use tonic::Code;
+
+pub const READY: &str = "Ready";
+pub const CREDENTIALS_VALID: &str = "CredentialsValid";
+pub const REACHABLE: &str = "Reachable";
+
+/// Interval between two checks of an unchanged DataNode
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Outcome of checking the backend of a DataNode
+pub struct Probe {
+    pub conditions: Vec<DataNodeCondition>,
+    /// Only known if the operator could be built
+    pub capabilities: Option<DataNodeCapabilities>,
+}
+
+/// Checks the backend of a (resolved) DataNode and returns its conditions,
+/// keeping the transition times of `previous` for conditions that did not change
+pub async fn probe(client: &Client, data_node: &DataNode, previous: &[DataNodeCondition]) -> Probe {
+    // Building the operator would check the repository out in the controller
+    #[cfg(feature = "services-git")]
+    if data_node.spec.backend.scheme() == "git" {
+        return probe_git(client, data_node, previous).await;
+    }
+
+    let mut capabilities = None;
+    let observed = if !is_supported(&data_node.spec.backend) {
+        let message = format!(
+            "Unsupported backend scheme `{}`, supported: {}",
+            data_node.spec.backend,
+            supported_schemes().join(", ")
+        );
+        unknown("UnsupportedBackend", message)
+    } else {
+        match get_operator(client, data_node).await {
+            Err(status) => unknown("InvalidConfig", status.message().to_string()),
+            Ok(operator) => {
+                capabilities = Some(capabilities::from_operator(&operator));
+                match operator.check().await {
+                    Ok(()) => succeeded(),
+                    // The backend answered, but refused the credentials
+                    Err(e) if e.kind() == ErrorKind::PermissionDenied => [
+                        (READY, "False", "PermissionDenied", Some(e.to_string())),
+                        (
+                            CREDENTIALS_VALID,
+                            "False",
+                            "PermissionDenied",
+                            Some(e.to_string()),
+                        ),
+                        (REACHABLE, "True", "Responded", None),
+                    ],
+                    // The backend answered, but the bucket or root is gone
+                    Err(e) if e.kind() == ErrorKind::NotFound => not_found(e.to_string()),
+                    Err(e) => unreachable(e.to_string()),
+                }
+            }
+        }
+    };
+
+    Probe {
+        conditions: record(observed, previous),
+        capabilities,
+    }
+}
+
+/// Checks a git DataNode by listing the refs of its repository
+#[cfg(feature = "services-git")]
+async fn probe_git(client: &Client, data_node: &DataNode, previous: &[DataNodeCondition]) -> Probe {
+    let cfg = match resolve_data_node(client, data_node.clone()).await {
+        Ok(data_node) => data_node_config(client, &data_node).await,
+        Err(status) => Err(status),
+    };
+    let observed = match cfg {
+        Err(status) => unknown("InvalidConfig", status.message().to_string()),
+        Ok(cfg) => match git::check_repository(&cfg).await {
+            Ok(()) => succeeded(),
+            // The server answered, but does not have the ref
+            Err(status) if status.code() == Code::NotFound => {
+                not_found(status.message().to_string())
+            }
+            Err(status) if status.code() == Code::Unavailable => {
+                unreachable(status.message().to_string())
+            }
+            Err(status) => unknown("InvalidConfig", status.message().to_string()),
+        },
+    };
+
+    Probe {
+        conditions: record(observed, previous),
+        capabilities: Some(git::capabilities()),
+    }
+}
+/// Conditions of a DataNode whose class or references could not be resolved
+pub fn unresolved(message: &str, previous: &[DataNodeCondition]) -> Vec<DataNodeCondition> {
+    record(unknown("InvalidConfig", message.to_string()), previous)
+}
+
+type Observation = (&'static str, &'static str, &'static str, Option<String>);
+
+fn record(observed: [Observation; 3], previous: &[DataNodeCondition]) -> Vec<DataNodeCondition> {
+    let now = Time(Utc::now());
+    observed
+        .into_iter()
+        .map(|(type_, status, reason, message)| {
+            let last_transition_time = find(previous, type_)
+                .filter(|condition| condition.status == status)
+                .map(|condition| condition.last_transition_time.clone())
+                .unwrap_or_else(|| now.clone());
+            DataNodeCondition {
+                type_: type_.to_string(),
+                status: status.to_string(),
+                last_probe_time: now.clone(),
+                last_transition_time,
+                reason: reason.to_string(),
+                message,
+            }
+        })
+        .collect()
+}
+
+fn succeeded() -> [Observation; 3] {
+    [
+        (READY, "True", "CheckSucceeded", None),
+        (CREDENTIALS_VALID, "True", "CheckSucceeded", None),
+        (REACHABLE, "True", "CheckSucceeded", None),
+    ]
+}
+
+fn not_found(message: String) -> [Observation; 3] {
+    [
+        (READY, "False", "NotFound", Some(message)),
+        (CREDENTIALS_VALID, "Unknown", "NotFound", None),
+        (REACHABLE, "True", "Responded", None),
+    ]
+}
+
+fn unreachable(message: String) -> [Observation; 3] {
+    [
+        (READY, "False", "Unreachable", Some(message.clone())),
+        (CREDENTIALS_VALID, "Unknown", "Unreachable", None),
+        (REACHABLE, "False", "Unreachable", Some(message)),
+    ]
+}
+
+/// Conditions of a DataNode that could not be checked at all
+fn unknown(reason: &'static str, message: String) -> [Observation; 3] {
+    [
+        (READY, "False", reason, Some(message)),
+        (CREDENTIALS_VALID, "Unknown", reason, None),
+        (REACHABLE, "Unknown", reason, None),
+    ]
+}
+
+pub fn find<'a>(conditions: &'a [DataNodeCondition], type_: &str) -> Option<&'a DataNodeCondition> {
+    conditions.iter().find(|condition| condition.type_ == type_)
+}
+
+pub fn is_ready(conditions: &[DataNodeCondition]) -> bool {
+    find(conditions, READY).is_some_and(|condition| condition.status == "True")
+}
+
+/// Error message of the Ready condition, used for the `message` of the status
+pub fn ready_message(conditions: &[DataNodeCondition]) -> Option<String> {
+    find(conditions, READY).and_then(|condition| condition.message.clone())
+}
+
+/// Time until the next check is due, `None` if it is due now
+pub fn next_probe(conditions: &[DataNodeCondition]) -> Option<Duration> {
+    let last_probe = find(conditions, READY)?.last_probe_time.0;
+    let elapsed = (Utc::now() - last_probe).to_std().unwrap_or_default();
+    PROBE_INTERVAL.checked_sub(elapsed).filter(|d| !d.is_zero())
+}
+
+/// Conditions whose status differs from `previous`, new conditions only count if they are not True
+pub fn transitions<'a>(
+    previous: &[DataNodeCondition],
+    current: &'a [DataNodeCondition],
+) -> Vec<&'a DataNodeCondition> {
+    current
+        .iter()
+        .filter(|condition| match find(previous, &condition.type_) {
+            Some(before) => before.status != condition.status,
+            None => condition.status != "True",
+        })
+        .collect()
+}
+
+/// Event for a changed condition, Warning unless the condition became True
+pub fn transition_event(condition: &DataNodeCondition) -> Event {
+    Event {
+        type_: match condition.status.as_str() {
+            "True" => EventType::Normal,
+            _ => EventType::Warning,
+        },
+        reason: condition.reason.clone(),
+        note: Some(match condition.message.as_ref() {
+            Some(message) => format!("{} is {}: {}", condition.type_, condition.status, message),
+            None => format!("{} is {}", condition.type_, condition.status),
+        }),
+        action: "Checked".into(),
+        secondary: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::chrono::TimeDelta;
+
+    fn checked_before(observed: [Observation; 3]) -> Vec<DataNodeCondition> {
+        let before = Time(Utc::now() - TimeDelta::hours(1));
+        record(observed, &[])
+            .into_iter()
+            .map(|condition| DataNodeCondition {
+                last_probe_time: before.clone(),
+                last_transition_time: before.clone(),
+                ..condition
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_transition_times_of_unchanged_conditions() {
+        let previous = checked_before(not_found("bucket is gone".to_string()));
+        let current = record(unreachable("timed out".to_string()), &previous);
+
+        let ready = find(&current, READY).unwrap();
+        assert_eq!(ready.status, "False");
+        assert_eq!(ready.reason, "Unreachable");
+        assert_eq!(ready.message.as_deref(), Some("timed out"));
+        assert_eq!(
+            ready.last_transition_time,
+            find(&previous, READY).unwrap().last_transition_time
+        );
+        assert!(ready.last_probe_time.0 > find(&previous, READY).unwrap().last_probe_time.0);
+
+        let reachable = find(&current, REACHABLE).unwrap();
+        assert!(
+            reachable.last_transition_time.0
+                > find(&previous, REACHABLE).unwrap().last_transition_time.0
+        );
+        assert!(!is_ready(&current));
+        assert_eq!(ready_message(&current).as_deref(), Some("timed out"));
+    }
+
+    #[test]
+    fn reports_changed_and_failing_new_conditions() {
+        // Conditions that start out True are not worth an event
+        assert!(transitions(&[], &record(succeeded(), &[])).is_empty());
+
+        let failing = record(unknown("InvalidConfig", "no bucket".to_string()), &[]);
+        let reported = transitions(&[], &failing);
+        assert_eq!(reported.len(), 3);
+        assert!(
+            transitions(
+                &failing,
+                &record(unknown("InvalidConfig", "no bucket".to_string()), &failing)
+            )
+            .is_empty()
+        );
+
+        let recovered = record(succeeded(), &failing);
+        let reported = transitions(&failing, &recovered)
+            .into_iter()
+            .map(|condition| condition.type_.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(reported, [READY, CREDENTIALS_VALID, REACHABLE]);
+        assert!(is_ready(&recovered));
+        assert_eq!(
+            transition_event(find(&recovered, READY).unwrap()).type_,
+            EventType::Normal
+        );
+    }
+
+    #[test]
+    fn marks_unresolved_data_nodes_unavailable() {
+        let previous = checked_before(succeeded());
+        let conditions = unresolved("DataNodeClass 'gone' not found", &previous);
+        assert!(!is_ready(&conditions));
+        assert_eq!(find(&conditions, READY).unwrap().reason, "InvalidConfig");
+        assert_eq!(
+            transitions(&previous, &conditions)
+                .into_iter()
+                .map(|condition| condition.type_.as_str())
+                .collect::<Vec<_>>(),
+            [READY, CREDENTIALS_VALID, REACHABLE]
+        );
+        assert_eq!(
+            transition_event(find(&conditions, READY).unwrap()).type_,
+            EventType::Warning
+        );
+    }
+
+    #[test]
+    fn schedules_the_next_probe() {
+        assert!(next_probe(&record(succeeded(), &[])).is_some_and(|due| due <= PROBE_INTERVAL));
+        assert_eq!(next_probe(&checked_before(succeeded())), None);
+        assert_eq!(next_probe(&[]), None);
+    }
+}
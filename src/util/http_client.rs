@@ -0,0 +1,274 @@
+use crate::resource::crd::{DataNode, HttpClientSpec, KeySelector, Ref};
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::{Api, Client, ResourceExt};
+#[cfg(feature = "http-client")]
+use reqwest::{Certificate, Identity, NoProxy, Proxy};
+#[cfg(feature = "services-git")]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(feature = "services-git")]
+use std::path::{Path, PathBuf};
+use tonic::Status;
+
+/// Default key of a CA bundle, as used by cert-manager and service account tokens
+pub const CA_BUNDLE_KEY: &str = "ca.crt";
+/// Secret key of the client certificate, as in `kubernetes.io/tls` secrets
+pub const CLIENT_CERT_KEY: &str = "tls.crt";
+/// Secret key of the client private key, as in `kubernetes.io/tls` secrets
+pub const CLIENT_KEY_KEY: &str = "tls.key";
+
+/// Client the HTTP based services send their requests with
+#[cfg(feature = "http-client")]
+pub type BackendClient = reqwest::Client;
+
+/// Builds without HTTP based services have no client to configure
+#[cfg(not(feature = "http-client"))]
+#[derive(Debug, Clone)]
+pub enum BackendClient {}
+
+// PEM material for tools that only read files is written per DataNode below this directory
+#[cfg(feature = "services-git")]
+const TLS_DIR: &str = "/tmp/kubedal/tls";
+
+/// CA bundle, client certificate and proxy of a DataNode, resolved from its references
+#[derive(Debug, Clone, Default)]
+pub struct HttpSettings {
+    pub ca_bundle: Option<Vec<u8>>,
+    /// PEM encoded certificate and private key
+    pub client_certificate: Option<(Vec<u8>, Vec<u8>)>,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+}
+
+impl HttpSettings {
+    /// Loads the referenced ConfigMaps and Secrets, `None` if the DataNode has no HTTP settings
+    pub async fn load(client: &Client, node: &DataNode) -> Result<Option<Self>, Status> {
+        let Some(spec) = node.spec.http.as_ref() else {
+            return Ok(None);
+        };
+        let HttpClientSpec {
+            ca_bundle,
+            client_certificate_ref,
+            proxy,
+            no_proxy,
+        } = spec;
+
+        let ca_bundle = match ca_bundle {
+            Some(selector) => Some(load_key(client, node, selector).await?),
+            None => None,
+        };
+        let client_certificate = match client_certificate_ref {
+            Some(reference) => {
+                let mut data = secret_api(client, node, reference)?
+                    .get(&reference.name)
+                    .await
+                    .map_err(|_| Status::not_found("Client certificate secret not found"))?
+                    .data
+                    .unwrap_or_default();
+                let mut take = |key: &str| {
+                    data.remove(key).map(|value| value.0).ok_or_else(|| {
+                        Status::invalid_argument(format!(
+                            "Client certificate secret {} has no `{}` key",
+                            reference.name, key
+                        ))
+                    })
+                };
+                Some((take(CLIENT_CERT_KEY)?, take(CLIENT_KEY_KEY)?))
+            }
+            None => None,
+        };
+
+        Ok(Some(HttpSettings {
+            ca_bundle,
+            client_certificate,
+            proxy: proxy.clone(),
+            no_proxy: no_proxy.clone(),
+        }))
+    }
+
+    /// Secrets and ConfigMaps the settings of a DataNode are read from, as resource,
+    /// namespace and name. Namespaces default to the one of the DataNode.
+    pub fn references(node: &DataNode) -> Result<Vec<(&'static str, String, String)>, Status> {
+        let Some(spec) = node.spec.http.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let ca_bundle = spec.ca_bundle.as_ref();
+        let references = [
+            ("secrets", spec.client_certificate_ref.as_ref()),
+            (
+                "configmaps",
+                ca_bundle.and_then(|selector| selector.config_map_ref.as_ref()),
+            ),
+            (
+                "secrets",
+                ca_bundle.and_then(|selector| selector.secret_ref.as_ref()),
+            ),
+        ];
+
+        let mut objects = Vec::new();
+        for (resource, reference) in references {
+            if let Some(reference) = reference {
+                objects.push((
+                    resource,
+                    namespace(node, reference)?,
+                    reference.name.clone(),
+                ));
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Builds a client that trusts the CA bundle, authenticates with the client
+    /// certificate and sends all requests through the proxy
+    #[cfg(feature = "http-client")]
+    pub fn build_client(&self) -> Result<BackendClient, Status> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(ca_bundle) = self.ca_bundle.as_ref() {
+            let certificates = Certificate::from_pem_bundle(ca_bundle)
+                .map_err(|e| Status::invalid_argument(format!("Invalid CA bundle: {}", e)))?;
+            if certificates.is_empty() {
+                return Err(Status::invalid_argument(
+                    "CA bundle contains no certificates",
+                ));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some((certificate, key)) = self.client_certificate.as_ref() {
+            let mut pem = certificate.clone();
+            if !pem.ends_with(b"\n") {
+                pem.push(b'\n');
+            }
+            pem.extend_from_slice(key);
+            let identity = Identity::from_pem(&pem).map_err(|e| {
+                Status::invalid_argument(format!("Invalid client certificate: {}", e))
+            })?;
+            builder = builder.identity(identity);
+        }
+
+        if let Some(proxy) = self.proxy.as_ref() {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| Status::invalid_argument(format!("Invalid proxy: {}", e)))?
+                .no_proxy(self.no_proxy.as_deref().and_then(NoProxy::from_string));
+            builder = builder.proxy(proxy);
+        }
+
+        builder
+            .build()
+            .map_err(|e| Status::internal(format!("Failed to build HTTP client: {}", e)))
+    }
+
+    #[cfg(not(feature = "http-client"))]
+    pub fn build_client(&self) -> Result<BackendClient, Status> {
+        Err(Status::unimplemented(
+            "HTTP settings require a build with an HTTP based backend",
+        ))
+    }
+
+    /// Writes the PEM material to files and returns the equivalent `http.*`
+    /// options of git, which does not use the HTTP client of OpenDAL
+    #[cfg(feature = "services-git")]
+    pub fn git_config(&self, node: &DataNode) -> Result<Vec<(String, String)>, Status> {
+        let dir = PathBuf::from(TLS_DIR).join(node.state_dir());
+        let mut config = Vec::new();
+
+        if let Some(ca_bundle) = self.ca_bundle.as_ref() {
+            let path = write_pem(&dir, CA_BUNDLE_KEY, ca_bundle)?;
+            config.push(("http.sslCAInfo".to_string(), path));
+        }
+        if let Some((certificate, key)) = self.client_certificate.as_ref() {
+            let path = write_pem(&dir, CLIENT_CERT_KEY, certificate)?;
+            config.push(("http.sslCert".to_string(), path));
+            let path = write_pem(&dir, CLIENT_KEY_KEY, key)?;
+            config.push(("http.sslKey".to_string(), path));
+        }
+        if let Some(proxy) = self.proxy.as_ref() {
+            config.push(("http.proxy".to_string(), proxy.clone()));
+            if let Some(no_proxy) = self.no_proxy.as_ref() {
+                config.push((crate::util::git::NO_PROXY_KEY.to_string(), no_proxy.clone()));
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Writes a PEM file readable only by the driver and returns its path
+#[cfg(feature = "services-git")]
+fn write_pem(dir: &Path, name: &str, content: &[u8]) -> Result<String, Status> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| Status::internal(format!("Failed to create TLS directory: {}", e)))?;
+    let path = dir.join(name);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| file.write_all(content))
+        .map_err(|e| Status::internal(format!("Failed to write {}: {}", name, e)))?;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Reads a key of the ConfigMap or Secret a selector points to
+async fn load_key(
+    client: &Client,
+    node: &DataNode,
+    selector: &KeySelector,
+) -> Result<Vec<u8>, Status> {
+    let key = selector.key.as_deref().unwrap_or(CA_BUNDLE_KEY);
+    let value = match (&selector.config_map_ref, &selector.secret_ref) {
+        (Some(reference), None) => {
+            let namespace = namespace(node, reference)?;
+            let config_map = Api::<ConfigMap>::namespaced(client.clone(), &namespace)
+                .get(&reference.name)
+                .await
+                .map_err(|_| Status::not_found("ConfigMap not found"))?;
+            config_map
+                .data
+                .and_then(|mut data| data.remove(key))
+                .map(String::into_bytes)
+                .or_else(|| {
+                    config_map
+                        .binary_data
+                        .and_then(|mut data| data.remove(key))
+                        .map(|value| value.0)
+                })
+        }
+        (None, Some(reference)) => secret_api(client, node, reference)?
+            .get(&reference.name)
+            .await
+            .map_err(|_| Status::not_found("Secret not found"))?
+            .data
+            .and_then(|mut data| data.remove(key))
+            .map(|value| value.0),
+        _ => {
+            return Err(Status::invalid_argument(
+                "Exactly one of configMapRef and secretRef must be set",
+            ));
+        }
+    };
+
+    value.ok_or_else(|| Status::invalid_argument(format!("Key `{}` not found", key)))
+}
+
+fn secret_api(client: &Client, node: &DataNode, reference: &Ref) -> Result<Api<Secret>, Status> {
+    Ok(Api::namespaced(
+        client.clone(),
+        &namespace(node, reference)?,
+    ))
+}
+
+/// Namespace of a reference, defaulting to the one of the DataNode
+fn namespace(node: &DataNode, reference: &Ref) -> Result<String, Status> {
+    reference
+        .namespace
+        .clone()
+        .or_else(|| node.namespace())
+        .ok_or_else(|| Status::invalid_argument("Namespace not provided"))
+}
@@ -0,0 +1,584 @@
+use crate::util::paths::is_below_root;
+use crate::util::virtual_tree::{Verifier, VirtualLister, is_dir, is_sha256, list_children};
+use opendal::raw::{
+    Access, AccessorInfo, Layer, LayeredAccess, OpList, OpRead, OpStat, OpWrite, RpDelete, RpList,
+    RpRead, RpStat, RpWrite, oio,
+};
+use opendal::{Buffer, EntryMode, Error, ErrorKind, Metadata};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, LazyLock};
+use tonic::Status;
+use url::Url;
+
+/// Config key naming a manifest (JSON or CSV) relative to the HTTP root, or an absolute URL
+pub const MANIFEST_KEY: &str = "manifest";
+/// Config key enabling the parsing of Apache/nginx autoindex pages
+pub const AUTOINDEX_KEY: &str = "autoindex";
+
+// Guards against link loops and runaway crawls of autoindex pages
+const MAX_AUTOINDEX_DEPTH: usize = 16;
+const MAX_INDEX_ENTRIES: usize = 100_000;
+
+static HREF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)href\s*=\s*"([^"]+)""#).expect("valid regex"));
+
+/// Where the directory tree of an HTTP DataNode comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexSource {
+    /// A JSON or CSV file listing all paths
+    Manifest(String),
+    /// Directory listings generated by the web server
+    Autoindex,
+}
+
+/// A file of the tree, sizes and checksums are only known from manifests
+#[derive(Debug, Clone, Default, PartialEq)]
+struct IndexEntry {
+    size: Option<u64>,
+    sha256: Option<String>,
+}
+
+/// Entry of a JSON manifest, either a plain path or an object with optional size and checksum
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ManifestEntry {
+    Path(String),
+    Object {
+        path: String,
+        size: Option<u64>,
+        sha256: Option<String>,
+    },
+}
+
+impl ManifestEntry {
+    fn into_parts(self) -> (String, IndexEntry) {
+        match self {
+            ManifestEntry::Path(path) => (path, IndexEntry::default()),
+            ManifestEntry::Object { path, size, sha256 } => (path, IndexEntry { size, sha256 }),
+        }
+    }
+}
+
+/// Removes the kubedal specific index options from the config of an HTTP DataNode
+pub fn take_index_source(cfg: &mut HashMap<String, String>) -> Result<Option<IndexSource>, Status> {
+    let manifest = cfg.remove(MANIFEST_KEY);
+    let autoindex = cfg
+        .remove(AUTOINDEX_KEY)
+        .map(|value| {
+            value.parse::<bool>().map_err(|_| {
+                Status::invalid_argument(format!("Invalid {} value: {}", AUTOINDEX_KEY, value))
+            })
+        })
+        .transpose()?
+        .unwrap_or(false);
+
+    match (manifest, autoindex) {
+        (Some(_), true) => Err(Status::invalid_argument(format!(
+            "Only one of {} and {} can be set",
+            MANIFEST_KEY, AUTOINDEX_KEY
+        ))),
+        (Some(manifest), false) => Ok(Some(IndexSource::Manifest(manifest))),
+        (None, true) => Ok(Some(IndexSource::Autoindex)),
+        (None, false) => Ok(None),
+    }
+}
+
+/// Builds a virtual directory tree over a plain HTTP file server, which makes
+/// the operator listable for cached mounts and directory browsing in FUSE
+pub async fn build_index_layer(
+    cfg: &HashMap<String, String>,
+    source: &IndexSource,
+    http_client: Option<reqwest::Client>,
+) -> Result<IndexLayer, Status> {
+    let base = base_url(cfg)?;
+    let client = IndexClient {
+        http: http_client.unwrap_or_default(),
+        cfg,
+    };
+
+    let files = match source {
+        IndexSource::Manifest(manifest) => {
+            let url = base
+                .join(manifest)
+                .map_err(|e| Status::invalid_argument(format!("Invalid manifest URL: {}", e)))?;
+            parse_manifest(&client.get(&url).await?)?
+        }
+        IndexSource::Autoindex => crawl_autoindex(&client, &base)
+            .await?
+            .into_iter()
+            .map(|path| (path, IndexEntry::default()))
+            .collect(),
+    };
+
+    Ok(IndexLayer {
+        files: Arc::new(files),
+    })
+}
+
+/// Endpoint joined with the root, always ending with a slash
+fn base_url(cfg: &HashMap<String, String>) -> Result<Url, Status> {
+    let endpoint = cfg
+        .get("endpoint")
+        .ok_or_else(|| Status::invalid_argument("HTTP DataNode requires an endpoint"))?;
+    let root = cfg.get("root").map(String::as_str).unwrap_or("/");
+
+    let mut base = format!(
+        "{}/{}",
+        endpoint.trim_end_matches('/'),
+        root.trim_matches('/')
+    );
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+    Url::parse(&base).map_err(|e| Status::invalid_argument(format!("Invalid endpoint: {}", e)))
+}
+
+/// Parses a JSON array of paths / objects with a `path` and optionally `size` and `sha256`,
+/// or CSV lines of `path[,size[,sha256]]`
+fn parse_manifest(content: &str) -> Result<BTreeMap<String, IndexEntry>, Status> {
+    let entries = if content.trim_start().starts_with('[') {
+        serde_json::from_str::<Vec<ManifestEntry>>(content)
+            .map_err(|e| Status::invalid_argument(format!("Invalid JSON manifest: {}", e)))?
+            .into_iter()
+            .map(ManifestEntry::into_parts)
+            .collect::<Vec<_>>()
+    } else {
+        let mut entries = Vec::new();
+        for line in content.lines().map(str::trim) {
+            let mut fields = line.split(',').map(|field| field.trim().trim_matches('"'));
+            let path = fields.next().unwrap_or_default();
+            if path.is_empty() || path.starts_with('#') || path == "path" {
+                continue;
+            }
+            let size = match fields.next().filter(|size| !size.is_empty()) {
+                Some(size) => Some(size.parse::<u64>().map_err(|_| {
+                    Status::invalid_argument(format!("Invalid size in manifest line: {}", line))
+                })?),
+                None => None,
+            };
+            let sha256 = fields
+                .next()
+                .filter(|sum| !sum.is_empty())
+                .map(str::to_string);
+            entries.push((path.to_string(), IndexEntry { size, sha256 }));
+        }
+        entries
+    };
+
+    if entries.len() > MAX_INDEX_ENTRIES {
+        return Err(Status::resource_exhausted(format!(
+            "Manifest exceeds {} entries",
+            MAX_INDEX_ENTRIES
+        )));
+    }
+
+    let mut files = BTreeMap::new();
+    for (path, mut entry) in entries {
+        let path = path.trim_start_matches('/');
+        if !is_below_root(path) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid path in manifest: {}",
+                path
+            )));
+        }
+        if let Some(sha256) = entry.sha256.as_mut() {
+            if !is_sha256(sha256) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid sha256 checksum for {}",
+                    path
+                )));
+            }
+            sha256.make_ascii_lowercase();
+        }
+        // Directories are derived from the files below them
+        if !path.ends_with('/') {
+            files.insert(path.to_string(), entry);
+        }
+    }
+    Ok(files)
+}
+
+/// Follows the links of autoindex pages below the base URL, returns the files found
+async fn crawl_autoindex(client: &IndexClient<'_>, base: &Url) -> Result<Vec<String>, Status> {
+    let mut paths = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![(base.clone(), 0)];
+
+    while let Some((dir, depth)) = pending.pop() {
+        if !visited.insert(dir.to_string()) {
+            continue;
+        }
+        let page = client.get(&dir).await?;
+
+        for href in HREF
+            .captures_iter(&page)
+            .map(|captures| captures[1].to_string())
+        {
+            // Sorting links, parent directories and external links are no entries
+            if href.starts_with('?') || href.starts_with('#') || href.starts_with("..") {
+                continue;
+            }
+            let Ok(url) = dir.join(&href) else {
+                continue;
+            };
+            let Some(relative) = url.path().strip_prefix(base.path()) else {
+                continue;
+            };
+            if url.origin() != base.origin()
+                || url.query().is_some()
+                || url.path().len() <= dir.path().len()
+            {
+                continue;
+            }
+
+            let path = percent_decode_str(relative).decode_utf8_lossy().to_string();
+            if !is_below_root(&path) {
+                continue;
+            }
+            if path.ends_with('/') {
+                if depth < MAX_AUTOINDEX_DEPTH {
+                    pending.push((url.clone(), depth + 1));
+                }
+                continue;
+            }
+            paths.push(path);
+
+            if paths.len() > MAX_INDEX_ENTRIES {
+                return Err(Status::resource_exhausted(format!(
+                    "Autoindex exceeds {} entries",
+                    MAX_INDEX_ENTRIES
+                )));
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Fetches index documents with the credentials of the DataNode
+struct IndexClient<'a> {
+    http: reqwest::Client,
+    cfg: &'a HashMap<String, String>,
+}
+
+impl IndexClient<'_> {
+    async fn get(&self, url: &Url) -> Result<String, Status> {
+        let mut request = self.http.get(url.clone());
+        if let Some(token) = self.cfg.get("token") {
+            request = request.bearer_auth(token);
+        } else if let Some(username) = self.cfg.get("username") {
+            request = request.basic_auth(username, self.cfg.get("password"));
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Status::unavailable(format!("Failed to fetch index {}: {}", url, e)))?;
+        response
+            .text()
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to read index {}: {}", url, e)))
+    }
+}
+
+/// Lists an HTTP DataNode from its index. Files of manifests with sizes are stat'ed
+/// from the manifest, complete downloads are checked against the size and checksum.
+#[derive(Debug, Clone)]
+pub struct IndexLayer {
+    files: Arc<BTreeMap<String, IndexEntry>>,
+}
+
+impl<A: Access> Layer<A> for IndexLayer {
+    type LayeredAccess = IndexAccessor<A>;
+
+    fn layer(&self, inner: A) -> Self::LayeredAccess {
+        IndexAccessor {
+            inner,
+            files: self.files.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct IndexAccessor<A: Access> {
+    inner: A,
+    files: Arc<BTreeMap<String, IndexEntry>>,
+}
+
+fn file_metadata(entry: &IndexEntry) -> Metadata {
+    let mut metadata = Metadata::new(EntryMode::FILE);
+    if let Some(size) = entry.size {
+        metadata.set_content_length(size);
+    }
+    metadata
+}
+
+impl<A: Access> LayeredAccess for IndexAccessor<A> {
+    type Inner = A;
+    type Reader = IndexReader<A::Reader>;
+    type Writer = A::Writer;
+    type Lister = VirtualLister;
+    type Deleter = A::Deleter;
+    type BlockingReader = A::BlockingReader;
+    type BlockingWriter = A::BlockingWriter;
+    type BlockingLister = ();
+    type BlockingDeleter = A::BlockingDeleter;
+
+    fn inner(&self) -> &Self::Inner {
+        &self.inner
+    }
+
+    fn info(&self) -> Arc<AccessorInfo> {
+        let mut info = (*self.inner.info()).clone();
+        let capability = info.full_capability_mut();
+        capability.list = true;
+        capability.list_with_recursive = true;
+        info.into()
+    }
+
+    async fn stat(&self, path: &str, args: OpStat) -> opendal::Result<RpStat> {
+        if path.ends_with('/') || path.is_empty() {
+            if !is_dir(&self.files, path) {
+                return Err(Error::new(ErrorKind::NotFound, "directory not found")
+                    .with_context("path", path));
+            }
+            return Ok(RpStat::new(Metadata::new(EntryMode::DIR)));
+        }
+
+        match self.files.get(path) {
+            Some(entry) if entry.size.is_some() => Ok(RpStat::new(file_metadata(entry))),
+            _ => self.inner.stat(path, args).await,
+        }
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        // Only complete downloads, e.g. when populating a cache, can be verified
+        let verifier = match self.files.get(path) {
+            Some(entry) if args.range().is_full() => {
+                Verifier::new(path, entry.size, entry.sha256.as_deref())
+            }
+            _ => None,
+        };
+        let (rp, inner) = self.inner.read(path, args).await?;
+        Ok((rp, IndexReader { inner, verifier }))
+    }
+
+    async fn write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::Writer)> {
+        self.inner.write(path, args).await
+    }
+
+    async fn delete(&self) -> opendal::Result<(RpDelete, Self::Deleter)> {
+        self.inner.delete().await
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        let dir = if path == "/" { "" } else { path };
+        Ok((
+            RpList::default(),
+            VirtualLister::new(list_children(
+                &self.files,
+                dir,
+                args.recursive(),
+                file_metadata,
+            )),
+        ))
+    }
+
+    fn blocking_read(
+        &self,
+        path: &str,
+        args: OpRead,
+    ) -> opendal::Result<(RpRead, Self::BlockingReader)> {
+        self.inner.blocking_read(path, args)
+    }
+
+    fn blocking_write(
+        &self,
+        path: &str,
+        args: OpWrite,
+    ) -> opendal::Result<(RpWrite, Self::BlockingWriter)> {
+        self.inner.blocking_write(path, args)
+    }
+
+    fn blocking_delete(&self) -> opendal::Result<(RpDelete, Self::BlockingDeleter)> {
+        self.inner.blocking_delete()
+    }
+
+    fn blocking_list(
+        &self,
+        path: &str,
+        _args: OpList,
+    ) -> opendal::Result<(RpList, Self::BlockingLister)> {
+        Err(
+            Error::new(ErrorKind::Unsupported, "blocking listing of HTTP indexes")
+                .with_context("path", path),
+        )
+    }
+}
+
+/// Passes reads through and verifies complete downloads at their end
+pub struct IndexReader<R> {
+    inner: R,
+    verifier: Option<Verifier>,
+}
+
+impl<R: oio::Read> oio::Read for IndexReader<R> {
+    async fn read(&mut self) -> opendal::Result<Buffer> {
+        let buffer = self.inner.read().await?;
+        if buffer.is_empty() {
+            if let Some(verifier) = self.verifier.take() {
+                verifier.finish()?;
+            }
+        } else if let Some(verifier) = self.verifier.as_mut() {
+            for chunk in buffer.clone() {
+                verifier.update(&chunk);
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn parses_json_manifests() {
+        let files = parse_manifest(&format!(
+            r#"["/a.txt", {{"path": "dir/b.txt", "size": 5, "sha256": "{}"}}, "dir/"]"#,
+            HELLO_SHA256.to_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(
+            files,
+            BTreeMap::from([
+                ("a.txt".to_string(), IndexEntry::default()),
+                (
+                    "dir/b.txt".to_string(),
+                    IndexEntry {
+                        size: Some(5),
+                        sha256: Some(HELLO_SHA256.to_string()),
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_csv_manifests() {
+        let files = parse_manifest(&format!(
+            "path,size,sha256\n# comment\n\"a.txt\"\ndir/b.txt,5,{}\nc.txt,,\n",
+            HELLO_SHA256
+        ))
+        .unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files["a.txt"], IndexEntry::default());
+        assert_eq!(files["dir/b.txt"].size, Some(5));
+        assert_eq!(files["dir/b.txt"].sha256.as_deref(), Some(HELLO_SHA256));
+        assert_eq!(files["c.txt"], IndexEntry::default());
+    }
+
+    #[test]
+    fn rejects_invalid_manifests() {
+        for manifest in [
+            "../secret",
+            "dir/../../secret",
+            "dir//file",
+            "a.txt,five",
+            "a.txt,5,abc",
+            r#"["../secret"]"#,
+            r#"[{"path": "a.txt", "size": -1}]"#,
+        ] {
+            assert!(parse_manifest(manifest).is_err(), "{}", manifest);
+        }
+    }
+
+    #[cfg(feature = "services-fs")]
+    mod layer {
+        use super::*;
+        use opendal::Operator;
+        use opendal::services::Fs;
+        use std::path::PathBuf;
+
+        struct TempDir(PathBuf);
+
+        impl Drop for TempDir {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+        }
+
+        /// Serves `hello` as `dir/hello.txt` with the given index entry
+        fn index_operator(entry: IndexEntry) -> (TempDir, Operator) {
+            let dir = TempDir(
+                std::env::temp_dir().join(format!("kubedal-index-{}", uuid::Uuid::new_v4())),
+            );
+            std::fs::create_dir_all(dir.0.join("dir")).unwrap();
+            std::fs::write(dir.0.join("dir/hello.txt"), "hello").unwrap();
+
+            let layer = IndexLayer {
+                files: Arc::new(BTreeMap::from([("dir/hello.txt".to_string(), entry)])),
+            };
+            let operator = Operator::new(Fs::default().root(&dir.0.to_string_lossy()))
+                .unwrap()
+                .layer(layer)
+                .finish();
+            (dir, operator)
+        }
+
+        #[tokio::test]
+        async fn lists_and_stats_from_the_manifest() {
+            let (_dir, operator) = index_operator(IndexEntry {
+                size: Some(42),
+                sha256: None,
+            });
+
+            let entries = operator.list_with("").recursive(true).await.unwrap();
+            let paths: Vec<_> = entries.iter().map(|entry| entry.path()).collect();
+            assert_eq!(paths, ["dir/", "dir/hello.txt"]);
+
+            let metadata = operator.stat("dir/hello.txt").await.unwrap();
+            assert_eq!(metadata.content_length(), 42);
+            assert!(operator.stat("dir/").await.unwrap().is_dir());
+            assert!(operator.stat("other/").await.is_err());
+        }
+
+        #[tokio::test]
+        async fn verifies_complete_downloads() {
+            let (_dir, operator) = index_operator(IndexEntry {
+                size: Some(5),
+                sha256: Some(HELLO_SHA256.to_string()),
+            });
+            let content = operator.read("dir/hello.txt").await.unwrap();
+            assert_eq!(content.to_vec(), b"hello");
+
+            let (_dir, operator) = index_operator(IndexEntry {
+                size: None,
+                sha256: Some("0".repeat(64)),
+            });
+            assert!(operator.read("dir/hello.txt").await.is_err());
+            // Ranges can not be verified and are passed through
+            let content = operator
+                .read_with("dir/hello.txt")
+                .range(1..3)
+                .await
+                .unwrap();
+            assert_eq!(content.to_vec(), b"el");
+        }
+
+        #[tokio::test]
+        async fn verifies_sizes() {
+            let (_dir, operator) = index_operator(IndexEntry {
+                size: Some(4),
+                sha256: None,
+            });
+            assert!(operator.read("dir/hello.txt").await.is_err());
+        }
+    }
+}
@@ -1,2 +1,35 @@
+pub mod capabilities;
+pub mod cluster_data_node;
+pub mod data_node_class;
+#[cfg(feature = "services-fs")]
+pub mod fs_root;
+#[cfg(feature = "services-git")]
+pub mod git;
+pub mod grant;
+pub mod health;
+pub mod http_client;
+#[cfg(feature = "services-http")]
+pub mod http_index;
 pub mod mount_helper;
+#[cfg(feature = "services-oci")]
+pub mod oci;
 pub mod opendal;
+pub mod operation_lock;
+pub mod operator_swap;
+pub mod paths;
+pub mod quantity;
+pub mod sftp;
+pub mod throttle;
+pub mod topology;
+#[cfg(feature = "services-urllist")]
+pub mod url_list;
+#[cfg(any(
+    feature = "services-http",
+    feature = "services-oci",
+    feature = "services-urllist"
+))]
+pub mod virtual_tree;
+pub mod volume_attributes;
+pub mod volume_context;
+#[cfg(feature = "services-s3")]
+pub mod web_identity;
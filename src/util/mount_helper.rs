@@ -1,13 +1,43 @@
-use std::{fs, io::Write, os::unix::fs::DirBuilderExt, path::Path};
+use std::{
+    fs,
+    io::Write,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
 
+use crate::util::opendal::SharedCredentials;
+use crate::util::operator_swap::OperatorSwap;
+use crate::util::paths::is_below_root;
+use crate::util::throttle::Throttle;
 use fuse3::{MountOptions, path::Session, raw::MountHandle};
 use fuse3_opendal::Filesystem;
 use futures::TryStreamExt;
+use opendal::layers::ThrottleLayer;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sys_mount::{Mount as SysMount, MountFlags, UnmountFlags, unmount};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
 use tonic::Status;
 
+/// Smallest burst of the FUSE throttle, single reads and writes must not exceed it
+const MIN_THROTTLE_BURST: u32 = 8 * 1024 * 1024;
+
+/// Directory of the driver inside cached volumes, hidden from the quota and the usage.
+/// Entries of data sources below it are not copied, so they can not fake the marker.
+const MARKER_DIR: &str = ".kubedal";
+/// Marker file in `MARKER_DIR`, it exists once the cache is fully populated and holds the
+/// number of cached bytes. Workloads and init containers can wait for `.kubedal/ready`.
+const READY_MARKER: &str = "ready";
+
+/// Minimum time between two progress reports of a cache population
+const CACHE_PROGRESS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Maximum age of the measured usage of a FUSE volume, measuring lists the whole data source
+const FUSE_USAGE_TTL: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
 pub enum MountMode {
     Cached,
@@ -27,6 +57,57 @@ pub struct Mount {
     pub operator: opendal::Operator,
     pub mount_mode: MountMode,
     pub access_mode: AccessMode,
+    /// Logical quota of the volume in bytes, `None` means unlimited. Cached volumes
+    /// stop populating beyond it, for FUSE mounts it is advisory and writes are not refused.
+    pub quota: Option<u64>,
+    /// Throughput limit, adjustable while mounted
+    pub throttle: Throttle,
+    /// Reuse files already present in the local cache
+    pub cache_reuse: bool,
+    /// Background task applying attribute changes to the live mount
+    pub attribute_watch: Option<JoinHandle<()>>,
+    /// Background task rebuilding the operator when credentials are rotated
+    pub credential_watch: Option<JoinHandle<()>>,
+    /// Replaces the backend of the live mount, e.g. with refreshed credentials
+    pub operator_swap: Option<OperatorSwap>,
+    /// Publish credentials the current operator was built with
+    pub credentials: SharedCredentials,
+    /// Bind mount right away and populate the cache in the background
+    pub cache_async: bool,
+    /// Receives the progress of the cache population
+    pub cache_events: Option<UnboundedSender<CacheEvent>>,
+    /// Background task populating the cache
+    pub cache_population: Option<JoinHandle<Result<(), Status>>>,
+    /// Last measured usage of a FUSE volume and when it was measured
+    fuse_usage: Arc<Mutex<Option<(Instant, u64)>>>,
+}
+
+/// Measures the usage of a mounted volume without borrowing the mount, measuring
+/// a FUSE volume lists its whole data source
+#[derive(Clone)]
+pub struct UsageMeter {
+    mount_mode: MountMode,
+    cache_path: PathBuf,
+    operator: opendal::Operator,
+    fuse_usage: Arc<Mutex<Option<(Instant, u64)>>>,
+}
+
+/// Progress of a cache population
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheEvent {
+    Started {
+        entries: usize,
+    },
+    Progress {
+        entries: usize,
+        total: usize,
+        bytes: u64,
+    },
+    Ready {
+        entries: usize,
+        bytes: u64,
+    },
+    Failed(String),
 }
 
 impl Mount {
@@ -36,6 +117,7 @@ impl Mount {
         operator: opendal::Operator,
         mount_mode: MountMode,
         access_mode: AccessMode,
+        quota: Option<u64>,
     ) -> Self {
         Mount {
             volume_id,
@@ -44,6 +126,98 @@ impl Mount {
             operator,
             mount_mode,
             access_mode,
+            quota,
+            throttle: Throttle::default(),
+            cache_reuse: false,
+            attribute_watch: None,
+            credential_watch: None,
+            operator_swap: None,
+            credentials: SharedCredentials::default(),
+            cache_async: false,
+            cache_events: None,
+            cache_population: None,
+            fuse_usage: Arc::default(),
+        }
+    }
+
+    pub fn with_cache_async(mut self, cache_async: bool) -> Self {
+        self.cache_async = cache_async;
+        self
+    }
+
+    pub fn with_cache_events(mut self, cache_events: UnboundedSender<CacheEvent>) -> Self {
+        self.cache_events = Some(cache_events);
+        self
+    }
+
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    pub fn with_cache_reuse(mut self, cache_reuse: bool) -> Self {
+        self.cache_reuse = cache_reuse;
+        self
+    }
+
+    pub fn with_attribute_watch(mut self, attribute_watch: JoinHandle<()>) -> Self {
+        self.attribute_watch = Some(attribute_watch);
+        self
+    }
+
+    pub fn with_credential_watch(mut self, credential_watch: JoinHandle<()>) -> Self {
+        self.credential_watch = Some(credential_watch);
+        self
+    }
+
+    pub fn with_operator_swap(mut self, operator_swap: OperatorSwap) -> Self {
+        self.operator_swap = Some(operator_swap);
+        self
+    }
+
+    pub fn with_credentials(mut self, credentials: SharedCredentials) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Updates the quota of an already mounted volume, no remount required.
+    /// Only future cache populations enforce it, see `quota`.
+    pub fn set_quota(&mut self, quota: Option<u64>) {
+        self.quota = quota;
+    }
+
+    /// Meter of the usage of the volume
+    pub fn usage_meter(&self) -> UsageMeter {
+        UsageMeter {
+            mount_mode: self.mount_mode.clone(),
+            cache_path: self.cache_path(),
+            operator: self.operator.clone(),
+            fuse_usage: self.fuse_usage.clone(),
+        }
+    }
+
+    fn cache_path(&self) -> PathBuf {
+        Path::new("/mnt").join(&self.volume_id)
+    }
+
+    fn ready_marker(&self) -> PathBuf {
+        self.cache_path().join(MARKER_DIR).join(READY_MARKER)
+    }
+
+    /// Health of a cached volume: abnormal while the cache is populated in the
+    /// background or after its population failed, `None` for FUSE mounts
+    pub fn cache_condition(&self) -> Option<(bool, String)> {
+        if self.mount_mode != MountMode::Cached {
+            return None;
+        }
+        if self.ready_marker().exists() {
+            return Some((false, "Cache is populated".to_string()));
+        }
+        match self.cache_population.as_ref() {
+            Some(population) if !population.is_finished() => {
+                Some((true, "Cache is being populated".to_string()))
+            }
+            _ => Some((true, "Cache population failed".to_string())),
         }
     }
 
@@ -74,6 +248,16 @@ impl Mount {
     }
 
     pub async fn unmount(&mut self) -> Result<(), Status> {
+        if let Some(attribute_watch) = self.attribute_watch.take() {
+            attribute_watch.abort();
+        }
+        if let Some(credential_watch) = self.credential_watch.take() {
+            credential_watch.abort();
+        }
+        if let Some(cache_population) = self.cache_population.take() {
+            cache_population.abort();
+        }
+
         match self.mount_mode {
             MountMode::Cached => self.unmount_cached().await,
             MountMode::Fuse => self.unmount_fuse().await,
@@ -81,7 +265,18 @@ impl Mount {
     }
 
     async fn mount_fuse(&mut self) -> Result<(), Status> {
-        let fs = Filesystem::new(self.operator.clone(), 1000, 1000);
+        // The FUSE filesystem owns its operator, so the throttle is fixed until the next publish
+        let operator = match self.throttle.limit() {
+            Some(limit) => {
+                let bandwidth = u32::try_from(limit).unwrap_or(u32::MAX);
+                self.operator.clone().layer(ThrottleLayer::new(
+                    bandwidth,
+                    bandwidth.max(MIN_THROTTLE_BURST),
+                ))
+            }
+            None => self.operator.clone(),
+        };
+        let fs = Filesystem::new(operator, 1000, 1000);
 
         let mut mount_options = MountOptions::default();
 
@@ -107,7 +302,7 @@ impl Mount {
 
     async fn mount_cached(&mut self) -> Result<(), Status> {
         // Create local mount dir if not exists
-        let cache_path = Path::new("/mnt").join(&self.volume_id);
+        let cache_path = self.cache_path();
         if !cache_path.exists() {
             let mut builder = fs::DirBuilder::new();
             builder
@@ -119,18 +314,178 @@ impl Mount {
                 })?;
         }
 
+        let fill = CacheFill {
+            operator: self.operator.clone(),
+            cache_path: cache_path.clone(),
+            ready_marker: self.ready_marker(),
+            started: self.cache_async.then(SystemTime::now),
+            quota: self.quota,
+            throttle: self.throttle.clone(),
+            cache_reuse: self.cache_reuse,
+            events: self.cache_events.take(),
+        };
+        fill.clear_ready_marker()?;
+
+        if self.cache_async {
+            // Bind mount right away, readiness is reported through events and the volume condition
+            self.bind_cache(cache_path).await?;
+            self.cache_population = Some(tokio::spawn(fill.run()));
+        } else {
+            fill.run().await?;
+            self.bind_cache(cache_path).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn bind_cache(&self, cache_path: PathBuf) -> Result<(), Status> {
+        let mut mount_builder = SysMount::builder();
+        let mountflags = if self.access_mode == AccessMode::ReadOnly {
+            MountFlags::RDONLY | MountFlags::BIND
+        } else {
+            MountFlags::BIND
+        };
+
+        mount_builder = mount_builder.flags(mountflags);
+        // Mount cache directory to target directory
+        let target_path = self.target_path.clone();
+        tokio::task::spawn_blocking(move || {
+            mount_builder
+                .mount(cache_path, target_path)
+                .map_err(|e| Status::internal(format!("Failed to mount cache: {}", e)))
+        })
+        .await
+        .map_err(|_| Status::internal("Unable to tokio::join mount operation"))??;
+
+        Ok(())
+    }
+
+    async fn unmount_cached(&self) -> Result<(), Status> {
+        let target_path = self.target_path.clone();
+        tokio::task::spawn_blocking(move || {
+            unmount(target_path, UnmountFlags::empty())
+                .map_err(|e| Status::internal(format!("Failed to unmount cache: {}", e)))
+        })
+        .await
+        .map_err(|_| Status::internal("Unable to tokio::join unmount operation"))?
+    }
+}
+
+impl UsageMeter {
+    /// Returns the number of bytes currently used by the volume, the usage
+    /// of FUSE volumes is measured at most every `FUSE_USAGE_TTL`
+    pub async fn usage(&self) -> Result<u64, Status> {
+        match self.mount_mode {
+            MountMode::Cached => {
+                let cache_path = self.cache_path.clone();
+                tokio::task::spawn_blocking(move || cache_size(&cache_path))
+                    .await
+                    .map_err(|_| Status::internal("Unable to tokio::join usage calculation"))?
+                    .map_err(|e| Status::internal(format!("Failed to read cache usage: {}", e)))
+            }
+            MountMode::Fuse => {
+                let cached = *self.fuse_usage.lock().unwrap();
+                if let Some((_, used)) =
+                    cached.filter(|(measured_at, _)| measured_at.elapsed() < FUSE_USAGE_TTL)
+                {
+                    return Ok(used);
+                }
+
+                let entries = self
+                    .operator
+                    .list_with("")
+                    .recursive(true)
+                    .await
+                    .map_err(|e| Status::internal(format!("Data source listing failed: {}", e)))?;
+                let used = entries
+                    .iter()
+                    .filter(|entry| entry.metadata().is_file())
+                    .map(|entry| entry.metadata().content_length())
+                    .sum();
+                *self.fuse_usage.lock().unwrap() = Some((Instant::now(), used));
+                Ok(used)
+            }
+        }
+    }
+}
+
+/// Copies a data source into the local cache and signals completion with a ready marker
+struct CacheFill {
+    operator: opendal::Operator,
+    cache_path: PathBuf,
+    ready_marker: PathBuf,
+    /// Start of a background population, files modified since were written by
+    /// the workload and are not overwritten
+    started: Option<SystemTime>,
+    quota: Option<u64>,
+    throttle: Throttle,
+    cache_reuse: bool,
+    events: Option<UnboundedSender<CacheEvent>>,
+}
+
+impl CacheFill {
+    async fn run(self) -> Result<(), Status> {
+        let result = self.populate().await;
+        if let Err(e) = &result {
+            self.emit(CacheEvent::Failed(e.message().to_string()));
+        }
+        result
+    }
+
+    async fn populate(&self) -> Result<(), Status> {
         let data_source_children = self
             .operator
-            .list("")
+            .list_with("")
+            .recursive(true)
             .await
             .map_err(|e| Status::internal(format!("Data source listing failed: {}", e)))?;
 
+        let total = data_source_children.len();
+        self.emit(CacheEvent::Started { entries: total });
+
         // Cache data source in target directory
         //TODO: More sophisticated directory structure to cache for data-source/version
-        for entry in data_source_children {
-            let entry_path = Path::new(&cache_path).join(entry.path());
+        let mut cached_bytes: u64 = 0;
+        let mut last_progress = Instant::now();
+        for (index, entry) in data_source_children.into_iter().enumerate() {
+            if last_progress.elapsed() >= CACHE_PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                self.emit(CacheEvent::Progress {
+                    entries: index,
+                    total,
+                    bytes: cached_bytes,
+                });
+            }
+
+            // Listings may contain the root itself, all other entries have to stay below it
+            if matches!(entry.path(), "" | "/") || is_marker_path(entry.path()) {
+                continue;
+            }
+            if !is_below_root(entry.path()) {
+                return Err(Status::invalid_argument(format!(
+                    "Data source listed a path outside of the volume: {}",
+                    entry.path()
+                )));
+            }
+            let entry_path = self.cache_path.join(entry.path());
             match entry.metadata().mode() {
                 opendal::EntryMode::FILE => {
+                    if self.written_by_workload(&entry_path) {
+                        continue;
+                    }
+                    if self.cache_reuse
+                        && fs::metadata(&entry_path)
+                            .is_ok_and(|local| local.len() == entry.metadata().content_length())
+                    {
+                        cached_bytes += entry.metadata().content_length();
+                        continue;
+                    }
+                    // Recursive listings do not necessarily contain all parent directories
+                    if let Some(parent) = entry_path.parent() {
+                        fs::create_dir_all(parent).map_err(|e| {
+                            Status::internal(format!("Failed to create target directory: {}", e))
+                        })?;
+                    }
                     // Create file
                     let mut file = std::fs::File::create(entry_path)?;
                     // Create stream
@@ -148,7 +503,15 @@ impl Mount {
                         })?;
                     // Write stream into file
                     while let Some(bytes) = r.try_next().await? {
+                        cached_bytes += bytes.len() as u64;
+                        if self.quota.is_some_and(|quota| cached_bytes > quota) {
+                            return Err(Status::resource_exhausted(format!(
+                                "Data source exceeds volume quota of {} bytes",
+                                self.quota.unwrap_or_default()
+                            )));
+                        }
                         file.write_all(&bytes)?;
+                        self.throttle.consume(bytes.len() as u64).await;
                     }
                 }
                 opendal::EntryMode::DIR => {
@@ -172,34 +535,107 @@ impl Mount {
             }
         }
 
-        let mut mount_builder = SysMount::builder();
-        let mountflags = if self.access_mode == AccessMode::ReadOnly {
-            MountFlags::RDONLY | MountFlags::BIND
-        } else {
-            MountFlags::BIND
-        };
+        self.write_ready_marker(cached_bytes)?;
+        self.emit(CacheEvent::Ready {
+            entries: total,
+            bytes: cached_bytes,
+        });
 
-        mount_builder = mount_builder.flags(mountflags);
-        // Mount cache directory to target directory
-        let target_path = self.target_path.clone();
-        tokio::task::spawn_blocking(move || {
-            mount_builder
-                .mount(cache_path, target_path)
-                .map_err(|e| Status::internal(format!("Failed to mount cache: {}", e)))
+        Ok(())
+    }
+
+    /// Whether a file was changed through the volume since the background population started
+    fn written_by_workload(&self, path: &Path) -> bool {
+        self.started.is_some_and(|started| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified >= started)
         })
-        .await
-        .map_err(|_| Status::internal("Unable to tokio::join mount operation"))??;
+    }
 
+    fn clear_ready_marker(&self) -> Result<(), Status> {
+        if self.ready_marker.exists() {
+            fs::remove_file(&self.ready_marker)
+                .map_err(|e| Status::internal(format!("Failed to remove ready marker: {}", e)))?;
+        }
         Ok(())
     }
 
-    async fn unmount_cached(&self) -> Result<(), Status> {
-        let target_path = self.target_path.clone();
-        tokio::task::spawn_blocking(move || {
-            unmount(target_path, UnmountFlags::empty())
-                .map_err(|e| Status::internal(format!("Failed to unmount cache: {}", e)))
-        })
-        .await
-        .map_err(|_| Status::internal("Unable to tokio::join unmount operation"))?
+    fn write_ready_marker(&self, cached_bytes: u64) -> Result<(), Status> {
+        if let Some(marker_dir) = self.ready_marker.parent() {
+            fs::create_dir_all(marker_dir).map_err(|e| {
+                Status::internal(format!("Failed to create marker directory: {}", e))
+            })?;
+        }
+        fs::write(&self.ready_marker, format!("{}\n", cached_bytes))
+            .map_err(|e| Status::internal(format!("Failed to write ready marker: {}", e)))
+    }
+
+    fn emit(&self, event: CacheEvent) {
+        if let Some(events) = &self.events {
+            // The receiver is gone if nobody is interested in progress anymore
+            let _ = events.send(event);
+        }
+    }
+}
+
+/// Whether a path of a data source lies in the marker directory of the driver
+fn is_marker_path(path: &str) -> bool {
+    let path = path.trim_start_matches('/');
+    path.strip_prefix(MARKER_DIR)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Size of a cache directory without the marker directory
+fn cache_size(cache_path: &Path) -> std::io::Result<u64> {
+    let marker_dir = cache_path.join(MARKER_DIR);
+    let hidden = if marker_dir.is_dir() {
+        dir_size(&marker_dir)?
+    } else {
+        0
+    };
+    Ok(dir_size(cache_path)?.saturating_sub(hidden))
+}
+
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_marker_paths() {
+        assert!(is_marker_path(".kubedal"));
+        assert!(is_marker_path(".kubedal/"));
+        assert!(is_marker_path("/.kubedal/ready"));
+        assert!(!is_marker_path(".kubedal-data/ready"));
+        assert!(!is_marker_path("data/.kubedal/ready"));
+    }
+
+    #[test]
+    fn hides_the_marker_from_the_usage() {
+        let dir = std::env::temp_dir().join(format!("kubedal-cache-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("data")).unwrap();
+        fs::write(dir.join("data/file"), [0; 100]).unwrap();
+        assert_eq!(cache_size(&dir).unwrap(), 100);
+
+        fs::create_dir_all(dir.join(MARKER_DIR)).unwrap();
+        fs::write(dir.join(MARKER_DIR).join(READY_MARKER), "100\n").unwrap();
+        assert_eq!(cache_size(&dir).unwrap(), 100);
+        assert_eq!(dir_size(&dir).unwrap(), 104);
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,765 @@
+use crate::util::paths::is_below_root;
+use crate::util::virtual_tree::{
+    VirtualLister, check_status, is_dir, list_children, request_error,
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use opendal::raw::{Access, AccessorInfo, OpList, OpRead, OpStat, RpList, RpRead, RpStat, oio};
+use opendal::{
+    Buffer, Capability, EntryMode, Error, ErrorKind, Metadata, Operator, OperatorBuilder, Scheme,
+};
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderValue, RANGE, WWW_AUTHENTICATE};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, OnceCell};
+use tonic::Status;
+
+/// Config key of the artifact, e.g. `ghcr.io/org/weights:v1` or `registry/repo@sha256:...`
+pub const REFERENCE_KEY: &str = "reference";
+/// Config key to talk plain HTTP to the registry, e.g. a local registry stand-in
+pub const INSECURE_KEY: &str = "insecure";
+
+// Layers are cached below the state directory of their DataNode, content-addressed by digest.
+// A shared cache would hand out layers of private repositories to DataNodes without credentials.
+const CACHE_DIR: &str = "/mnt/kubedal-oci";
+// Title of a layer as set by ORAS and other artifact tools
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.manifest.v1+json, \
+     application/vnd.oci.image.index.v1+json, \
+     application/vnd.docker.distribution.manifest.v2+json, \
+     application/vnd.docker.distribution.manifest.list.v2+json";
+const READ_CHUNK_SIZE: u64 = 256 * 1024;
+
+/// Builds a read-only operator over the layers of an OCI artifact
+pub fn init_oci(
+    cfg: HashMap<String, String>,
+    http_client: Option<reqwest::Client>,
+    state_dir: &Path,
+) -> Result<Operator, Status> {
+    let reference = cfg
+        .get(REFERENCE_KEY)
+        .ok_or_else(|| Status::invalid_argument("OCI DataNode requires a reference"))?;
+    let insecure = cfg
+        .get(INSECURE_KEY)
+        .map(|value| {
+            value.parse::<bool>().map_err(|_| {
+                Status::invalid_argument(format!("Invalid {} value: {}", INSECURE_KEY, value))
+            })
+        })
+        .transpose()?
+        .unwrap_or(false);
+
+    let reference = Reference::parse(reference, insecure)?;
+    let credentials = Credentials::from_config(&cfg, &reference.registry)?;
+
+    let op = OperatorBuilder::new(OciAccessor::new(
+        reference,
+        credentials,
+        Path::new(CACHE_DIR).join(state_dir),
+        http_client.unwrap_or_default(),
+    ))
+    .finish();
+
+    Ok(op)
+}
+
+/// Location of an artifact in a registry
+#[derive(Debug, Clone, PartialEq)]
+struct Reference {
+    registry: String,
+    repository: String,
+    /// Tag or digest
+    reference: String,
+    base_url: String,
+}
+
+impl Reference {
+    fn parse(reference: &str, insecure: bool) -> Result<Self, Status> {
+        let (name, tag_or_digest) = match reference.split_once('@') {
+            Some((name, digest)) => {
+                sha256_hex(digest).map_err(|_| {
+                    Status::invalid_argument(format!(
+                        "Invalid digest in OCI reference: {}",
+                        reference
+                    ))
+                })?;
+                (name, digest.to_string())
+            }
+            None => {
+                // A colon after the last slash separates the tag, others belong to the port
+                match reference.rsplit_once(':') {
+                    Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+                    _ => (reference, "latest".to_string()),
+                }
+            }
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, path))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), path.to_string())
+            }
+            _ => ("docker.io".to_string(), name.to_string()),
+        };
+        if repository.is_empty() {
+            return Err(Status::invalid_argument(format!(
+                "Invalid OCI reference: {}",
+                reference
+            )));
+        }
+
+        // Docker Hub is served from a different host and prefixes official images
+        let (host, repository) = match registry.as_str() {
+            "docker.io" if !repository.contains('/') => {
+                ("registry-1.docker.io", format!("library/{}", repository))
+            }
+            "docker.io" => ("registry-1.docker.io", repository),
+            host => (host, repository),
+        };
+        let scheme = if insecure { "http" } else { "https" };
+
+        Ok(Self {
+            base_url: format!("{}://{}/v2/{}", scheme, host, repository),
+            registry,
+            repository,
+            reference: tag_or_digest,
+        })
+    }
+}
+
+/// Pull credentials from the secret of the DataNode
+#[derive(Debug, Clone, PartialEq)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DockerConfig {
+    auths: HashMap<String, DockerAuth>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DockerAuth {
+    username: Option<String>,
+    password: Option<String>,
+    auth: Option<String>,
+}
+
+impl Credentials {
+    /// Reads `username` / `password` or the entry for the registry in a `.dockerconfigjson`
+    fn from_config(cfg: &HashMap<String, String>, registry: &str) -> Result<Option<Self>, Status> {
+        if let (Some(username), Some(password)) = (cfg.get("username"), cfg.get("password")) {
+            return Ok(Some(Self {
+                username: username.clone(),
+                password: password.clone(),
+            }));
+        }
+
+        let Some(config) = cfg.get(".dockerconfigjson") else {
+            return Ok(None);
+        };
+        let config: DockerConfig = serde_json::from_str(config)
+            .map_err(|e| Status::invalid_argument(format!("Invalid .dockerconfigjson: {}", e)))?;
+        let Some(auth) = config.auths.into_iter().find_map(|(server, auth)| {
+            let host = server
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .split('/')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let matches =
+                host == registry || (registry == "docker.io" && host.ends_with("docker.io"));
+            matches.then_some(auth)
+        }) else {
+            return Ok(None);
+        };
+
+        match (auth.username, auth.password, auth.auth) {
+            (Some(username), Some(password), _) => Ok(Some(Self { username, password })),
+            (_, _, Some(encoded)) => {
+                let decoded = STANDARD
+                    .decode(encoded)
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .ok_or_else(|| Status::invalid_argument("Invalid auth in .dockerconfigjson"))?;
+                let (username, password) = decoded
+                    .split_once(':')
+                    .ok_or_else(|| Status::invalid_argument("Invalid auth in .dockerconfigjson"))?;
+                Ok(Some(Self {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    digest: String,
+    size: u64,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+    #[serde(default)]
+    platform: Option<Platform>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Platform {
+    os: String,
+    architecture: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+#[derive(Debug)]
+struct OciAccessor {
+    client: reqwest::Client,
+    reference: Reference,
+    credentials: Option<Credentials>,
+    cache_dir: PathBuf,
+    // Authorization header, obtained on the first challenge of the registry
+    authorization: Mutex<Option<HeaderValue>>,
+    // Layers by path, resolved on first use
+    layers: OnceCell<BTreeMap<String, Descriptor>>,
+    info: Arc<AccessorInfo>,
+}
+
+impl OciAccessor {
+    fn new(
+        reference: Reference,
+        credentials: Option<Credentials>,
+        cache_dir: PathBuf,
+        client: reqwest::Client,
+    ) -> Self {
+        let mut info = AccessorInfo::default();
+        info.set_scheme(Scheme::Custom("oci"))
+            .set_root("/")
+            .set_native_capability(Capability {
+                stat: true,
+                read: true,
+                list: true,
+                list_with_recursive: true,
+                ..Default::default()
+            });
+
+        Self {
+            client,
+            reference,
+            credentials,
+            cache_dir,
+            authorization: Mutex::new(None),
+            layers: OnceCell::new(),
+            info: Arc::new(info),
+        }
+    }
+
+    /// Sends a request, answering an authentication challenge of the registry once
+    async fn send(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        url: &str,
+    ) -> opendal::Result<Response> {
+        let authorization = self.authorization.lock().await.clone();
+        let response = authorize(request(), authorization)
+            .send()
+            .await
+            .map_err(|e| request_error(e, url))?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            check_status(response.status(), url)?;
+            return Ok(response);
+        }
+
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let authorization = self.authenticate(&challenge).await?;
+        *self.authorization.lock().await = Some(authorization.clone());
+
+        let response = authorize(request(), Some(authorization))
+            .send()
+            .await
+            .map_err(|e| request_error(e, url))?;
+        check_status(response.status(), url)?;
+        Ok(response)
+    }
+
+    /// Answers a `Basic` challenge with the credentials or exchanges them for a `Bearer` token
+    async fn authenticate(&self, challenge: &str) -> opendal::Result<HeaderValue> {
+        let Some(params) = challenge.strip_prefix("Bearer ") else {
+            let credentials = self.credentials.as_ref().ok_or_else(|| {
+                Error::new(ErrorKind::PermissionDenied, "registry requires credentials")
+            })?;
+            let encoded =
+                STANDARD.encode(format!("{}:{}", credentials.username, credentials.password));
+            return header_value(&format!("Basic {}", encoded));
+        };
+
+        let params: HashMap<&str, &str> = params
+            .split(',')
+            .filter_map(|param| param.trim().split_once('='))
+            .map(|(key, value)| (key, value.trim_matches('"')))
+            .collect();
+        let realm = params.get("realm").ok_or_else(|| {
+            Error::new(
+                ErrorKind::Unexpected,
+                "authentication challenge without realm",
+            )
+        })?;
+        let scope = format!("repository:{}:pull", self.reference.repository);
+        let mut query = vec![("scope", scope.as_str())];
+        if let Some(service) = params.get("service") {
+            query.push(("service", *service));
+        }
+
+        let mut request = self.client.get(*realm).query(&query);
+        if let Some(credentials) = self.credentials.as_ref() {
+            request = request.basic_auth(&credentials.username, Some(&credentials.password));
+        }
+        let response = request.send().await.map_err(|e| request_error(e, realm))?;
+        check_status(response.status(), realm)?;
+        let token: TokenResponse = response.json().await.map_err(|e| {
+            Error::new(ErrorKind::Unexpected, "invalid token response").set_source(e)
+        })?;
+        let token = token
+            .token
+            .or(token.access_token)
+            .ok_or_else(|| Error::new(ErrorKind::Unexpected, "token response without token"))?;
+        header_value(&format!("Bearer {}", token))
+    }
+
+    async fn manifest(&self, reference: &str) -> opendal::Result<Manifest> {
+        let url = format!("{}/manifests/{}", self.reference.base_url, reference);
+        let response = self
+            .send(
+                || self.client.get(&url).header(ACCEPT, MANIFEST_MEDIA_TYPES),
+                &url,
+            )
+            .await?;
+        response.json().await.map_err(|e| {
+            Error::new(ErrorKind::Unexpected, "invalid manifest")
+                .with_context("url", &url)
+                .set_source(e)
+        })
+    }
+
+    /// Resolves the artifact, an index is resolved to the manifest for this platform
+    async fn layers(&self) -> opendal::Result<&BTreeMap<String, Descriptor>> {
+        self.layers
+            .get_or_try_init(|| async {
+                let mut manifest = self.manifest(&self.reference.reference).await?;
+                if manifest.layers.is_empty() && !manifest.manifests.is_empty() {
+                    let architecture = match std::env::consts::ARCH {
+                        "x86_64" => "amd64",
+                        "aarch64" => "arm64",
+                        arch => arch,
+                    };
+                    let descriptor = manifest
+                        .manifests
+                        .iter()
+                        .find(|descriptor| {
+                            descriptor.platform.as_ref().is_some_and(|platform| {
+                                platform.os == "linux" && platform.architecture == architecture
+                            })
+                        })
+                        .unwrap_or(&manifest.manifests[0]);
+                    sha256_hex(&descriptor.digest)?;
+                    let digest = descriptor.digest.clone();
+                    manifest = self.manifest(&digest).await?;
+                }
+
+                // Digests end up in URLs and cache paths
+                let mut layers = BTreeMap::new();
+                for layer in manifest.layers {
+                    sha256_hex(&layer.digest)?;
+                    layers.insert(layer_path(&layer)?, layer);
+                }
+                Ok::<_, Error>(layers)
+            })
+            .await
+    }
+
+    async fn layer(&self, path: &str) -> opendal::Result<&Descriptor> {
+        self.layers().await?.get(path).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, "path is not part of the artifact")
+                .with_context("path", path)
+        })
+    }
+
+    fn blob_path(&self, digest: &str) -> opendal::Result<PathBuf> {
+        Ok(self
+            .cache_dir
+            .join("blobs")
+            .join("sha256")
+            .join(sha256_hex(digest)?))
+    }
+}
+
+impl Access for OciAccessor {
+    type Reader = OciReader;
+    type Writer = ();
+    type Lister = VirtualLister;
+    type Deleter = ();
+    type BlockingReader = ();
+    type BlockingWriter = ();
+    type BlockingLister = ();
+    type BlockingDeleter = ();
+
+    fn info(&self) -> Arc<AccessorInfo> {
+        self.info.clone()
+    }
+
+    async fn stat(&self, path: &str, _args: OpStat) -> opendal::Result<RpStat> {
+        if path.ends_with('/') || path.is_empty() {
+            if !is_dir(self.layers().await?, path) {
+                return Err(Error::new(ErrorKind::NotFound, "directory not found")
+                    .with_context("path", path));
+            }
+            return Ok(RpStat::new(Metadata::new(EntryMode::DIR)));
+        }
+
+        Ok(RpStat::new(layer_metadata(self.layer(path).await?)))
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        let layer = self.layer(path).await?;
+        let range = args.range();
+
+        // Layers are immutable, cached blobs are served without asking the registry
+        let blob_path = self.blob_path(&layer.digest)?;
+        if let Ok(mut file) = File::open(&blob_path).await {
+            file.seek(SeekFrom::Start(range.offset()))
+                .await
+                .map_err(|e| io_error(e, &blob_path))?;
+            let remaining = range
+                .size()
+                .unwrap_or_else(|| layer.size.saturating_sub(range.offset()));
+            return Ok((RpRead::new(), OciReader::Cached { file, remaining }));
+        }
+
+        let url = format!("{}/blobs/{}", self.reference.base_url, layer.digest);
+        let response = self
+            .send(
+                || {
+                    let request = self.client.get(&url);
+                    if range.is_full() {
+                        request
+                    } else {
+                        request.header(RANGE, range.to_header())
+                    }
+                },
+                &url,
+            )
+            .await?;
+
+        // Complete downloads of sha256 blobs are verified and added to the cache
+        let fill = match range.is_full() {
+            true => Some(Box::new(
+                BlobFill::create(blob_path, sha256_hex(&layer.digest)?).await?,
+            )),
+            false => None,
+        };
+
+        Ok((
+            RpRead::new(),
+            OciReader::Remote {
+                path: path.to_string(),
+                response,
+                fill,
+            },
+        ))
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        let dir = if path == "/" { "" } else { path };
+        let layers = self.layers().await?;
+        Ok((
+            RpList::default(),
+            VirtualLister::new(list_children(layers, dir, args.recursive(), layer_metadata)),
+        ))
+    }
+}
+
+/// Hex part of a `sha256:<64 lowercase hex digits>` digest, the only kind that is accepted
+fn sha256_hex(digest: &str) -> opendal::Result<&str> {
+    match digest.strip_prefix("sha256:") {
+        Some(hex) if hex.len() == 64 && hex.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) => {
+            Ok(hex)
+        }
+        _ => {
+            Err(Error::new(ErrorKind::Unexpected, "invalid digest").with_context("digest", digest))
+        }
+    }
+}
+
+/// Layers are named by their title annotation, untitled ones by their digest
+fn layer_path(layer: &Descriptor) -> opendal::Result<String> {
+    let path = match layer.annotations.get(TITLE_ANNOTATION) {
+        Some(title) => title.trim_start_matches('/').to_string(),
+        None => format!("blobs/sha256/{}", sha256_hex(&layer.digest)?),
+    };
+    if path.ends_with('/') || !is_below_root(&path) {
+        return Err(
+            Error::new(ErrorKind::Unexpected, "invalid layer title").with_context("title", path)
+        );
+    }
+    Ok(path)
+}
+
+fn layer_metadata(layer: &Descriptor) -> Metadata {
+    let mut metadata = Metadata::new(EntryMode::FILE);
+    metadata.set_content_length(layer.size);
+    metadata
+}
+
+fn authorize(request: RequestBuilder, authorization: Option<HeaderValue>) -> RequestBuilder {
+    match authorization {
+        Some(authorization) => request.header(AUTHORIZATION, authorization),
+        None => request,
+    }
+}
+
+fn header_value(value: &str) -> opendal::Result<HeaderValue> {
+    HeaderValue::from_str(value)
+        .map_err(|e| Error::new(ErrorKind::Unexpected, "invalid credentials").set_source(e))
+}
+
+fn io_error(e: std::io::Error, path: &std::path::Path) -> Error {
+    Error::new(ErrorKind::Unexpected, "layer cache failed")
+        .with_context("path", path.display())
+        .set_source(e)
+}
+
+/// Writes a downloaded blob to a temporary file, which is moved into the
+/// cache once its digest has been verified
+struct BlobFill {
+    file: File,
+    hasher: Sha256,
+    expected: String,
+    temp_path: PathBuf,
+    blob_path: PathBuf,
+}
+
+impl BlobFill {
+    async fn create(blob_path: PathBuf, expected: &str) -> opendal::Result<Self> {
+        if let Some(parent) = blob_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error(e, parent))?;
+        }
+        let temp_path = blob_path.with_extension(format!("{}.partial", uuid::Uuid::new_v4()));
+        let file = File::create(&temp_path)
+            .await
+            .map_err(|e| io_error(e, &temp_path))?;
+
+        Ok(Self {
+            file,
+            hasher: Sha256::new(),
+            expected: expected.to_string(),
+            temp_path,
+            blob_path,
+        })
+    }
+
+    async fn write(&mut self, bytes: &[u8]) -> opendal::Result<()> {
+        self.hasher.update(bytes);
+        self.file
+            .write_all(bytes)
+            .await
+            .map_err(|e| io_error(e, &self.temp_path))
+    }
+
+    async fn finish(mut self) -> opendal::Result<()> {
+        self.file
+            .flush()
+            .await
+            .map_err(|e| io_error(e, &self.temp_path))?;
+
+        let actual = format!("{:x}", self.hasher.finalize());
+        if actual != self.expected {
+            let _ = tokio::fs::remove_file(&self.temp_path).await;
+            return Err(Error::new(ErrorKind::Unexpected, "layer digest mismatch")
+                .with_context("expected", self.expected)
+                .with_context("actual", actual));
+        }
+        tokio::fs::rename(&self.temp_path, &self.blob_path)
+            .await
+            .map_err(|e| io_error(e, &self.blob_path))
+    }
+}
+
+enum OciReader {
+    /// Reads a verified blob from the layer cache
+    Cached { file: File, remaining: u64 },
+    /// Streams a blob from the registry
+    Remote {
+        path: String,
+        response: Response,
+        fill: Option<Box<BlobFill>>,
+    },
+}
+
+impl oio::Read for OciReader {
+    async fn read(&mut self) -> opendal::Result<Buffer> {
+        match self {
+            OciReader::Cached { file, remaining } => {
+                let mut buffer = vec![0; READ_CHUNK_SIZE.min(*remaining) as usize];
+                let read = file.read(&mut buffer).await.map_err(|e| {
+                    Error::new(ErrorKind::Unexpected, "failed to read cached layer").set_source(e)
+                })?;
+                buffer.truncate(read);
+                *remaining -= read as u64;
+                Ok(Buffer::from(buffer))
+            }
+            OciReader::Remote {
+                path,
+                response,
+                fill,
+            } => {
+                let chunk = response.chunk().await.map_err(|e| {
+                    Error::new(ErrorKind::Unexpected, "failed to read layer")
+                        .with_context("path", path.as_str())
+                        .set_source(e)
+                })?;
+                match chunk {
+                    Some(bytes) => {
+                        if let Some(fill) = fill.as_mut() {
+                            fill.write(&bytes).await?;
+                        }
+                        Ok(Buffer::from(bytes))
+                    }
+                    None => {
+                        if let Some(fill) = fill.take() {
+                            fill.finish().await?;
+                        }
+                        Ok(Buffer::new())
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn layer(digest: &str, title: Option<&str>) -> Descriptor {
+        Descriptor {
+            digest: digest.to_string(),
+            size: 5,
+            annotations: title
+                .map(|title| HashMap::from([(TITLE_ANNOTATION.to_string(), title.to_string())]))
+                .unwrap_or_default(),
+            platform: None,
+        }
+    }
+
+    #[test]
+    fn accepts_only_sha256_digests() {
+        assert_eq!(sha256_hex(DIGEST).unwrap(), &DIGEST[7..]);
+        for digest in [
+            "",
+            "sha256:",
+            "sha512:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            "sha256:2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824",
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b982",
+            "sha256:../../../../etc/passwd",
+            "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824/..",
+        ] {
+            assert!(sha256_hex(digest).is_err(), "{}", digest);
+        }
+    }
+
+    #[test]
+    fn confines_blobs_to_the_cache() {
+        let accessor = OciAccessor::new(
+            Reference::parse("localhost:5000/weights:v1", true).unwrap(),
+            None,
+            PathBuf::from(CACHE_DIR),
+            reqwest::Client::new(),
+        );
+        assert_eq!(
+            accessor.blob_path(DIGEST).unwrap(),
+            PathBuf::from(CACHE_DIR)
+                .join("blobs/sha256")
+                .join(&DIGEST[7..])
+        );
+        assert!(accessor.blob_path("sha256:../../etc/passwd").is_err());
+        assert!(accessor.blob_path("../../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn parses_references() {
+        let reference = Reference::parse("ghcr.io/org/weights:v1", false).unwrap();
+        assert_eq!(reference.registry, "ghcr.io");
+        assert_eq!(reference.repository, "org/weights");
+        assert_eq!(reference.reference, "v1");
+        assert_eq!(reference.base_url, "https://ghcr.io/v2/org/weights");
+
+        let reference =
+            Reference::parse(&format!("localhost:5000/weights@{}", DIGEST), true).unwrap();
+        assert_eq!(reference.reference, DIGEST);
+        assert_eq!(reference.base_url, "http://localhost:5000/v2/weights");
+
+        let reference = Reference::parse("alpine", false).unwrap();
+        assert_eq!(
+            reference.base_url,
+            "https://registry-1.docker.io/v2/library/alpine"
+        );
+        assert_eq!(reference.reference, "latest");
+
+        assert!(Reference::parse("ghcr.io/org/weights@sha256:../../x", false).is_err());
+        assert!(Reference::parse("ghcr.io/org/weights@md5:abc", false).is_err());
+    }
+
+    #[test]
+    fn names_layers() {
+        assert_eq!(
+            layer_path(&layer(DIGEST, Some("/model/weights.bin"))).unwrap(),
+            "model/weights.bin"
+        );
+        assert_eq!(
+            layer_path(&layer(DIGEST, None)).unwrap(),
+            format!("blobs/sha256/{}", &DIGEST[7..])
+        );
+        for title in ["../escape", "dir/../../escape", "dir/", "", "./file"] {
+            assert!(
+                layer_path(&layer(DIGEST, Some(title))).is_err(),
+                "{}",
+                title
+            );
+        }
+        assert!(layer_path(&layer("sha256:../../x", None)).is_err());
+    }
+}
@@ -1,60 +1,619 @@
-use crate::resource::crd::{Backend, DataNode};
-use k8s_openapi::api::core::v1::Secret;
-use kube::{Api, Client};
-use opendal::layers::{LoggingLayer, RetryLayer};
+use crate::resource::crd::{Backend, ConfigSourceRef, DataNode, DataPod, LayerSpec};
+use crate::util::data_node_class::resolve_data_node;
+use crate::util::http_client::{BackendClient, HttpSettings};
+#[cfg(feature = "services-http")]
+use crate::util::http_index::{build_index_layer, take_index_source};
+use crate::util::paths::{cluster_data_pod_path, data_pod_prefix};
+use crate::util::quantity::quantity_to_bytes;
+use crate::util::sftp::materialize_credentials;
+#[cfg(feature = "services-s3")]
+use crate::util::web_identity::assume_role;
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::{Api, Client, ResourceExt};
+#[cfg(feature = "http-client")]
+use opendal::Configurator;
+use opendal::layers::{
+    ConcurrentLimitLayer, ImmutableIndexLayer, LoggingLayer, RetryLayer, ThrottleLayer,
+    TimeoutLayer,
+};
+#[cfg(feature = "http-client")]
+use opendal::raw::HttpClient;
 use opendal::{Builder, Operator, services};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
 use tonic::Status;
 
+// Immutable indexes by DataNode state directory and operator root, with the generation they were listed at
+type IndexKey = (PathBuf, String);
+static IMMUTABLE_INDEXES: LazyLock<Mutex<HashMap<IndexKey, (i64, ImmutableIndexLayer)>>> =
+    LazyLock::new(Default::default);
+
 pub async fn get_operator(client: &Client, node: &DataNode) -> Result<Operator, Status> {
-    let mut cfg = node.spec.config.clone();
+    let node = &resolve_data_node(client, node.clone()).await?;
+
+    #[cfg(feature = "services-git")]
+    if node.spec.backend.scheme() == "git" {
+        let cfg = data_node_config(client, node).await?;
+        let op = crate::util::git::init_git(cfg, None, None, &node.state_dir()).await?;
+        return apply_layers(op, node).await;
+    }
+
+    let http_client = load_http_client(client, node).await?;
+    let op = build_operator(node, data_node_config(client, node).await?, http_client).await?;
+    apply_layers(op, node).await
+}
+
+/// Credentials that come with the publish request of a pod instead of the DataNode
+#[derive(Debug, Clone, Default)]
+pub struct PublishCredentials {
+    /// Token of the pods service account for the web identity audience of the DataNode
+    pub service_account_token: Option<String>,
+    /// Names the STS session after the pod
+    pub session_name: String,
+    /// Secrets of the publish request, e.g. the node-publish secret of the
+    /// StorageClass, they take precedence over the config of the DataNode
+    pub secrets: HashMap<String, String>,
+}
 
-    let secret = match node.spec.secret_ref.as_ref() {
+/// Credentials of a live mount, replaced when the kubelet republishes with new ones
+pub type SharedCredentials = Arc<Mutex<PublishCredentials>>;
+
+/// Operator for the content of a DataPod. Backends that resolve a version of
+/// their data, like git, use the one recorded in the DataPod status.
+pub async fn get_data_pod_operator(
+    client: &Client,
+    node: &DataNode,
+    pod: &DataPod,
+    credentials: &PublishCredentials,
+) -> Result<Operator, Status> {
+    let node = &resolve_data_node(client, node.clone()).await?;
+    let mut cfg = data_node_config(client, node).await?;
+    cfg.extend(credentials.secrets.clone());
+
+    // DataPods of ClusterDataNodes only see the directory of their namespace
+    let namespace_dir = match &pod.spec.cluster_data_node_ref {
+        Some(_) => Some(cluster_data_pod_dir(pod)?),
         None => None,
-        Some(cred) => {
-            let secret_name = &cred.name;
-            let secret_ns = cred
-                .namespace
-                .as_ref()
-                .or(node.metadata.namespace.as_ref())
-                .ok_or_else(|| Status::invalid_argument("Secret namespace not provided"))?;
-            let secret_api: Api<Secret> = Api::namespaced(client.clone(), secret_ns);
-            let secret = secret_api
-                .get(secret_name)
-                .await
-                .map_err(|_| Status::not_found("Secret not found"))?;
-            Some(secret)
-        }
     };
-    if let Some(secret) = secret {
-        if let Some(data) = secret.data {
-            for (k, v) in data.into_iter() {
-                cfg.insert(
-                    k,
-                    std::str::from_utf8(&v.0)
-                        .map_err(|e| {
-                            Status::internal(format!("Failed to deserialize secret: {}", e))
-                        })?
-                        .to_string(),
-                );
-            }
+
+    #[cfg(feature = "services-git")]
+    if node.spec.backend.scheme() == "git" {
+        let status = pod.status.as_ref();
+        // Generated paths are only used to separate DataPods on writable backends.
+        // Repositories are read-only, the namespace directory is not part of them.
+        let subdirectory = if status.is_some_and(|status| status.generated_path) {
+            None
+        } else {
+            pod.spec.path.as_deref().map(|path| match &namespace_dir {
+                Some(dir) => {
+                    let relative = path.trim_start_matches('/');
+                    relative.strip_prefix(dir.as_str()).unwrap_or(relative)
+                }
+                None => path,
+            })
+        };
+        let commit = status.and_then(|status| status.commit.as_deref());
+        let op = crate::util::git::init_git(cfg, commit, subdirectory, &node.state_dir()).await?;
+        return apply_layers(op, node).await;
+    }
+
+    if node.spec.web_identity.is_some() && node.spec.backend.scheme() != "s3" {
+        return Err(Status::invalid_argument(
+            "Web identity is only supported for s3 DataNodes",
+        ));
+    }
+
+    // Pods get their own credentials and only see the prefix of their DataPod
+    #[cfg(feature = "services-s3")]
+    if let Some(web_identity) = node.spec.web_identity.as_ref() {
+        let token = credentials
+            .service_account_token
+            .as_deref()
+            .ok_or_else(|| Status::failed_precondition("No service account token provided"))?;
+
+        let http_client = load_http_client(client, node).await?;
+        let prefix = data_pod_prefix(&cfg, pod.spec.path.as_deref());
+        let temporary = assume_role(
+            web_identity,
+            &cfg,
+            token,
+            &credentials.session_name,
+            &prefix,
+            node.spec.read_only,
+            http_client.clone(),
+        )
+        .await?;
+        cfg.extend(temporary.into_config());
+        cfg.insert("root".to_string(), format!("/{}", prefix));
+
+        let op = build_operator(node, cfg, http_client).await?;
+        return apply_layers(op, node).await;
+    }
+
+    if namespace_dir.is_some() {
+        let prefix = data_pod_prefix(&cfg, pod.spec.path.as_deref());
+        cfg.insert("root".to_string(), format!("/{}", prefix));
+    }
+
+    let http_client = load_http_client(client, node).await?;
+    let op = build_operator(node, cfg, http_client).await?;
+    apply_layers(op, node).await
+}
+
+/// Namespace directory of a DataPod of a ClusterDataNode, `<namespace>/`. Its path has to stay
+/// below it, the controller moves the paths of new DataPods there.
+fn cluster_data_pod_dir(pod: &DataPod) -> Result<String, Status> {
+    let namespace = pod.namespace().unwrap_or_default();
+    let path = pod.spec.path.as_deref().unwrap_or_default();
+    if cluster_data_pod_path(&namespace, path).as_deref() != Some(path) {
+        return Err(Status::failed_precondition(format!(
+            "DataPod {} is not below the directory of its namespace: {}",
+            pod.name_any(),
+            path
+        )));
+    }
+    Ok(format!("{}/", namespace))
+}
+
+/// HTTP client with the TLS and proxy settings of a DataNode, if it has any
+async fn load_http_client(
+    client: &Client,
+    node: &DataNode,
+) -> Result<Option<BackendClient>, Status> {
+    match HttpSettings::load(client, node).await? {
+        Some(settings) => Ok(Some(settings.build_client()?)),
+        None => Ok(None),
+    }
+}
+
+/// Config of a DataNode merged from its ConfigMap, inline config, secret and
+/// the ConfigMaps referenced by backend specific options
+pub async fn data_node_config(
+    client: &Client,
+    node: &DataNode,
+) -> Result<HashMap<String, String>, Status> {
+    let mut cfg = HashMap::new();
+
+    if let Some(source) = node.spec.config_map_ref.as_ref() {
+        let namespace = source
+            .namespace
+            .as_ref()
+            .or(node.metadata.namespace.as_ref())
+            .ok_or_else(|| Status::invalid_argument("ConfigMap namespace not provided"))?;
+        let config_map_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+        let config_map = config_map_api
+            .get(&source.name)
+            .await
+            .map_err(|_| Status::not_found("ConfigMap not found"))?;
+        let data = config_map
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(k, v)| (k, v.into_bytes()))
+            .chain(
+                config_map
+                    .binary_data
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(k, v)| (k, v.0)),
+            )
+            .collect();
+        cfg.extend(select_keys(source, data, "ConfigMap")?);
+    }
+
+    cfg.extend(node.spec.config.clone());
+
+    if let Some(source) = node.spec.secret_ref.as_ref() {
+        let secret_ns = source
+            .namespace
+            .as_ref()
+            .or(node.metadata.namespace.as_ref())
+            .ok_or_else(|| Status::invalid_argument("Secret namespace not provided"))?;
+        let secret_api: Api<Secret> = Api::namespaced(client.clone(), secret_ns);
+        let secret = secret_api
+            .get(&source.name)
+            .await
+            .map_err(|_| Status::not_found("Secret not found"))?;
+        let data = secret
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(k, v)| (k, v.0))
+            .collect();
+        cfg.extend(select_keys(source, data, "Secret")?);
+    }
+
+    // Long URL lists can be kept in a ConfigMap next to the DataNode
+    #[cfg(feature = "services-urllist")]
+    let config_map = match node.spec.backend.scheme().as_str() {
+        "urllist" => cfg.remove(crate::util::url_list::CONFIG_MAP_KEY),
+        _ => None,
+    };
+    #[cfg(feature = "services-urllist")]
+    if let Some(config_map) = config_map {
+        let namespace = node
+            .metadata
+            .namespace
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("DataNode namespace not provided"))?;
+        let config_map_api: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
+        let entries = config_map_api
+            .get(&config_map)
+            .await
+            .map_err(|_| Status::not_found("ConfigMap not found"))?
+            .data
+            .and_then(|mut data| data.remove(crate::util::url_list::ENTRIES_KEY))
+            .ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "ConfigMap {} has no `{}` key",
+                    config_map,
+                    crate::util::url_list::ENTRIES_KEY
+                ))
+            })?;
+        cfg.insert(crate::util::url_list::ENTRIES_KEY.to_string(), entries);
+    }
+
+    // git does not use the HTTP client of OpenDAL and gets its TLS and proxy settings as options
+    #[cfg(feature = "services-git")]
+    let settings = match node.spec.backend.scheme().as_str() {
+        "git" => HttpSettings::load(client, node).await?,
+        _ => None,
+    };
+    #[cfg(feature = "services-git")]
+    if node.spec.backend.scheme() == "git" {
+        // Keys of the config must not smuggle in options reading or writing files of the node
+        cfg.retain(|key, _| !key.starts_with("http."));
+        if let Some(settings) = settings {
+            cfg.extend(settings.git_config(node)?);
         }
+    }
+
+    Ok(cfg)
+}
+
+/// Config options of the keys a Secret or ConfigMap reference selects. Values
+/// of selected keys must be UTF-8, other binary values are skipped.
+fn select_keys(
+    source: &ConfigSourceRef,
+    data: BTreeMap<String, Vec<u8>>,
+    kind: &str,
+) -> Result<HashMap<String, String>, Status> {
+    let mut data: BTreeMap<String, Vec<u8>> = match source.prefix.as_deref() {
+        Some(prefix) => data
+            .into_iter()
+            .filter_map(|(k, v)| k.strip_prefix(prefix).map(|k| (k.to_string(), v)))
+            .collect(),
+        None => data,
     };
 
-    let op = match node.spec.backend {
-        Backend::S3 => init_service::<services::S3>(cfg)?,
-        Backend::HTTP => init_service::<services::Http>(cfg)?,
+    if source.items.is_empty() {
+        return Ok(data
+            .into_iter()
+            .filter_map(|(k, v)| match String::from_utf8(v) {
+                Ok(v) => Some((k, v)),
+                Err(_) => {
+                    tracing::warn!("Skipping non UTF-8 key {} of {} {}", k, kind, source.name);
+                    None
+                }
+            })
+            .collect());
+    }
+
+    source
+        .items
+        .iter()
+        .map(|(key, option)| {
+            let value = data.remove(key).ok_or_else(|| {
+                Status::invalid_argument(format!("{} {} has no `{}` key", kind, source.name, key))
+            })?;
+            let value = String::from_utf8(value).map_err(|_| {
+                Status::invalid_argument(format!(
+                    "Key `{}` of {} {} is not UTF-8",
+                    key, kind, source.name
+                ))
+            })?;
+            Ok((option.clone(), value))
+        })
+        .collect()
+}
+
+/// Builds the operator of a registered service
+async fn build_operator(
+    node: &DataNode,
+    mut cfg: HashMap<String, String>,
+    http_client: Option<BackendClient>,
+) -> Result<Operator, Status> {
+    // SSH material can not be passed inline and is written to files instead
+    if node.spec.backend.scheme() == "sftp" {
+        materialize_credentials(node, &mut cfg)?;
+    }
+
+    // Host paths are confined to the directory the node plugin mounts from the host
+    #[cfg(feature = "services-fs")]
+    if node.spec.backend.scheme() == "fs" {
+        crate::util::fs_root::confine_config(&mut cfg)?;
+    }
+
+    // The layer cache is kept per DataNode
+    #[cfg(feature = "services-oci")]
+    if node.spec.backend.scheme() == "oci" {
+        return crate::util::oci::init_oci(cfg, http_client, &node.state_dir());
+    }
+
+    // Plain HTTP servers are made listable through a manifest or their autoindex pages
+    #[cfg(feature = "services-http")]
+    let index_source = if node.spec.backend.scheme() == "http" {
+        take_index_source(&mut cfg)?
+    } else {
+        None
     };
 
-    Ok(op)
+    let init = lookup_service(&node.spec.backend).ok_or_else(|| {
+        Status::unimplemented(format!("Unsupported backend scheme: {}", node.spec.backend))
+    })?;
+
+    #[cfg(feature = "services-http")]
+    if let Some(source) = index_source {
+        let index = build_index_layer(&cfg, &source, http_client.clone()).await?;
+        return Ok(init(cfg, http_client)?.layer(index));
+    }
+
+    init(cfg, http_client)
 }
 
-pub fn init_service<B: Builder>(cfg: HashMap<String, String>) -> Result<Operator, Status> {
+/// Builds an operator for one service from its config map and, if the
+/// DataNode has TLS or proxy settings, the HTTP client to use
+pub type ServiceInit =
+    fn(HashMap<String, String>, Option<BackendClient>) -> Result<Operator, Status>;
+
+/// Services compiled into this build, each enabled by the cargo feature of the same name
+const SERVICES: &[(&str, ServiceInit)] = &[
+    #[cfg(feature = "services-azblob")]
+    ("azblob", init_http_service::<services::Azblob>),
+    #[cfg(feature = "services-azdls")]
+    ("azdls", init_http_service::<services::Azdls>),
+    #[cfg(feature = "services-b2")]
+    ("b2", init_http_service::<services::B2>),
+    #[cfg(feature = "services-cos")]
+    ("cos", init_http_service::<services::Cos>),
+    #[cfg(feature = "services-fs")]
+    ("fs", init_service::<services::Fs>),
+    #[cfg(feature = "services-ftp")]
+    ("ftp", init_service::<services::Ftp>),
+    #[cfg(feature = "services-gcs")]
+    ("gcs", init_http_service::<services::Gcs>),
+    #[cfg(feature = "services-http")]
+    ("http", init_http_service::<services::Http>),
+    #[cfg(feature = "services-huggingface")]
+    ("huggingface", init_service::<services::Huggingface>),
+    #[cfg(feature = "services-memory")]
+    ("memory", init_service::<services::Memory>),
+    #[cfg(feature = "services-obs")]
+    ("obs", init_http_service::<services::Obs>),
+    #[cfg(feature = "services-oss")]
+    ("oss", init_http_service::<services::Oss>),
+    #[cfg(feature = "services-s3")]
+    ("s3", init_http_service::<services::S3>),
+    #[cfg(feature = "services-sftp")]
+    ("sftp", init_service::<services::Sftp>),
+    #[cfg(feature = "services-swift")]
+    ("swift", init_service::<services::Swift>),
+    #[cfg(feature = "services-urllist")]
+    ("urllist", crate::util::url_list::init_url_list),
+    #[cfg(feature = "services-webdav")]
+    ("webdav", init_http_service::<services::Webdav>),
+];
+
+/// Returns the constructor of a backend, if its scheme is compiled in
+pub fn lookup_service(backend: &Backend) -> Option<ServiceInit> {
+    let scheme = backend.scheme();
+    SERVICES
+        .iter()
+        .find(|(name, _)| *name == scheme)
+        .map(|(_, init)| *init)
+}
+
+/// Whether the scheme of a backend is compiled in
+pub fn is_supported(backend: &Backend) -> bool {
+    #[cfg(feature = "services-git")]
+    if backend.scheme() == "git" {
+        return true;
+    }
+    #[cfg(feature = "services-oci")]
+    if backend.scheme() == "oci" {
+        return true;
+    }
+    lookup_service(backend).is_some()
+}
+
+/// Schemes of all services compiled into this build
+pub fn supported_schemes() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut schemes: Vec<&'static str> = SERVICES.iter().map(|(name, _)| *name).collect();
+    #[cfg(feature = "services-git")]
+    schemes.push("git");
+    #[cfg(feature = "services-oci")]
+    schemes.push("oci");
+    schemes.sort_unstable();
+    schemes
+}
+
+/// Builds a service that does not talk HTTP, like fs or sftp
+pub fn init_service<B: Builder>(
+    cfg: HashMap<String, String>,
+    http_client: Option<BackendClient>,
+) -> Result<Operator, Status> {
+    if http_client.is_some() {
+        return Err(Status::invalid_argument(format!(
+            "Backend `{}` does not support HTTP settings",
+            B::SCHEME
+        )));
+    }
+
     let op = Operator::from_iter::<B>(cfg)
         .map_err(|e| Status::internal(format!("Failed to init openDAL operator: {}", e)))?
-        .layer(LoggingLayer::default())
-        .layer(RetryLayer::new())
         .finish();
 
     Ok(op)
 }
+
+/// Builders of services that talk HTTP and accept a custom client
+#[cfg(feature = "http-client")]
+pub trait HttpService: Builder {
+    fn with_http_client(self, client: HttpClient) -> Self;
+}
+
+macro_rules! http_services {
+    ($($feature:literal => $service:ty),* $(,)?) => {
+        $(
+            #[cfg(feature = $feature)]
+            impl HttpService for $service {
+                fn with_http_client(self, client: HttpClient) -> Self {
+                    self.http_client(client)
+                }
+            }
+        )*
+    };
+}
+
+http_services!(
+    "services-azblob" => services::Azblob,
+    "services-azdls" => services::Azdls,
+    "services-b2" => services::B2,
+    "services-cos" => services::Cos,
+    "services-gcs" => services::Gcs,
+    "services-http" => services::Http,
+    "services-obs" => services::Obs,
+    "services-oss" => services::Oss,
+    "services-s3" => services::S3,
+    "services-webdav" => services::Webdav,
+);
+
+/// Builds a service that talks HTTP, using the client of the DataNode if it has TLS or proxy settings
+#[cfg(feature = "http-client")]
+pub fn init_http_service<B>(
+    cfg: HashMap<String, String>,
+    http_client: Option<BackendClient>,
+) -> Result<Operator, Status>
+where
+    B: HttpService,
+    B::Config: Configurator<Builder = B>,
+{
+    let mut builder = B::Config::from_iter(cfg)
+        .map_err(|e| Status::internal(format!("Failed to init openDAL operator: {}", e)))?
+        .into_builder();
+    if let Some(http_client) = http_client {
+        builder = builder.with_http_client(HttpClient::with(http_client));
+    }
+
+    let op = Operator::new(builder)
+        .map_err(|e| Status::internal(format!("Failed to init openDAL operator: {}", e)))?
+        .finish();
+
+    Ok(op)
+}
+
+/// Index of the content below the root of an operator, listed once per generation of its DataNode
+async fn immutable_index(op: &Operator, node: &DataNode) -> Result<ImmutableIndexLayer, Status> {
+    let key = (node.state_dir(), op.info().root().to_string());
+    let generation = node.metadata.generation.unwrap_or_default();
+    if let Some((_, index)) = IMMUTABLE_INDEXES
+        .lock()
+        .unwrap()
+        .get(&key)
+        .filter(|(indexed, _)| *indexed == generation)
+    {
+        return Ok(index.clone());
+    }
+
+    let entries = op
+        .list_with("")
+        .recursive(true)
+        .await
+        .map_err(|e| Status::internal(format!("Failed to build immutable index: {}", e)))?;
+    let mut index = ImmutableIndexLayer::default();
+    for entry in entries {
+        index.insert(entry.path().to_string());
+    }
+    IMMUTABLE_INDEXES
+        .lock()
+        .unwrap()
+        .insert(key, (generation, index.clone()));
+
+    Ok(index)
+}
+
+/// Wraps an operator in the layers configured for its DataNode, from the
+/// backend outwards: immutable index, timeout, retry, concurrency limit,
+/// throttle and logging
+pub async fn apply_layers(mut op: Operator, node: &DataNode) -> Result<Operator, Status> {
+    let default_spec = LayerSpec::default();
+    let spec = node.spec.layers.as_ref().unwrap_or(&default_spec);
+
+    if spec.immutable_index {
+        let index = immutable_index(&op, node).await?;
+        op = op.layer(index);
+    }
+
+    if let Some(timeout) = spec.timeout.as_ref() {
+        let mut layer = TimeoutLayer::new();
+        if let Some(seconds) = timeout.operation_seconds {
+            layer = layer.with_timeout(Duration::from_secs(seconds));
+        }
+        if let Some(seconds) = timeout.io_seconds {
+            layer = layer.with_io_timeout(Duration::from_secs(seconds));
+        }
+        op = op.layer(layer);
+    }
+
+    let mut retry = RetryLayer::new();
+    if let Some(spec) = spec.retry.as_ref() {
+        if let Some(max_times) = spec.max_times {
+            retry = retry.with_max_times(max_times);
+        }
+        if let Some(min_delay) = spec.min_delay_ms {
+            retry = retry.with_min_delay(Duration::from_millis(min_delay));
+        }
+        if let Some(max_delay) = spec.max_delay_ms {
+            retry = retry.with_max_delay(Duration::from_millis(max_delay));
+        }
+        if let Some(factor) = spec.factor {
+            retry = retry.with_factor(factor);
+        }
+        if spec.jitter {
+            retry = retry.with_jitter();
+        }
+    }
+    op = op.layer(retry);
+
+    if let Some(permits) = spec.concurrent_limit {
+        if permits == 0 {
+            return Err(Status::invalid_argument(
+                "Concurrent limit must be positive",
+            ));
+        }
+        op = op.layer(ConcurrentLimitLayer::new(permits));
+    }
+
+    if let Some(throttle) = spec.throttle.as_ref() {
+        let bandwidth = quantity_to_bytes(&throttle.bandwidth)?;
+        let burst = match throttle.burst.as_ref() {
+            Some(burst) => quantity_to_bytes(burst)?,
+            None => bandwidth,
+        };
+        if bandwidth == 0 {
+            return Err(Status::invalid_argument(
+                "Throttle bandwidth must be positive",
+            ));
+        }
+        let clamp = |bytes: u64| u32::try_from(bytes).unwrap_or(u32::MAX);
+        op = op.layer(ThrottleLayer::new(
+            clamp(bandwidth),
+            clamp(burst.max(bandwidth)),
+        ));
+    }
+
+    Ok(op.layer(LoggingLayer::default()))
+}
@@ -0,0 +1,60 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tonic::Status;
+
+/// Tracks the volumes that currently have an operation in flight
+#[derive(Debug, Clone, Default)]
+pub struct OperationLocks {
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl OperationLocks {
+    /// Acquires the lock for a volume, fails with `ABORTED` if another operation
+    /// for the same volume is still in flight, as recommended by the CSI spec
+    pub fn try_lock(&self, volume_id: &str) -> Result<OperationGuard, Status> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if !in_flight.insert(volume_id.to_string()) {
+            return Err(Status::aborted(format!(
+                "An operation for volume '{}' is already in progress",
+                volume_id
+            )));
+        }
+        Ok(OperationGuard {
+            in_flight: self.in_flight.clone(),
+            volume_id: volume_id.to_string(),
+        })
+    }
+}
+
+/// Releases the volume lock when dropped
+#[derive(Debug)]
+pub struct OperationGuard {
+    in_flight: Arc<Mutex<HashSet<String>>>,
+    volume_id: String,
+}
+
+impl OperationGuard {
+    /// Runs the operation in its own task while holding the lock. The operation keeps
+    /// running if the gRPC caller times out, retries are aborted until it is finished.
+    pub async fn run_detached<F, T>(self, operation: F) -> Result<T, Status>
+    where
+        F: Future<Output = Result<T, Status>> + Send + 'static,
+        T: Send + 'static,
+    {
+        tokio::spawn(async move {
+            let _guard = self;
+            operation.await
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Operation task failed: {}", e)))?
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            in_flight.remove(&self.volume_id);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use opendal::Operator;
+use opendal::raw::{
+    Access, Accessor, AccessorInfo, OpCopy, OpCreateDir, OpList, OpPresign, OpRead, OpRename,
+    OpStat, OpWrite, RpCopy, RpCreateDir, RpDelete, RpList, RpPresign, RpRead, RpRename, RpStat,
+    RpWrite, oio,
+};
+use std::sync::{Arc, RwLock};
+
+/// Replaces the backend behind an operator while it is in use, e.g. after the
+/// credentials of a mounted DataNode were rotated. Operations started before
+/// a swap, like open readers of a FUSE mount, keep using the previous backend.
+#[derive(Debug, Clone)]
+pub struct OperatorSwap {
+    current: Arc<RwLock<Accessor>>,
+}
+
+impl OperatorSwap {
+    /// Wraps an operator, the returned operator always uses the latest backend
+    pub fn new(operator: Operator) -> (Self, Operator) {
+        let swap = OperatorSwap {
+            current: Arc::new(RwLock::new(operator.into_inner())),
+        };
+        let operator = Operator::from_inner(Arc::new(SwappableAccessor { swap: swap.clone() }));
+        (swap, operator)
+    }
+
+    /// Routes all further operations to a new backend
+    pub fn replace(&self, operator: Operator) {
+        *self.current.write().unwrap() = operator.into_inner();
+    }
+
+    fn current(&self) -> Accessor {
+        self.current.read().unwrap().clone()
+    }
+}
+
+#[derive(Debug)]
+struct SwappableAccessor {
+    swap: OperatorSwap,
+}
+
+impl Access for SwappableAccessor {
+    type Reader = oio::Reader;
+    type Writer = oio::Writer;
+    type Lister = oio::Lister;
+    type Deleter = oio::Deleter;
+    type BlockingReader = oio::BlockingReader;
+    type BlockingWriter = oio::BlockingWriter;
+    type BlockingLister = oio::BlockingLister;
+    type BlockingDeleter = oio::BlockingDeleter;
+
+    fn info(&self) -> Arc<AccessorInfo> {
+        self.swap.current().info()
+    }
+
+    async fn create_dir(&self, path: &str, args: OpCreateDir) -> opendal::Result<RpCreateDir> {
+        self.swap.current().create_dir(path, args).await
+    }
+
+    async fn stat(&self, path: &str, args: OpStat) -> opendal::Result<RpStat> {
+        self.swap.current().stat(path, args).await
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        self.swap.current().read(path, args).await
+    }
+
+    async fn write(&self, path: &str, args: OpWrite) -> opendal::Result<(RpWrite, Self::Writer)> {
+        self.swap.current().write(path, args).await
+    }
+
+    async fn delete(&self) -> opendal::Result<(RpDelete, Self::Deleter)> {
+        self.swap.current().delete().await
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        self.swap.current().list(path, args).await
+    }
+
+    async fn copy(&self, from: &str, to: &str, args: OpCopy) -> opendal::Result<RpCopy> {
+        self.swap.current().copy(from, to, args).await
+    }
+
+    async fn rename(&self, from: &str, to: &str, args: OpRename) -> opendal::Result<RpRename> {
+        self.swap.current().rename(from, to, args).await
+    }
+
+    async fn presign(&self, path: &str, args: OpPresign) -> opendal::Result<RpPresign> {
+        self.swap.current().presign(path, args).await
+    }
+
+    fn blocking_create_dir(&self, path: &str, args: OpCreateDir) -> opendal::Result<RpCreateDir> {
+        self.swap.current().blocking_create_dir(path, args)
+    }
+
+    fn blocking_stat(&self, path: &str, args: OpStat) -> opendal::Result<RpStat> {
+        self.swap.current().blocking_stat(path, args)
+    }
+
+    fn blocking_read(
+        &self,
+        path: &str,
+        args: OpRead,
+    ) -> opendal::Result<(RpRead, Self::BlockingReader)> {
+        self.swap.current().blocking_read(path, args)
+    }
+
+    fn blocking_write(
+        &self,
+        path: &str,
+        args: OpWrite,
+    ) -> opendal::Result<(RpWrite, Self::BlockingWriter)> {
+        self.swap.current().blocking_write(path, args)
+    }
+
+    fn blocking_delete(&self) -> opendal::Result<(RpDelete, Self::BlockingDeleter)> {
+        self.swap.current().blocking_delete()
+    }
+
+    fn blocking_list(
+        &self,
+        path: &str,
+        args: OpList,
+    ) -> opendal::Result<(RpList, Self::BlockingLister)> {
+        self.swap.current().blocking_list(path, args)
+    }
+
+    fn blocking_copy(&self, from: &str, to: &str, args: OpCopy) -> opendal::Result<RpCopy> {
+        self.swap.current().blocking_copy(from, to, args)
+    }
+
+    fn blocking_rename(&self, from: &str, to: &str, args: OpRename) -> opendal::Result<RpRename> {
+        self.swap.current().blocking_rename(from, to, args)
+    }
+}
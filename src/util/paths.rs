@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+/// Whether a path relative to the root of a DataNode stays below it: It must not be
+/// absolute or contain empty, `.` or `..` segments. A trailing slash marks directories.
+pub fn is_below_root(path: &str) -> bool {
+    let path = path.strip_suffix('/').unwrap_or(path);
+    !path.is_empty()
+        && path
+            .split('/')
+            .all(|segment| !matches!(segment, "" | "." | ".."))
+}
+
+/// Key prefix of a DataPod below the root of its DataNode, without leading and with a trailing slash
+pub fn data_pod_prefix(cfg: &HashMap<String, String>, path: Option<&str>) -> String {
+    let prefix = [
+        cfg.get("root").map(String::as_str).unwrap_or_default(),
+        path.unwrap_or_default(),
+    ]
+    .iter()
+    .flat_map(|part| part.split('/'))
+    .filter(|segment| !segment.is_empty())
+    .collect::<Vec<_>>()
+    .join("/");
+
+    if prefix.is_empty() {
+        prefix
+    } else {
+        format!("{}/", prefix)
+    }
+}
+
+/// Path of a DataPod of a ClusterDataNode, below the directory of the namespace of the
+/// DataPod. `None` if the path escapes the root of the DataNode.
+pub fn cluster_data_pod_path(namespace: &str, path: &str) -> Option<String> {
+    let relative = path.trim_start_matches('/');
+    if !is_below_root(relative) {
+        return None;
+    }
+    if relative.starts_with(&format!("{}/", namespace)) {
+        Some(format!("/{}", relative))
+    } else {
+        Some(format!("/{}/{}", namespace, relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_relative_paths() {
+        assert!(is_below_root("file"));
+        assert!(is_below_root("dir/"));
+        assert!(is_below_root("dir/sub/file.txt"));
+        assert!(is_below_root("dir/..file"));
+    }
+
+    #[test]
+    fn rejects_escaping_paths() {
+        for path in [
+            "",
+            "/",
+            "/etc/passwd",
+            "../file",
+            "dir/../../file",
+            "dir/./file",
+            "dir//file",
+            "..",
+            "./",
+        ] {
+            assert!(!is_below_root(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn builds_data_pod_prefixes() {
+        let cfg = HashMap::from([("root".to_string(), "/datasets/".to_string())]);
+        assert_eq!(data_pod_prefix(&cfg, Some("/pods//a")), "datasets/pods/a/");
+        assert_eq!(data_pod_prefix(&cfg, None), "datasets/");
+        assert_eq!(data_pod_prefix(&HashMap::new(), Some("/")), "");
+    }
+
+    #[test]
+    fn confines_cluster_data_pods_to_their_namespace() {
+        assert_eq!(cluster_data_pod_path("ns", "/data").unwrap(), "/ns/data");
+        assert_eq!(
+            cluster_data_pod_path("ns", "ns/data/").unwrap(),
+            "/ns/data/"
+        );
+        assert_eq!(
+            cluster_data_pod_path("ns", "/nsdata").unwrap(),
+            "/ns/nsdata"
+        );
+        assert_eq!(
+            cluster_data_pod_path("ns", "/other/ns").unwrap(),
+            "/ns/other/ns"
+        );
+        assert!(cluster_data_pod_path("ns", "/ns/../other").is_none());
+        assert!(cluster_data_pod_path("ns", "../other").is_none());
+    }
+}
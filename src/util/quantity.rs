@@ -0,0 +1,103 @@
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use tonic::Status;
+
+/// Converts a Kubernetes quantity (e.g. `10Gi`, `500M` or `1e9`) into bytes.
+/// Fractional results are rounded up to the next full byte.
+pub fn quantity_to_bytes(quantity: &Quantity) -> Result<u64, Status> {
+    let value = quantity.0.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| Status::invalid_argument(format!("Invalid quantity: {}", value)))?;
+
+    let multiplier = match suffix {
+        "" => 1.0,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024f64,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        exp if exp.starts_with(['e', 'E']) => {
+            let exp: i32 = exp[1..].parse().map_err(|_| {
+                Status::invalid_argument(format!("Invalid quantity exponent: {}", value))
+            })?;
+            10f64.powi(exp)
+        }
+        _ => {
+            return Err(Status::invalid_argument(format!(
+                "Invalid quantity suffix: {}",
+                value
+            )));
+        }
+    };
+
+    let bytes = (number * multiplier).ceil();
+    if !bytes.is_finite() || bytes < 0.0 || bytes > u64::MAX as f64 {
+        return Err(Status::invalid_argument(format!(
+            "Quantity out of range: {}",
+            value
+        )));
+    }
+
+    Ok(bytes as u64)
+}
+
+/// Converts a number of bytes into a plain Kubernetes quantity
+pub fn bytes_to_quantity(bytes: u64) -> Quantity {
+    Quantity(bytes.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(value: &str) -> Result<u64, Status> {
+        quantity_to_bytes(&Quantity(value.to_string()))
+    }
+
+    #[test]
+    fn parses_suffixes() {
+        assert_eq!(bytes("1024").unwrap(), 1024);
+        assert_eq!(bytes("5k").unwrap(), 5_000);
+        assert_eq!(bytes("500M").unwrap(), 500_000_000);
+        assert_eq!(bytes("10Gi").unwrap(), 10 * 1024 * 1024 * 1024);
+        assert_eq!(bytes("1Ti").unwrap(), 1024u64.pow(4));
+        assert_eq!(bytes(" 2Ki ").unwrap(), 2048);
+    }
+
+    #[test]
+    fn parses_exponents_and_fractions() {
+        assert_eq!(bytes("1e9").unwrap(), 1_000_000_000);
+        assert_eq!(bytes("1E3").unwrap(), 1_000);
+        assert_eq!(bytes("1.5Gi").unwrap(), 1_610_612_736);
+        // Fractions of a byte are rounded up
+        assert_eq!(bytes("1500m").unwrap(), 2);
+    }
+
+    #[test]
+    fn rejects_invalid_quantities() {
+        assert!(bytes("").is_err());
+        assert!(bytes("Gi").is_err());
+        assert!(bytes("10GB").is_err());
+        assert!(bytes("1e").is_err());
+        assert!(bytes("-1").is_err());
+        assert!(bytes("1e30").is_err());
+    }
+
+    #[test]
+    fn round_trips_bytes() {
+        assert_eq!(bytes(&bytes_to_quantity(123_456).0).unwrap(), 123_456);
+    }
+}
@@ -0,0 +1,353 @@
+use crate::resource::crd::DataNode;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tonic::Status;
+
+/// Secret key holding the PEM encoded private key
+pub const PRIVATE_KEY_KEY: &str = "private_key";
+/// Secret key holding entries in the OpenSSH known_hosts format
+pub const KNOWN_HOSTS_KEY: &str = "known_hosts";
+
+// Private keys, known hosts and ssh configs are written per DataNode below this directory
+const CREDENTIALS_DIR: &str = "/tmp/kubedal/sftp";
+// Name of the per DataNode ssh config, all of them are included by the config of the user
+const SSH_CONFIG_FILE: &str = "ssh_config";
+
+// Concurrent publishes must not both add the include to the config of the user
+static SSH_CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+/// Replaces the private key and known hosts in the config of an SFTP DataNode
+/// with what OpenDAL expects: A path to the key file and a host key strategy.
+///
+/// OpenDAL can not pass options to ssh, known hosts are therefore checked through a host
+/// alias per DataNode whose ssh config points to a known_hosts file of the DataNode only.
+pub fn materialize_credentials(
+    node: &DataNode,
+    cfg: &mut HashMap<String, String>,
+) -> Result<(), Status> {
+    let dir = PathBuf::from(CREDENTIALS_DIR).join(node.state_dir());
+
+    if let Some(private_key) = cfg.remove(PRIVATE_KEY_KEY) {
+        let key_path = dir.join("id");
+        // ssh rejects keys without a trailing newline
+        write_file(&key_path, &with_newline(&private_key), 0o600)?;
+        cfg.insert("key".to_string(), key_path.to_string_lossy().to_string());
+    }
+
+    if let Some(known_hosts) = cfg.remove(KNOWN_HOSTS_KEY) {
+        let endpoint = cfg
+            .get("endpoint")
+            .ok_or_else(|| Status::invalid_argument("SFTP DataNodes need an endpoint"))?;
+        let alias = host_alias(node);
+        let (endpoint, host_config) = pin_known_hosts(endpoint, &alias, &dir.join("known_hosts"))?;
+
+        write_file(&dir.join("known_hosts"), &with_newline(&known_hosts), 0o644)?;
+        write_file(&dir.join(SSH_CONFIG_FILE), &host_config, 0o644)?;
+        include_host_configs(&user_ssh_dir().join("config"))?;
+
+        cfg.insert("endpoint".to_string(), endpoint);
+        cfg.insert("known_hosts_strategy".to_string(), "strict".to_string());
+    }
+
+    Ok(())
+}
+
+/// Host name ssh resolves through the config of the DataNode. Namespaces are DNS labels
+/// without dots, so kind, namespace and name can not run into each other.
+fn host_alias(node: &DataNode) -> String {
+    let components: Vec<_> = node
+        .state_dir()
+        .iter()
+        .map(|component| component.to_string_lossy().into_owned())
+        .collect();
+    format!("kubedal-sftp.{}", components.join("."))
+}
+
+/// Endpoint connecting to the alias and the ssh config of the alias, which sends it to
+/// the host of the original endpoint and only trusts the keys in `known_hosts`
+fn pin_known_hosts(
+    endpoint: &str,
+    alias: &str,
+    known_hosts: &Path,
+) -> Result<(String, String), Status> {
+    let (prefix, host, port) = split_destination(endpoint);
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(Status::invalid_argument(format!(
+            "Invalid SFTP endpoint {}",
+            endpoint
+        )));
+    }
+
+    let mut config = format!("Host {}\n  HostName {}\n", alias, host);
+    if let Some(port) = port {
+        config.push_str(&format!("  Port {}\n", port));
+    }
+    config.push_str(&format!(
+        "  UserKnownHostsFile {}\n  GlobalKnownHostsFile none\n  StrictHostKeyChecking yes\n",
+        known_hosts.display()
+    ));
+    Ok((format!("{}{}", prefix, alias), config))
+}
+
+/// Splits an ssh destination (`[ssh://][user@]host[:port]`) into the part before the host,
+/// the host without brackets and the port
+fn split_destination(endpoint: &str) -> (&str, &str, Option<u16>) {
+    let rest_start = if endpoint.starts_with("ssh://") { 6 } else { 0 };
+    let host_start = rest_start + endpoint[rest_start..].rfind('@').map_or(0, |at| at + 1);
+    let destination = &endpoint[host_start..];
+
+    // Unbracketed IPv6 addresses have no port
+    let (host, port) = match destination.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (destination, None),
+        },
+        _ => (destination, None),
+    };
+
+    (
+        &endpoint[..host_start],
+        host.trim_start_matches('[').trim_end_matches(']'),
+        port,
+    )
+}
+
+/// Includes the configs of all DataNodes at the top of the ssh config of the user,
+/// later lines would be scoped to the last `Host` block of the file
+fn include_host_configs(config_path: &Path) -> Result<(), Status> {
+    let _lock = SSH_CONFIG_LOCK
+        .lock()
+        .map_err(|_| Status::internal("ssh config lock poisoned"))?;
+
+    let include = format!("Include {}/*/*/*/{}", CREDENTIALS_DIR, SSH_CONFIG_FILE);
+    let existing = fs::read_to_string(config_path).unwrap_or_default();
+    if existing.lines().any(|line| line.trim() == include) {
+        return Ok(());
+    }
+
+    write_file(config_path, &format!("{}\n{}", include, existing), 0o600)
+}
+
+fn user_ssh_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or("/root".to_string())).join(".ssh")
+}
+
+fn with_newline(content: &str) -> String {
+    match content.ends_with('\n') {
+        true => content.to_string(),
+        false => format!("{}\n", content),
+    }
+}
+
+fn write_file(path: &Path, content: &str, mode: u32) -> Result<(), Status> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            Status::internal(format!("Failed to create {}: {}", parent.display(), e))
+        })?;
+    }
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| Status::internal(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kube::ResourceExt;
+    use std::process::Command;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!("kubedal-sftp-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Options ssh resolves for the host from the given config, without connecting
+    fn resolve(config: &Path, host: &str) -> HashMap<String, String> {
+        let output = Command::new("ssh")
+            .arg("-G")
+            .arg("-F")
+            .arg(config)
+            .arg(host)
+            .output()
+            .expect("ssh client installed");
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_destinations() {
+        assert_eq!(split_destination("example.org"), ("", "example.org", None));
+        assert_eq!(
+            split_destination("example.org:2222"),
+            ("", "example.org", Some(2222))
+        );
+        assert_eq!(
+            split_destination("ssh://kubedal@example.org:22"),
+            ("ssh://kubedal@", "example.org", Some(22))
+        );
+        assert_eq!(
+            split_destination("kubedal@[::1]:2222"),
+            ("kubedal@", "::1", Some(2222))
+        );
+        assert_eq!(split_destination("::1"), ("", "::1", None));
+        assert_eq!(split_destination("[::1]"), ("", "::1", None));
+    }
+
+    #[test]
+    fn pins_known_hosts_of_the_data_node() {
+        let dir = TempDir::new();
+        let known_hosts = dir.0.join("known_hosts");
+        let (endpoint, host_config) = pin_known_hosts(
+            "ssh://kubedal@sftp.example.org:2222",
+            "kubedal-sftp.datanodes.a.b",
+            &known_hosts,
+        )
+        .unwrap();
+        assert_eq!(endpoint, "ssh://kubedal@kubedal-sftp.datanodes.a.b");
+
+        let config = dir.0.join("config");
+        fs::write(&config, host_config).unwrap();
+        let resolved = resolve(&config, "kubedal-sftp.datanodes.a.b");
+        assert_eq!(resolved["hostname"], "sftp.example.org");
+        assert_eq!(resolved["port"], "2222");
+        assert_eq!(resolved["stricthostkeychecking"], "true");
+        assert_eq!(
+            resolved["userknownhostsfile"],
+            known_hosts.to_string_lossy()
+        );
+        assert_eq!(resolved["globalknownhostsfile"], "none");
+
+        // Other hosts keep the defaults
+        let resolved = resolve(&config, "sftp.example.org");
+        assert_ne!(
+            resolved["userknownhostsfile"],
+            known_hosts.to_string_lossy()
+        );
+
+        assert!(pin_known_hosts("kubedal@", "alias", &known_hosts).is_err());
+        assert!(pin_known_hosts("exa mple.org", "alias", &known_hosts).is_err());
+    }
+
+    #[test]
+    fn keeps_host_aliases_of_data_nodes_apart() {
+        let node = |namespace: &str, name: &str| -> DataNode {
+            serde_json::from_value(serde_json::json!({
+                "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+                "kind": "DataNode",
+                "metadata": { "name": name, "namespace": namespace },
+                "spec": { "backend": "sftp", "read_only": true },
+            }))
+            .unwrap()
+        };
+        assert_eq!(
+            host_alias(&node("a-b", "c")),
+            "kubedal-sftp.datanodes.a-b.c"
+        );
+        assert_ne!(host_alias(&node("a-b", "c")), host_alias(&node("a", "b-c")));
+
+        let mut cluster_data_node = node("kube-system", "shared");
+        cluster_data_node.annotations_mut().insert(
+            crate::resource::crd::CLUSTER_DATA_NODE_ANNOTATION.to_string(),
+            "true".to_string(),
+        );
+        assert_eq!(
+            host_alias(&cluster_data_node),
+            "kubedal-sftp.clusterdatanodes.kube-system.shared"
+        );
+        assert_ne!(
+            host_alias(&cluster_data_node),
+            host_alias(&node("kube-system", "shared"))
+        );
+    }
+
+    #[test]
+    fn includes_host_configs_once_before_other_hosts() {
+        let dir = TempDir::new();
+        let config = dir.0.join("config");
+        fs::write(&config, "Host other\n  Port 2222\n").unwrap();
+
+        include_host_configs(&config).unwrap();
+        include_host_configs(&config).unwrap();
+        let content = fs::read_to_string(&config).unwrap();
+        assert_eq!(
+            content,
+            format!(
+                "Include {}/*/*/*/{}\nHost other\n  Port 2222\n",
+                CREDENTIALS_DIR, SSH_CONFIG_FILE
+            )
+        );
+    }
+
+    /// Runs against the stand-in server of yamls/webdav_sftp_example.yaml, e.g.
+    /// `docker run -p 2222:22 -v <key.pub>:/home/kubedal/.ssh/keys/id.pub:ro atmoz/sftp kubedal::1001::data`
+    /// with `KUBEDAL_SFTP_TEST_KEY` pointing to the private key and `KUBEDAL_SFTP_TEST_KNOWN_HOSTS`
+    /// to the output of `ssh-keyscan -p 2222 localhost`. Adds the include to `~/.ssh/config`.
+    #[tokio::test]
+    #[ignore = "needs a stand-in SFTP server"]
+    async fn checks_host_keys_against_stand_in_server() {
+        let endpoint = std::env::var("KUBEDAL_SFTP_TEST_ENDPOINT")
+            .unwrap_or("ssh://kubedal@localhost:2222".to_string());
+        let private_key =
+            fs::read_to_string(std::env::var("KUBEDAL_SFTP_TEST_KEY").unwrap()).unwrap();
+        let known_hosts =
+            fs::read_to_string(std::env::var("KUBEDAL_SFTP_TEST_KNOWN_HOSTS").unwrap()).unwrap();
+        // A valid key of another host under the name of the stand-in server
+        let host = known_hosts.split_whitespace().next().unwrap();
+        let foreign_known_hosts = format!(
+            "{} ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl",
+            host
+        );
+
+        let list = |name: &str, known_hosts: &str| {
+            let node: DataNode = serde_json::from_value(serde_json::json!({
+                "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+                "kind": "DataNode",
+                "metadata": { "name": name, "namespace": "sftp-test" },
+                "spec": { "backend": "sftp", "read_only": true },
+            }))
+            .unwrap();
+            let mut cfg = HashMap::from([
+                ("endpoint".to_string(), endpoint.clone()),
+                ("root".to_string(), "/data".to_string()),
+                (PRIVATE_KEY_KEY.to_string(), private_key.clone()),
+                (KNOWN_HOSTS_KEY.to_string(), known_hosts.to_string()),
+            ]);
+            materialize_credentials(&node, &mut cfg).unwrap();
+            async move {
+                opendal::Operator::via_iter(opendal::Scheme::Sftp, cfg)
+                    .unwrap()
+                    .list("/")
+                    .await
+            }
+        };
+
+        list("trusted", &known_hosts).await.unwrap();
+        assert!(list("foreign", &foreign_known_hosts).await.is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+
+/// Byte rate limiter that can be adjusted while a transfer is running.
+/// A limit of `None` disables throttling.
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    bytes_per_second: Arc<AtomicU64>,
+}
+
+impl Throttle {
+    pub fn new(limit: Option<u64>) -> Self {
+        let throttle = Throttle::default();
+        throttle.set_limit(limit);
+        throttle
+    }
+
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.bytes_per_second
+            .store(limit.unwrap_or_default(), Ordering::Relaxed);
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        match self.bytes_per_second.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Waits as long as transferring `bytes` takes at the current limit
+    pub async fn consume(&self, bytes: u64) {
+        if let Some(limit) = self.limit() {
+            tokio::time::sleep(Duration::from_secs_f64(bytes as f64 / limit as f64)).await;
+        }
+    }
+}
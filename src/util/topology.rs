@@ -0,0 +1,61 @@
+use crate::csi::Topology;
+use crate::resource::crd::DataNode;
+use k8s_openapi::api::core::v1::Node;
+use kube::api::ListParams;
+use kube::{Api, Client, ResourceExt};
+use std::collections::HashMap;
+use tonic::Status;
+
+/// Topology key reported by every node plugin with the name of its node. Volumes of
+/// DataNodes with a `nodeSelector` are restricted to the matching nodes through it.
+pub const NODE_TOPOLOGY_KEY: &str = "topology.kubedal.arunaengine.org/node";
+
+/// Topology segment of a node plugin, as reported by NodeGetInfo
+pub fn node_topology(node_id: &str) -> Topology {
+    Topology {
+        segments: HashMap::from([(NODE_TOPOLOGY_KEY.to_string(), node_id.to_string())]),
+    }
+}
+
+/// Nodes a volume of the DataNode can be published on, empty if it is reachable from all nodes
+pub async fn accessible_topology(
+    client: &Client,
+    data_node: &DataNode,
+) -> Result<Vec<Topology>, Status> {
+    let Some(selector) = data_node
+        .spec
+        .node_selector
+        .as_ref()
+        .filter(|selector| !selector.is_empty())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let labels = selector
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",");
+    let node_api: Api<Node> = Api::all(client.clone());
+    let nodes = node_api
+        .list(&ListParams::default().labels(&labels))
+        .await
+        .map_err(|e| {
+            tracing::error!("Error listing Nodes: {:?}", e);
+            Status::internal("Error listing Nodes")
+        })?;
+
+    if nodes.items.is_empty() {
+        return Err(Status::failed_precondition(format!(
+            "No node matches the nodeSelector {} of DataNode {}",
+            labels,
+            data_node.name_any()
+        )));
+    }
+
+    Ok(nodes
+        .items
+        .iter()
+        .map(|node| node_topology(&node.name_any()))
+        .collect())
+}
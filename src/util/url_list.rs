@@ -0,0 +1,298 @@
+use crate::util::paths::is_below_root;
+use crate::util::virtual_tree::{
+    Verifier, VirtualLister, check_status, is_dir, is_sha256, list_children, request_error,
+};
+use opendal::raw::{Access, AccessorInfo, OpList, OpRead, OpStat, RpList, RpRead, RpStat, oio};
+use opendal::{
+    Buffer, Capability, EntryMode, Error, ErrorKind, Metadata, Operator, OperatorBuilder, Scheme,
+};
+use reqwest::header::{CONTENT_LENGTH, RANGE};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tonic::Status;
+
+/// Config key holding the entries, one `<path> <url> [sha256]` per line
+pub const ENTRIES_KEY: &str = "entries";
+/// Config key naming a ConfigMap in the namespace of the DataNode that holds the entries
+pub const CONFIG_MAP_KEY: &str = "configMap";
+
+/// A file of the tree and where it is downloaded from
+#[derive(Debug, Clone, PartialEq)]
+struct UrlEntry {
+    url: String,
+    sha256: Option<String>,
+}
+
+/// Builds a read-only operator over files spread across many hosts
+pub fn init_url_list(
+    cfg: HashMap<String, String>,
+    http_client: Option<reqwest::Client>,
+) -> Result<Operator, Status> {
+    let entries = cfg.get(ENTRIES_KEY).ok_or_else(|| {
+        Status::invalid_argument(format!(
+            "URL list requires `{}` or `{}`",
+            ENTRIES_KEY, CONFIG_MAP_KEY
+        ))
+    })?;
+
+    let op = OperatorBuilder::new(UrlListAccessor::new(
+        parse_entries(entries)?,
+        http_client.unwrap_or_default(),
+    ))
+    .finish();
+
+    Ok(op)
+}
+
+/// Parses whitespace separated `<path> <url> [sha256]` lines, `#` starts a comment
+fn parse_entries(content: &str) -> Result<BTreeMap<String, UrlEntry>, Status> {
+    let mut files = BTreeMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (Some(path), Some(url)) = (fields.next(), fields.next()) else {
+            return Err(Status::invalid_argument(format!(
+                "Invalid URL list entry: {}",
+                line
+            )));
+        };
+        let sha256 = fields.next().map(str::to_ascii_lowercase);
+        if sha256.as_ref().is_some_and(|sum| !is_sha256(sum)) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid sha256 checksum for {}",
+                path
+            )));
+        }
+
+        let path = path.trim_start_matches('/');
+        if path.ends_with('/') || !is_below_root(path) {
+            return Err(Status::invalid_argument(format!(
+                "URL list entries must name files below the root: {}",
+                line
+            )));
+        }
+        if !reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+            return Err(Status::invalid_argument(format!(
+                "URL list entries must have an HTTP(S) URL: {}",
+                line
+            )));
+        }
+        files.insert(
+            path.to_string(),
+            UrlEntry {
+                url: url.to_string(),
+                sha256,
+            },
+        );
+    }
+
+    Ok(files)
+}
+
+#[derive(Debug)]
+struct UrlListAccessor {
+    client: reqwest::Client,
+    files: BTreeMap<String, UrlEntry>,
+    info: Arc<AccessorInfo>,
+}
+
+impl UrlListAccessor {
+    fn new(files: BTreeMap<String, UrlEntry>, client: reqwest::Client) -> Self {
+        let mut info = AccessorInfo::default();
+        info.set_scheme(Scheme::Custom("urllist"))
+            .set_root("/")
+            .set_native_capability(Capability {
+                stat: true,
+                read: true,
+                list: true,
+                list_with_recursive: true,
+                ..Default::default()
+            });
+
+        Self {
+            client,
+            files,
+            info: Arc::new(info),
+        }
+    }
+
+    fn entry(&self, path: &str) -> opendal::Result<&UrlEntry> {
+        self.files.get(path).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, "path is not part of the URL list")
+                .with_context("path", path)
+        })
+    }
+}
+
+impl Access for UrlListAccessor {
+    type Reader = UrlReader;
+    type Writer = ();
+    type Lister = VirtualLister;
+    type Deleter = ();
+    type BlockingReader = ();
+    type BlockingWriter = ();
+    type BlockingLister = ();
+    type BlockingDeleter = ();
+
+    fn info(&self) -> Arc<AccessorInfo> {
+        self.info.clone()
+    }
+
+    async fn stat(&self, path: &str, _args: OpStat) -> opendal::Result<RpStat> {
+        if path.ends_with('/') || path.is_empty() {
+            if !is_dir(&self.files, path) {
+                return Err(Error::new(ErrorKind::NotFound, "directory not found")
+                    .with_context("path", path));
+            }
+            return Ok(RpStat::new(Metadata::new(EntryMode::DIR)));
+        }
+
+        let entry = self.entry(path)?;
+        let response = self
+            .client
+            .head(&entry.url)
+            .send()
+            .await
+            .map_err(|e| request_error(e, &entry.url))?;
+        check_status(response.status(), &entry.url)?;
+
+        let mut metadata = Metadata::new(EntryMode::FILE);
+        if let Some(length) = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+        {
+            metadata.set_content_length(length);
+        }
+        Ok(RpStat::new(metadata))
+    }
+
+    async fn read(&self, path: &str, args: OpRead) -> opendal::Result<(RpRead, Self::Reader)> {
+        let entry = self.entry(path)?;
+
+        let mut request = self.client.get(&entry.url);
+        let range = args.range();
+        if !range.is_full() {
+            request = request.header(RANGE, range.to_header());
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| request_error(e, &entry.url))?;
+        check_status(response.status(), &entry.url)?;
+
+        // Only complete downloads, e.g. when populating a cache, can be verified
+        let verifier = if range.is_full() {
+            Verifier::new(path, None, entry.sha256.as_deref())
+        } else {
+            None
+        };
+
+        Ok((
+            RpRead::new(),
+            UrlReader {
+                path: path.to_string(),
+                response,
+                verifier,
+            },
+        ))
+    }
+
+    async fn list(&self, path: &str, args: OpList) -> opendal::Result<(RpList, Self::Lister)> {
+        let dir = if path == "/" { "" } else { path };
+        Ok((
+            RpList::default(),
+            VirtualLister::new(list_children(&self.files, dir, args.recursive(), |_| {
+                Metadata::new(EntryMode::FILE)
+            })),
+        ))
+    }
+}
+
+/// Streams a file and verifies its sha256 checksum once complete
+pub struct UrlReader {
+    path: String,
+    response: reqwest::Response,
+    verifier: Option<Verifier>,
+}
+
+impl oio::Read for UrlReader {
+    async fn read(&mut self) -> opendal::Result<Buffer> {
+        let chunk = self.response.chunk().await.map_err(|e| {
+            Error::new(ErrorKind::Unexpected, "failed to read response body")
+                .with_context("path", &self.path)
+                .set_source(e)
+        })?;
+
+        match chunk {
+            Some(bytes) => {
+                if let Some(verifier) = self.verifier.as_mut() {
+                    verifier.update(&bytes);
+                }
+                Ok(Buffer::from(bytes))
+            }
+            None => {
+                if let Some(verifier) = self.verifier.take() {
+                    verifier.finish()?;
+                }
+                Ok(Buffer::new())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn parses_entries() {
+        let files = parse_entries(&format!(
+            "# weights\n/models/a.bin https://a.example.org/a.bin {}\n\n  b.txt   http://b.example.org/b.txt  \n",
+            SHA256.to_uppercase()
+        ))
+        .unwrap();
+        assert_eq!(
+            files,
+            BTreeMap::from([
+                (
+                    "b.txt".to_string(),
+                    UrlEntry {
+                        url: "http://b.example.org/b.txt".to_string(),
+                        sha256: None,
+                    }
+                ),
+                (
+                    "models/a.bin".to_string(),
+                    UrlEntry {
+                        url: "https://a.example.org/a.bin".to_string(),
+                        sha256: Some(SHA256.to_string()),
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        for entries in [
+            "a.txt",
+            "a.txt https://example.org/a.txt abc",
+            "dir/ https://example.org/dir/",
+            "../a.txt https://example.org/a.txt",
+            "dir/../../a.txt https://example.org/a.txt",
+            "dir//a.txt https://example.org/a.txt",
+            "./a.txt https://example.org/a.txt",
+            "a.txt file:///etc/passwd",
+            "a.txt example.org/a.txt",
+        ] {
+            assert!(parse_entries(entries).is_err(), "{}", entries);
+        }
+    }
+}
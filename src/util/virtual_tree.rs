@@ -0,0 +1,153 @@
+use opendal::raw::oio;
+use opendal::{EntryMode, Error, ErrorKind, Metadata};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Entries below a directory of a tree given by its file paths, either direct
+/// children or all descendants. Directories are derived from the paths.
+pub fn list_children<V>(
+    files: &BTreeMap<String, V>,
+    dir: &str,
+    recursive: bool,
+    metadata: impl Fn(&V) -> Metadata,
+) -> Vec<oio::Entry> {
+    let mut dirs = BTreeSet::new();
+    let mut entries = Vec::new();
+
+    for (path, value) in files.range(dir.to_string()..) {
+        let Some(relative) = path.strip_prefix(dir) else {
+            break;
+        };
+        let mut parts: Vec<&str> = relative.split('/').collect();
+        parts.pop();
+
+        // Every parent directory below `dir` is an entry of its own
+        let depth = if recursive {
+            parts.len()
+        } else {
+            parts.len().min(1)
+        };
+        for index in 0..depth {
+            let sub_dir = format!("{}{}/", dir, parts[..=index].join("/"));
+            if dirs.insert(sub_dir.clone()) {
+                entries.push(oio::Entry::new(&sub_dir, Metadata::new(EntryMode::DIR)));
+            }
+        }
+        if recursive || parts.is_empty() {
+            entries.push(oio::Entry::new(path, metadata(value)));
+        }
+    }
+
+    entries
+}
+
+/// Whether a path is the root or a directory containing at least one file
+pub fn is_dir<V>(files: &BTreeMap<String, V>, path: &str) -> bool {
+    path.is_empty()
+        || path == "/"
+        || files
+            .range(path.to_string()..)
+            .next()
+            .is_some_and(|(file, _)| file.starts_with(path))
+}
+
+/// Lists precomputed entries
+pub struct VirtualLister {
+    entries: Vec<oio::Entry>,
+}
+
+impl VirtualLister {
+    pub fn new(entries: Vec<oio::Entry>) -> Self {
+        Self {
+            entries: entries.into_iter().rev().collect(),
+        }
+    }
+}
+
+impl oio::List for VirtualLister {
+    async fn next(&mut self) -> opendal::Result<Option<oio::Entry>> {
+        Ok(self.entries.pop())
+    }
+}
+
+/// Maps failed requests to temporary errors, so that they are retried
+pub fn request_error(e: reqwest::Error, url: &str) -> Error {
+    Error::new(ErrorKind::Unexpected, "request failed")
+        .with_context("url", url)
+        .set_temporary()
+        .set_source(e)
+}
+
+/// Maps HTTP error statuses to the matching OpenDAL error kinds
+pub fn check_status(status: StatusCode, url: &str) -> opendal::Result<()> {
+    let kind = match status {
+        status if status.is_success() => return Ok(()),
+        StatusCode::NOT_FOUND => ErrorKind::NotFound,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ErrorKind::PermissionDenied,
+        StatusCode::RANGE_NOT_SATISFIABLE => ErrorKind::RangeNotSatisfied,
+        _ => ErrorKind::Unexpected,
+    };
+    let error = Error::new(kind, format!("unexpected status {}", status)).with_context("url", url);
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(error.set_temporary());
+    }
+    Err(error)
+}
+
+/// Whether a value is a hex encoded sha256 checksum
+pub fn is_sha256(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Checks a complete download against the size and sha256 checksum it is known to have
+pub struct Verifier {
+    path: String,
+    size: Option<u64>,
+    sha256: Option<(Sha256, String)>,
+    read: u64,
+}
+
+impl Verifier {
+    /// `None` if neither the size nor the checksum are known
+    pub fn new(path: &str, size: Option<u64>, sha256: Option<&str>) -> Option<Self> {
+        if size.is_none() && sha256.is_none() {
+            return None;
+        }
+        Some(Self {
+            path: path.to_string(),
+            size,
+            sha256: sha256.map(|sum| (Sha256::new(), sum.to_ascii_lowercase())),
+            read: 0,
+        })
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.read += bytes.len() as u64;
+        if let Some((hasher, _)) = self.sha256.as_mut() {
+            hasher.update(bytes);
+        }
+    }
+
+    /// Called once the download is complete
+    pub fn finish(self) -> opendal::Result<()> {
+        if let Some(size) = self.size.filter(|size| *size != self.read) {
+            return Err(Error::new(ErrorKind::Unexpected, "size mismatch")
+                .with_context("path", &self.path)
+                .with_context("expected", size)
+                .with_context("actual", self.read));
+        }
+        if let Some((hasher, expected)) = self.sha256 {
+            let actual = format!("{:x}", hasher.finalize());
+            if actual != expected {
+                return Err(
+                    Error::new(ErrorKind::Unexpected, "sha256 checksum mismatch")
+                        .with_context("path", &self.path)
+                        .with_context("expected", expected)
+                        .with_context("actual", actual),
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::resource::crd::MountAccess;
+use crate::util::quantity::quantity_to_bytes;
+use crate::util::volume_context::{
+    CACHE_ASYNC_KEY, CACHE_REUSE_KEY, MOUNT_KEY, THROUGHPUT_KEY, VolumeContext, parse_bool,
+    parse_mount,
+};
+use k8s_openapi::api::core::v1::PersistentVolume;
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::{Api, Client, ResourceExt};
+use std::collections::{BTreeMap, HashMap};
+use tonic::Status;
+
+/// Volume attributes that can be changed after creation, e.g. through a VolumeAttributesClass.
+/// Unset attributes keep their current value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MutableAttributes {
+    /// Mount mode and access, applied on the next publish
+    pub mount: Option<MountAccess>,
+    /// Reuse already cached files on the next publish instead of downloading them again
+    pub cache_reuse: Option<bool>,
+    /// Populate the cache in the background on the next publish
+    pub cache_async: Option<bool>,
+    /// Throughput limit in bytes per second, `0` disables the limit
+    pub throughput: Option<u64>,
+}
+
+impl MutableAttributes {
+    /// Parses CSI mutable parameters, unknown keys are rejected
+    pub fn from_parameters(parameters: &HashMap<String, String>) -> Result<Self, Status> {
+        if let Some(key) = parameters.keys().find(|key| {
+            ![MOUNT_KEY, CACHE_REUSE_KEY, CACHE_ASYNC_KEY, THROUGHPUT_KEY].contains(&key.as_str())
+        }) {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported mutable parameter: {}",
+                key
+            )));
+        }
+        Self::from_context(parameters)
+    }
+
+    /// Parses the attributes from a volume context or annotations, other keys are ignored
+    pub fn from_context<'a>(
+        context: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Result<Self, Status> {
+        let mut attributes = MutableAttributes::default();
+        for (key, value) in context {
+            match key.as_str() {
+                MOUNT_KEY => attributes.mount = Some(parse_mount(value)?),
+                CACHE_REUSE_KEY => attributes.cache_reuse = Some(parse_bool(key, value)?),
+                CACHE_ASYNC_KEY => attributes.cache_async = Some(parse_bool(key, value)?),
+                THROUGHPUT_KEY => {
+                    attributes.throughput = Some(quantity_to_bytes(&Quantity(value.clone()))?);
+                }
+                _ => {}
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Overrides all attributes that are set in `other`
+    pub fn merge(&mut self, other: MutableAttributes) {
+        if other.mount.is_some() {
+            self.mount = other.mount;
+        }
+        if other.cache_reuse.is_some() {
+            self.cache_reuse = other.cache_reuse;
+        }
+        if other.cache_async.is_some() {
+            self.cache_async = other.cache_async;
+        }
+        if other.throughput.is_some() {
+            self.throughput = other.throughput;
+        }
+    }
+
+    /// Throughput limit, `None` if unlimited
+    pub fn throughput_limit(&self) -> Option<u64> {
+        self.throughput.filter(|limit| *limit > 0)
+    }
+
+    pub fn into_context(self) -> BTreeMap<String, String> {
+        let mut context = BTreeMap::new();
+        if let Some(mount) = self.mount {
+            context.insert(MOUNT_KEY.to_string(), mount.to_string());
+        }
+        if let Some(cache_reuse) = self.cache_reuse {
+            context.insert(CACHE_REUSE_KEY.to_string(), cache_reuse.to_string());
+        }
+        if let Some(cache_async) = self.cache_async {
+            context.insert(CACHE_ASYNC_KEY.to_string(), cache_async.to_string());
+        }
+        if let Some(throughput) = self.throughput {
+            context.insert(THROUGHPUT_KEY.to_string(), throughput.to_string());
+        }
+        context
+    }
+}
+
+/// Looks up the PersistentVolume backing a CSI volume handle, volume ids are the
+/// names of their PersistentVolumes
+pub async fn find_persistent_volume(
+    client: Client,
+    volume_id: &str,
+) -> Result<Option<PersistentVolume>, Status> {
+    let pv_api: Api<PersistentVolume> = Api::all(client);
+    let pv = pv_api.get_opt(volume_id).await.map_err(|e| {
+        tracing::error!("Error getting PersistentVolume: {:?}", e);
+        Status::internal("Error getting PersistentVolume")
+    })?;
+
+    Ok(pv.filter(|pv| {
+        pv.spec
+            .as_ref()
+            .and_then(|spec| spec.csi.as_ref())
+            .is_some_and(|csi| csi.volume_handle == volume_id)
+    }))
+}
+
+/// Current attributes of a PersistentVolume: Modifications stored as annotations
+/// override the attributes the volume was created with.
+pub fn persistent_volume_attributes(pv: &PersistentVolume) -> Result<MutableAttributes, Status> {
+    let mut attributes = match pv
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.csi.as_ref())
+        .and_then(|csi| csi.volume_attributes.as_ref())
+    {
+        Some(volume_attributes) => MutableAttributes::from_context(volume_attributes)?,
+        None => MutableAttributes::default(),
+    };
+    attributes.merge(MutableAttributes::from_context(pv.annotations())?);
+    Ok(attributes)
+}
+
+/// Context of the volume of a PersistentVolume, as created with the modifications of its
+/// annotations applied. It outlives the controller, unlike the volumes it keeps in memory.
+pub fn persistent_volume_context(pv: &PersistentVolume) -> Result<VolumeContext, Status> {
+    let volume_attributes: HashMap<String, String> = pv
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.csi.as_ref())
+        .and_then(|csi| csi.volume_attributes.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let mut context = VolumeContext::parse(&volume_attributes)?;
+    context.apply(persistent_volume_attributes(pv)?);
+    Ok(context)
+}
@@ -0,0 +1,440 @@
+use crate::resource::crd::{ClusterDataNode, DataNode, MountAccess};
+use crate::util::quantity::quantity_to_bytes;
+use crate::util::volume_attributes::MutableAttributes;
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::Resource;
+use kube::api::ObjectMeta;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use tonic::Status;
+
+/// Version of the volume context layout written by this driver
+pub const CONTEXT_VERSION: u32 = 1;
+
+pub const VERSION_KEY: &str = "kubedal.arunaengine.org/context-version";
+pub const DATA_NODE_NAME_KEY: &str = "kubedal.arunaengine.org/data-node-name";
+pub const DATA_NODE_NAMESPACE_KEY: &str = "kubedal.arunaengine.org/data-node-namespace";
+pub const DATA_NODE_KIND_KEY: &str = "kubedal.arunaengine.org/data-node-kind";
+pub const DATA_POD_NAME_KEY: &str = "kubedal.arunaengine.org/data-pod-name";
+pub const DATA_POD_NAMESPACE_KEY: &str = "kubedal.arunaengine.org/data-pod-namespace";
+pub const MOUNT_KEY: &str = "kubedal.arunaengine.org/mount";
+pub const CACHE_REUSE_KEY: &str = "kubedal.arunaengine.org/cache-reuse";
+pub const CACHE_ASYNC_KEY: &str = "kubedal.arunaengine.org/cache-async";
+pub const THROUGHPUT_KEY: &str = "kubedal.arunaengine.org/throughput";
+
+// Pod information passed by the kubelet if `podInfoOnMount` is enabled
+pub const POD_NAME_KEY: &str = "csi.storage.k8s.io/pod.name";
+pub const POD_NAMESPACE_KEY: &str = "csi.storage.k8s.io/pod.namespace";
+pub const POD_UID_KEY: &str = "csi.storage.k8s.io/pod.uid";
+pub const POD_SERVICE_ACCOUNT_KEY: &str = "csi.storage.k8s.io/serviceAccount.name";
+// Tokens passed by the kubelet for the `tokenRequests` of the CSIDriver
+pub const SERVICE_ACCOUNT_TOKENS_KEY: &str = "csi.storage.k8s.io/serviceAccount.tokens";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PodInfo {
+    pub name: String,
+    pub namespace: String,
+    pub service_account: String,
+    pub uid: String,
+}
+
+/// Typed volume context shared between the controller and node service.
+/// The controller serializes it on CreateVolume, the node parses it on publish.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeContext {
+    pub data_node_name: String,
+    pub data_node_namespace: String,
+    /// The data node is a ClusterDataNode, its namespace is not used
+    pub cluster_data_node: bool,
+    pub data_pod_name: String,
+    pub data_pod_namespace: String,
+    pub mount: MountAccess,
+    pub cache_reuse: bool,
+    /// Populate the cache in the background after mounting
+    pub cache_async: bool,
+    /// Throughput limit in bytes per second, `None` if unlimited
+    pub throughput: Option<u64>,
+    /// Only available on the node and if the kubelet passes pod information
+    pub pod: Option<PodInfo>,
+    /// Service account tokens of the pod by audience, only available on the node
+    pub service_account_tokens: HashMap<String, String>,
+}
+
+/// Token entry of the kubelet, the expiration is not needed
+#[derive(Debug, Deserialize)]
+struct ServiceAccountToken {
+    token: String,
+}
+
+impl VolumeContext {
+    /// Builds the context from the kubedal annotations of a PVC,
+    /// namespaces default to the namespace of the PVC
+    pub fn from_pvc_meta(pvc_meta: &ObjectMeta) -> Result<Self, Status> {
+        let annotations = pvc_meta.annotations.clone().unwrap_or_default();
+        let pvc_namespace = pvc_meta.namespace.clone().unwrap_or_default();
+
+        let annotation = |key: &str| {
+            annotations.get(key).cloned().ok_or_else(|| {
+                tracing::error!("Annotation {} not found", key);
+                Status::invalid_argument(format!("Annotation {} not found", key))
+            })
+        };
+
+        let mut context = VolumeContext {
+            data_node_name: annotation(DATA_NODE_NAME_KEY)?,
+            data_node_namespace: annotations
+                .get(DATA_NODE_NAMESPACE_KEY)
+                .cloned()
+                .unwrap_or(pvc_namespace.clone()),
+            cluster_data_node: annotations
+                .get(DATA_NODE_KIND_KEY)
+                .map(|value| parse_data_node_kind(value))
+                .transpose()?
+                .unwrap_or(false),
+            data_pod_name: annotation(DATA_POD_NAME_KEY)?,
+            data_pod_namespace: annotations
+                .get(DATA_POD_NAMESPACE_KEY)
+                .cloned()
+                .unwrap_or(pvc_namespace),
+            mount: parse_mount(&annotation(MOUNT_KEY)?)?,
+            cache_reuse: false,
+            cache_async: false,
+            throughput: None,
+            pod: None,
+            service_account_tokens: HashMap::new(),
+        };
+        context.apply(MutableAttributes::from_context(&annotations)?);
+
+        Ok(context)
+    }
+
+    /// Parses a volume context, rejecting missing or invalid kubedal keys
+    pub fn parse(context: &HashMap<String, String>) -> Result<Self, Status> {
+        // Contexts without version were written before versioning and share the layout of v1
+        let version = match context.get(VERSION_KEY) {
+            None => CONTEXT_VERSION,
+            Some(version) => version.parse::<u32>().map_err(|_| {
+                Status::invalid_argument(format!("Invalid {}: {}", VERSION_KEY, version))
+            })?,
+        };
+        if version != CONTEXT_VERSION {
+            return Err(Status::failed_precondition(format!(
+                "Unsupported volume context version {}, expected {}",
+                version, CONTEXT_VERSION
+            )));
+        }
+
+        let required = |key: &str| {
+            context
+                .get(key)
+                .filter(|value| !value.is_empty())
+                .cloned()
+                .ok_or_else(|| Status::invalid_argument(format!("{} not provided", key)))
+        };
+
+        Ok(VolumeContext {
+            data_node_name: required(DATA_NODE_NAME_KEY)?,
+            data_node_namespace: required(DATA_NODE_NAMESPACE_KEY)?,
+            cluster_data_node: context
+                .get(DATA_NODE_KIND_KEY)
+                .map(|value| parse_data_node_kind(value))
+                .transpose()?
+                .unwrap_or(false),
+            data_pod_name: required(DATA_POD_NAME_KEY)?,
+            data_pod_namespace: required(DATA_POD_NAMESPACE_KEY)?,
+            mount: parse_mount(&required(MOUNT_KEY)?)?,
+            cache_reuse: context
+                .get(CACHE_REUSE_KEY)
+                .map(|value| parse_bool(CACHE_REUSE_KEY, value))
+                .transpose()?
+                .unwrap_or(false),
+            cache_async: context
+                .get(CACHE_ASYNC_KEY)
+                .map(|value| parse_bool(CACHE_ASYNC_KEY, value))
+                .transpose()?
+                .unwrap_or(false),
+            throughput: context
+                .get(THROUGHPUT_KEY)
+                .map(|value| parse_throughput(value))
+                .transpose()?
+                .flatten(),
+            pod: parse_pod_info(context)?,
+            service_account_tokens: context
+                .get(SERVICE_ACCOUNT_TOKENS_KEY)
+                .map(|value| parse_service_account_tokens(value))
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Serializes the context, pod information and tokens are left to the kubelet
+    pub fn to_map(&self) -> HashMap<String, String> {
+        let mut context = HashMap::from([
+            (VERSION_KEY.to_string(), CONTEXT_VERSION.to_string()),
+            (DATA_NODE_NAME_KEY.to_string(), self.data_node_name.clone()),
+            (
+                DATA_NODE_NAMESPACE_KEY.to_string(),
+                self.data_node_namespace.clone(),
+            ),
+            (DATA_POD_NAME_KEY.to_string(), self.data_pod_name.clone()),
+            (
+                DATA_POD_NAMESPACE_KEY.to_string(),
+                self.data_pod_namespace.clone(),
+            ),
+            (MOUNT_KEY.to_string(), self.mount.to_string()),
+            (CACHE_REUSE_KEY.to_string(), self.cache_reuse.to_string()),
+            (CACHE_ASYNC_KEY.to_string(), self.cache_async.to_string()),
+        ]);
+        if let Some(throughput) = self.throughput {
+            context.insert(THROUGHPUT_KEY.to_string(), throughput.to_string());
+        }
+        if self.cluster_data_node {
+            context.insert(
+                DATA_NODE_KIND_KEY.to_string(),
+                ClusterDataNode::kind(&()).to_string(),
+            );
+        }
+        context
+    }
+
+    /// Applies modified attributes, unset attributes are kept
+    pub fn apply(&mut self, attributes: MutableAttributes) {
+        if let Some(mount) = attributes.mount {
+            self.mount = mount;
+        }
+        if let Some(cache_reuse) = attributes.cache_reuse {
+            self.cache_reuse = cache_reuse;
+        }
+        if let Some(cache_async) = attributes.cache_async {
+            self.cache_async = cache_async;
+        }
+        if let Some(throughput) = attributes.throughput {
+            self.throughput = Some(throughput).filter(|limit| *limit > 0);
+        }
+    }
+
+    /// Pod information, required to check the access of the pods service account
+    pub fn pod_info(&self) -> Result<&PodInfo, Status> {
+        self.pod.as_ref().ok_or_else(|| {
+            Status::failed_precondition(
+                "Pod information not provided, is podInfoOnMount enabled for the CSIDriver?",
+            )
+        })
+    }
+
+    /// Service account token of the pod for an audience
+    pub fn service_account_token(&self, audience: &str) -> Result<&str, Status> {
+        self.service_account_tokens
+            .get(audience)
+            .map(String::as_str)
+            .ok_or_else(|| {
+                Status::failed_precondition(format!(
+                    "No service account token for audience {}, is it part of the tokenRequests of the CSIDriver?",
+                    audience
+                ))
+            })
+    }
+}
+
+/// Annotations of a PVC referencing a DataPod on a DataNode
+pub fn pvc_annotations(
+    data_node_name: String,
+    data_node_namespace: String,
+    data_pod_name: String,
+    data_pod_namespace: String,
+    mount: &MountAccess,
+) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        (DATA_NODE_NAME_KEY.to_string(), data_node_name),
+        (DATA_NODE_NAMESPACE_KEY.to_string(), data_node_namespace),
+        (DATA_POD_NAME_KEY.to_string(), data_pod_name),
+        (DATA_POD_NAMESPACE_KEY.to_string(), data_pod_namespace),
+        (MOUNT_KEY.to_string(), mount.to_string()),
+    ])
+}
+
+pub(crate) fn parse_mount(value: &str) -> Result<MountAccess, Status> {
+    value.parse().map_err(Status::invalid_argument)
+}
+
+pub(crate) fn parse_bool(key: &str, value: &str) -> Result<bool, Status> {
+    value
+        .parse()
+        .map_err(|_| Status::invalid_argument(format!("Invalid {}: {}", key, value)))
+}
+
+/// Whether the kind of the data node is ClusterDataNode
+pub(crate) fn parse_data_node_kind(value: &str) -> Result<bool, Status> {
+    if value == DataNode::kind(&()) {
+        Ok(false)
+    } else if value == ClusterDataNode::kind(&()) {
+        Ok(true)
+    } else {
+        Err(Status::invalid_argument(format!(
+            "Invalid {}: {}, expected DataNode or ClusterDataNode",
+            DATA_NODE_KIND_KEY, value
+        )))
+    }
+}
+
+/// Parses a throughput quantity, `0` disables the limit
+pub(crate) fn parse_throughput(value: &str) -> Result<Option<u64>, Status> {
+    Ok(Some(quantity_to_bytes(&Quantity(value.to_string()))?).filter(|limit| *limit > 0))
+}
+
+fn parse_service_account_tokens(value: &str) -> Result<HashMap<String, String>, Status> {
+    serde_json::from_str::<HashMap<String, ServiceAccountToken>>(value)
+        .map(|tokens| {
+            tokens
+                .into_iter()
+                .map(|(audience, token)| (audience, token.token))
+                .collect()
+        })
+        .map_err(|e| {
+            Status::invalid_argument(format!("Invalid {}: {}", SERVICE_ACCOUNT_TOKENS_KEY, e))
+        })
+}
+
+fn parse_pod_info(context: &HashMap<String, String>) -> Result<Option<PodInfo>, Status> {
+    let keys = [
+        POD_NAME_KEY,
+        POD_NAMESPACE_KEY,
+        POD_SERVICE_ACCOUNT_KEY,
+        POD_UID_KEY,
+    ];
+    let values = keys.map(|key| context.get(key).filter(|value| !value.is_empty()));
+
+    if values.iter().all(Option::is_none) {
+        return Ok(None);
+    }
+    if let Some(index) = values.iter().position(Option::is_none) {
+        return Err(Status::failed_precondition(format!(
+            "Incomplete pod information, {} not provided",
+            keys[index]
+        )));
+    }
+
+    let [name, namespace, service_account, uid] =
+        values.map(|value| value.cloned().unwrap_or_default());
+    Ok(Some(PodInfo {
+        name,
+        namespace,
+        service_account,
+        uid,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> VolumeContext {
+        VolumeContext {
+            data_node_name: "node".to_string(),
+            data_node_namespace: "storage".to_string(),
+            cluster_data_node: false,
+            data_pod_name: "pod".to_string(),
+            data_pod_namespace: "team".to_string(),
+            mount: MountAccess::FuseReadWrite,
+            cache_reuse: true,
+            cache_async: false,
+            throughput: Some(1_000_000),
+            pod: None,
+            service_account_tokens: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_map() {
+        let context = context();
+        assert_eq!(VolumeContext::parse(&context.to_map()).unwrap(), context);
+
+        let cluster = VolumeContext {
+            cluster_data_node: true,
+            throughput: None,
+            mount: MountAccess::CacheReadOnly,
+            ..context
+        };
+        let map = cluster.to_map();
+        assert_eq!(map[DATA_NODE_KIND_KEY], "ClusterDataNode");
+        assert!(!map.contains_key(THROUGHPUT_KEY));
+        assert_eq!(VolumeContext::parse(&map).unwrap(), cluster);
+    }
+
+    #[test]
+    fn parses_pod_information_and_tokens() {
+        let mut map = context().to_map();
+        map.extend([
+            (POD_NAME_KEY.to_string(), "app".to_string()),
+            (POD_NAMESPACE_KEY.to_string(), "team".to_string()),
+            (POD_UID_KEY.to_string(), "1234".to_string()),
+            (POD_SERVICE_ACCOUNT_KEY.to_string(), "default".to_string()),
+            (
+                SERVICE_ACCOUNT_TOKENS_KEY.to_string(),
+                r#"{"sts.amazonaws.com":{"token":"abc","expirationTimestamp":"2030-01-01T00:00:00Z"}}"#
+                    .to_string(),
+            ),
+        ]);
+
+        let parsed = VolumeContext::parse(&map).unwrap();
+        assert_eq!(parsed.pod_info().unwrap().service_account, "default");
+        assert_eq!(
+            parsed.service_account_token("sts.amazonaws.com").unwrap(),
+            "abc"
+        );
+        assert!(parsed.service_account_token("other").is_err());
+
+        map.remove(POD_UID_KEY);
+        assert!(VolumeContext::parse(&map).is_err());
+    }
+
+    #[test]
+    fn accepts_unversioned_and_rejects_unknown_versions() {
+        let mut map = context().to_map();
+        map.remove(VERSION_KEY);
+        assert_eq!(VolumeContext::parse(&map).unwrap(), context());
+
+        map.insert(VERSION_KEY.to_string(), "2".to_string());
+        assert!(VolumeContext::parse(&map).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_keys() {
+        let mut map = context().to_map();
+        map.insert(DATA_POD_NAME_KEY.to_string(), String::new());
+        assert!(VolumeContext::parse(&map).is_err());
+
+        let mut map = context().to_map();
+        map.insert(MOUNT_KEY.to_string(), "nfs".to_string());
+        assert!(VolumeContext::parse(&map).is_err());
+
+        let mut map = context().to_map();
+        map.insert(DATA_NODE_KIND_KEY.to_string(), "Node".to_string());
+        assert!(VolumeContext::parse(&map).is_err());
+    }
+
+    #[test]
+    fn builds_from_pvc_annotations() {
+        let mut annotations = pvc_annotations(
+            "node".to_string(),
+            "storage".to_string(),
+            "pod".to_string(),
+            "team".to_string(),
+            &MountAccess::CacheReadWrite,
+        );
+        annotations.remove(DATA_POD_NAMESPACE_KEY);
+        annotations.insert(THROUGHPUT_KEY.to_string(), "10Mi".to_string());
+        let meta = ObjectMeta {
+            namespace: Some("pvc-namespace".to_string()),
+            annotations: Some(annotations),
+            ..Default::default()
+        };
+
+        let context = VolumeContext::from_pvc_meta(&meta).unwrap();
+        assert_eq!(context.data_node_namespace, "storage");
+        assert_eq!(context.data_pod_namespace, "pvc-namespace");
+        assert_eq!(context.mount, MountAccess::CacheReadWrite);
+        assert_eq!(context.throughput, Some(10 * 1024 * 1024));
+        assert!(!context.cluster_data_node);
+    }
+}
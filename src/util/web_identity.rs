@@ -0,0 +1,265 @@
+use crate::resource::crd::WebIdentitySpec;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+use serde_json::json;
+use std::collections::HashMap;
+use tonic::Status;
+
+const STS_VERSION: &str = "2011-06-15";
+const AWS_STS_ENDPOINT: &str = "https://sts.amazonaws.com";
+
+/// Temporary credentials of one pod, limited to the prefix of its DataPod
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemporaryCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+}
+
+impl TemporaryCredentials {
+    /// Options of the s3 service using the credentials
+    pub fn into_config(self) -> HashMap<String, String> {
+        HashMap::from([
+            ("access_key_id".to_string(), self.access_key_id),
+            ("secret_access_key".to_string(), self.secret_access_key),
+            ("session_token".to_string(), self.session_token),
+        ])
+    }
+}
+
+/// Exchanges the service account token of a pod via AssumeRoleWithWebIdentity
+/// for credentials whose session policy only allows access below the prefix
+pub async fn assume_role(
+    spec: &WebIdentitySpec,
+    cfg: &HashMap<String, String>,
+    token: &str,
+    session_name: &str,
+    prefix: &str,
+    read_only: bool,
+    http_client: Option<reqwest::Client>,
+) -> Result<TemporaryCredentials, Status> {
+    let bucket = cfg
+        .get("bucket")
+        .ok_or_else(|| Status::invalid_argument("Web identity requires a bucket"))?;
+    let endpoint = spec
+        .sts_endpoint
+        .as_deref()
+        .or(cfg.get("endpoint").map(String::as_str))
+        .unwrap_or(AWS_STS_ENDPOINT);
+
+    let policy = session_policy(bucket, prefix, read_only).to_string();
+    let duration = spec.duration_seconds.map(|seconds| seconds.to_string());
+    let mut params = vec![
+        ("Action", "AssumeRoleWithWebIdentity"),
+        ("Version", STS_VERSION),
+        ("WebIdentityToken", token),
+        ("RoleSessionName", session_name),
+        ("Policy", policy.as_str()),
+    ];
+    if let Some(role_arn) = spec.role_arn.as_deref() {
+        params.push(("RoleArn", role_arn));
+    }
+    if let Some(duration) = duration.as_deref() {
+        params.push(("DurationSeconds", duration));
+    }
+
+    let response = http_client
+        .unwrap_or_default()
+        .post(endpoint)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| Status::unavailable(format!("STS request to {} failed: {}", endpoint, e)))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| Status::unavailable(format!("Failed to read STS response: {}", e)))?;
+    if !status.is_success() {
+        let message = xml_value(&body, "Message").unwrap_or_else(|| status.to_string());
+        return Err(Status::permission_denied(format!(
+            "AssumeRoleWithWebIdentity failed: {}",
+            message
+        )));
+    }
+
+    let value = |tag: &str| {
+        xml_value(&body, tag)
+            .ok_or_else(|| Status::internal(format!("STS response has no {}", tag)))
+    };
+    Ok(TemporaryCredentials {
+        access_key_id: value("AccessKeyId")?,
+        secret_access_key: value("SecretAccessKey")?,
+        session_token: value("SessionToken")?,
+    })
+}
+
+/// Allows object access below the prefix and listing only within it
+fn session_policy(bucket: &str, prefix: &str, read_only: bool) -> serde_json::Value {
+    let object_actions = if read_only {
+        json!(["s3:GetObject"])
+    } else {
+        json!([
+            "s3:GetObject",
+            "s3:PutObject",
+            "s3:DeleteObject",
+            "s3:AbortMultipartUpload",
+            "s3:ListMultipartUploadParts"
+        ])
+    };
+
+    json!({
+        "Version": "2012-10-17",
+        "Statement": [
+            {
+                "Effect": "Allow",
+                "Action": object_actions,
+                "Resource": [format!("arn:aws:s3:::{}/{}*", bucket, prefix)]
+            },
+            {
+                "Effect": "Allow",
+                "Action": ["s3:ListBucket"],
+                "Resource": [format!("arn:aws:s3:::{}", bucket)],
+                "Condition": { "StringLike": { "s3:prefix": [format!("{}*", prefix)] } }
+            }
+        ]
+    })
+}
+
+/// Unescaped text of the first element with the given local name, `None` for malformed XML
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut value = None;
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start)
+                if value.is_none() && start.local_name().as_ref() == tag.as_bytes() =>
+            {
+                value = Some(String::new());
+            }
+            Event::Text(text) => {
+                if let Some(value) = value.as_mut() {
+                    value.push_str(&text.unescape().ok()?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(value) = value.as_mut() {
+                    value.push_str(std::str::from_utf8(&data).ok()?);
+                }
+            }
+            Event::End(end) if value.is_some() && end.local_name().as_ref() == tag.as_bytes() => {
+                return value.map(|value| value.trim().to_string());
+            }
+            Event::Eof => return None,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opendal::{ErrorKind, Operator, Scheme};
+
+    const RESPONSE: &str = r#"<AssumeRoleWithWebIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleWithWebIdentityResult>
+    <Credentials>
+      <AccessKeyId>ASIAEXAMPLE</AccessKeyId>
+      <SecretAccessKey>secret&amp;key</SecretAccessKey>
+      <SessionToken><![CDATA[token<with>markup]]></SessionToken>
+    </Credentials>
+  </AssumeRoleWithWebIdentityResult>
+</AssumeRoleWithWebIdentityResponse>"#;
+
+    #[test]
+    fn reads_xml_values() {
+        assert_eq!(xml_value(RESPONSE, "AccessKeyId").unwrap(), "ASIAEXAMPLE");
+        assert_eq!(
+            xml_value(RESPONSE, "SecretAccessKey").unwrap(),
+            "secret&key"
+        );
+        assert_eq!(
+            xml_value(RESPONSE, "SessionToken").unwrap(),
+            "token<with>markup"
+        );
+        assert_eq!(xml_value(RESPONSE, "Expiration"), None);
+
+        let error = "<ErrorResponse><Error><Code>AccessDenied</Code>\
+            <Message>Token &quot;expired&quot;</Message></Error></ErrorResponse>";
+        assert_eq!(xml_value(error, "Message").unwrap(), "Token \"expired\"");
+        assert_eq!(xml_value("<Message>unterminated", "Message"), None);
+    }
+
+    #[test]
+    fn limits_policies_to_the_prefix() {
+        let policy = session_policy("bucket", "pods/a/", true);
+        assert_eq!(
+            policy["Statement"][0]["Resource"][0],
+            "arn:aws:s3:::bucket/pods/a/*"
+        );
+        assert_eq!(policy["Statement"][0]["Action"], json!(["s3:GetObject"]));
+        assert_eq!(
+            policy["Statement"][1]["Condition"]["StringLike"]["s3:prefix"][0],
+            "pods/a/*"
+        );
+    }
+
+    /// Runs against a MinIO server that trusts an OpenID provider, e.g. started with
+    /// `MINIO_IDENTITY_OPENID_CONFIG_URL` and `MINIO_IDENTITY_OPENID_ROLE_POLICY=readwrite`,
+    /// with `KUBEDAL_STS_TEST_ENDPOINT`, `KUBEDAL_STS_TEST_BUCKET` (existing), optionally
+    /// `KUBEDAL_STS_TEST_ROLE_ARN` (printed by MinIO on startup) and `KUBEDAL_STS_TEST_TOKEN`,
+    /// an ID token of the provider for the configured client.
+    #[tokio::test]
+    #[ignore = "needs a MinIO server with an OpenID provider"]
+    async fn limits_minio_credentials_to_the_data_pod() {
+        let var = |name: &str| std::env::var(name).unwrap();
+        let endpoint = std::env::var("KUBEDAL_STS_TEST_ENDPOINT")
+            .unwrap_or("http://localhost:9000".to_string());
+        let cfg = HashMap::from([
+            ("bucket".to_string(), var("KUBEDAL_STS_TEST_BUCKET")),
+            ("endpoint".to_string(), endpoint),
+            ("region".to_string(), "us-east-1".to_string()),
+        ]);
+        let spec = WebIdentitySpec {
+            role_arn: std::env::var("KUBEDAL_STS_TEST_ROLE_ARN").ok(),
+            ..Default::default()
+        };
+
+        let operator = |prefix: &'static str, read_only: bool| {
+            let (spec, cfg, token) = (spec.clone(), cfg.clone(), var("KUBEDAL_STS_TEST_TOKEN"));
+            async move {
+                let credentials =
+                    assume_role(&spec, &cfg, &token, "kubedal-test", prefix, read_only, None)
+                        .await
+                        .unwrap();
+                let mut cfg = cfg;
+                cfg.extend(credentials.into_config());
+                Operator::via_iter(Scheme::S3, cfg).unwrap()
+            }
+        };
+
+        let writer = operator("kubedal-test/a/", false).await;
+        writer.write("kubedal-test/a/data", "a").await.unwrap();
+        assert_eq!(
+            writer.list("kubedal-test/a/").await.unwrap().len(),
+            1,
+            "listing within the prefix"
+        );
+        let denied = [
+            writer.write("kubedal-test/b/data", "b").await.unwrap_err(),
+            writer.list("kubedal-test/").await.unwrap_err(),
+        ];
+
+        let reader = operator("kubedal-test/a/", true).await;
+        assert_eq!(
+            reader.read("kubedal-test/a/data").await.unwrap().to_vec(),
+            b"a"
+        );
+        let read_only = reader.write("kubedal-test/a/other", "a").await.unwrap_err();
+
+        writer.delete("kubedal-test/a/data").await.unwrap();
+        for error in denied.iter().chain([&read_only]) {
+            assert_eq!(error.kind(), ErrorKind::PermissionDenied, "{}", error);
+        }
+    }
+}
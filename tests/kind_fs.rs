@@ -0,0 +1,168 @@
+//! Runs the whole CSI flow against an `fs` DataNode in a kind cluster created from
+//! yamls/kind.yaml with the driver deployed, see the comment at the top of that file.
+
+use k8s_openapi::api::core::v1::{PersistentVolumeClaim, Pod};
+use kube::api::{Api, DeleteParams, LogParams, PostParams};
+use kube::{Client, Resource};
+use kubedal::resource::crd::{DataNode, DataPod};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+/// Host directory kind mounts to `/srv/kubedal` on its worker
+const HOST_DATA: &str = "/tmp/kubedal-kind";
+const NAMESPACE: &str = "default";
+const NAME: &str = "kind-fs-test";
+const CONTENT: &str = "served from the host";
+
+fn from_json<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("valid manifest")
+}
+
+async fn create<K>(client: &Client, value: Value) -> Api<K>
+where
+    K: Resource<Scope = k8s_openapi::NamespaceResourceScope>
+        + Clone
+        + DeserializeOwned
+        + serde::Serialize
+        + std::fmt::Debug,
+    K::DynamicType: Default,
+{
+    let api: Api<K> = Api::namespaced(client.clone(), NAMESPACE);
+    // Leftovers of an aborted run
+    let _ = api.delete(NAME, &DeleteParams::default()).await;
+    for _ in 0..30 {
+        if api.get_opt(NAME).await.unwrap().is_none() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    api.create(&PostParams::default(), &from_json(value))
+        .await
+        .expect("create resource");
+    api
+}
+
+#[tokio::test]
+#[ignore = "needs a kind cluster with the driver deployed"]
+async fn mounts_fs_data_node() {
+    std::fs::create_dir_all(format!("{}/datasets/kind-fs-test", HOST_DATA)).unwrap();
+    std::fs::write(
+        format!("{}/datasets/kind-fs-test/hello.txt", HOST_DATA),
+        CONTENT,
+    )
+    .unwrap();
+
+    let client = Client::try_default()
+        .await
+        .expect("kubeconfig of the kind cluster");
+
+    let data_nodes = create::<DataNode>(
+        &client,
+        json!({
+            "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+            "kind": "DataNode",
+            "metadata": { "name": NAME, "namespace": NAMESPACE },
+            "spec": {
+                "backend": "fs",
+                "read_only": true,
+                "config": { "root": "/srv/kubedal/datasets" },
+                // Only the worker has the host directory mounted
+                "nodeSelector": { "kubernetes.io/hostname": "kind-worker" },
+            }
+        }),
+    )
+    .await;
+    let data_pods = create::<DataPod>(
+        &client,
+        json!({
+            "apiVersion": "kubedal.arunaengine.org/v1alpha1",
+            "kind": "DataPod",
+            "metadata": { "name": NAME, "namespace": NAMESPACE },
+            "spec": {
+                "path": "/kind-fs-test",
+                "dataNodeRef": { "name": NAME, "namespace": NAMESPACE },
+            }
+        }),
+    )
+    .await;
+    let pvcs = create::<PersistentVolumeClaim>(
+        &client,
+        json!({
+            "apiVersion": "v1",
+            "kind": "PersistentVolumeClaim",
+            "metadata": {
+                "name": NAME,
+                "namespace": NAMESPACE,
+                "annotations": {
+                    "kubedal.arunaengine.org/data-node-name": NAME,
+                    "kubedal.arunaengine.org/data-node-namespace": NAMESPACE,
+                    "kubedal.arunaengine.org/data-pod-name": NAME,
+                    "kubedal.arunaengine.org/data-pod-namespace": NAMESPACE,
+                    "kubedal.arunaengine.org/mount": "cache-read-only",
+                }
+            },
+            "spec": {
+                "accessModes": ["ReadOnlyMany"],
+                "storageClassName": "kubedal",
+                "resources": { "requests": { "storage": "1Gi" } },
+            }
+        }),
+    )
+    .await;
+    let pods = create::<Pod>(
+        &client,
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": NAME, "namespace": NAMESPACE },
+            "spec": {
+                "restartPolicy": "Never",
+                "containers": [{
+                    "name": "reader",
+                    "image": "busybox:1.37",
+                    "command": ["cat", "/data/hello.txt"],
+                    "volumeMounts": [{ "name": "data", "mountPath": "/data" }],
+                }],
+                "volumes": [{
+                    "name": "data",
+                    "persistentVolumeClaim": { "claimName": NAME, "readOnly": true },
+                }],
+            }
+        }),
+    )
+    .await;
+
+    let mut phase = None;
+    for _ in 0..90 {
+        let pod = pods.get(NAME).await.unwrap();
+        phase = pod.status.and_then(|status| status.phase);
+        if matches!(phase.as_deref(), Some("Succeeded" | "Failed")) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    let logs = pods.logs(NAME, &LogParams::default()).await;
+
+    for result in [
+        pods.delete(NAME, &DeleteParams::default())
+            .await
+            .map(|_| ()),
+        pvcs.delete(NAME, &DeleteParams::default())
+            .await
+            .map(|_| ()),
+        data_pods
+            .delete(NAME, &DeleteParams::default())
+            .await
+            .map(|_| ()),
+        data_nodes
+            .delete(NAME, &DeleteParams::default())
+            .await
+            .map(|_| ()),
+    ] {
+        result.expect("cleanup");
+    }
+
+    assert_eq!(phase.as_deref(), Some("Succeeded"), "logs: {:?}", logs);
+    assert_eq!(logs.unwrap(), CONTENT);
+}